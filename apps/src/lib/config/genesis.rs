@@ -42,6 +42,7 @@ pub mod genesis_config {
 
     use super::{
         EstablishedAccount, Genesis, ImplicitAccount, TokenAccount, Validator,
+        VpTemplate,
     };
     use crate::cli;
 
@@ -250,6 +251,10 @@ pub mod genesis_config {
         // Hashes of whitelisted txs array. `None` value or an empty array
         // disables whitelisting.
         pub tx_whitelist: Option<Vec<String>>,
+        // Names of the wasm VP definitions to store as VP templates, from
+        // which accounts can be initialized if their hash is in the
+        // `vp_whitelist`. (default: none)
+        pub vp_templates: Option<Vec<String>>,
     }
 
     #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -484,6 +489,29 @@ pub mod genesis_config {
         }
     }
 
+    fn load_vp_template(
+        name: &str,
+        wasm: &HashMap<String, WasmConfig>,
+    ) -> VpTemplate {
+        let template_config = wasm.get(name).unwrap_or_else(|| {
+            eprintln!("Unknown VP template WASM {}", name);
+            cli::safe_exit(1);
+        });
+
+        VpTemplate {
+            code_path: template_config.filename.to_owned(),
+            sha256: template_config
+                .sha256
+                .clone()
+                .unwrap_or_else(|| {
+                    eprintln!("Unknown VP template WASM sha256");
+                    cli::safe_exit(1);
+                })
+                .to_sha256_bytes()
+                .unwrap(),
+        }
+    }
+
     fn load_implicit(config: &ImplicitAccountConfig) -> ImplicitAccount {
         ImplicitAccount {
             public_key: config
@@ -528,6 +556,14 @@ pub mod genesis_config {
                 )
             })
             .collect();
+        let vp_templates = config
+            .parameters
+            .vp_templates
+            .as_ref()
+            .unwrap_or(&Vec::default())
+            .iter()
+            .map(|name| load_vp_template(name, &wasms))
+            .collect();
 
         let parameters = Parameters {
             epoch_duration: EpochDuration {
@@ -585,6 +621,7 @@ pub mod genesis_config {
             established_accounts: established_accounts.into_values().collect(),
             implicit_accounts: implicit_accounts.into_values().collect(),
             parameters,
+            vp_templates,
             pos_params,
             gov_params,
             treasury_params,
@@ -620,6 +657,7 @@ pub struct Genesis {
     pub established_accounts: Vec<EstablishedAccount>,
     pub implicit_accounts: Vec<ImplicitAccount>,
     pub parameters: Parameters,
+    pub vp_templates: Vec<VpTemplate>,
     pub pos_params: PosParams,
     pub gov_params: GovParams,
     pub treasury_params: TreasuryParams,
@@ -632,6 +670,7 @@ impl Genesis {
         self.token_accounts.sort();
         self.established_accounts.sort();
         self.implicit_accounts.sort();
+        self.vp_templates.sort();
     }
 }
 
@@ -722,6 +761,25 @@ pub struct ImplicitAccount {
     pub public_key: common::PublicKey,
 }
 
+#[derive(
+    Clone,
+    Debug,
+    BorshSerialize,
+    BorshDeserialize,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+/// Genesis validity predicate template definition
+pub struct VpTemplate {
+    /// Validity predicate template code WASM
+    pub code_path: String,
+    /// Expected SHA-256 hash of the validity predicate template wasm, which is
+    /// also the key of the template in storage
+    pub sha256: [u8; 32],
+}
+
 #[cfg(not(feature = "dev"))]
 pub fn genesis(base_dir: impl AsRef<Path>, chain_id: &ChainId) -> Genesis {
     let path = base_dir
@@ -856,6 +914,7 @@ pub fn genesis() -> Genesis {
         implicit_accounts,
         token_accounts,
         parameters,
+        vp_templates: vec![],
         pos_params: PosParams::default(),
         gov_params: GovParams::default(),
         treasury_params: TreasuryParams::default(),
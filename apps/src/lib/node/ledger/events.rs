@@ -4,6 +4,7 @@ use std::fmt::{self, Display};
 use std::ops::{Index, IndexMut};
 
 use anoma::ledger::governance::utils::ProposalEvent;
+use anoma::types::event::TxEvent;
 use anoma::types::ibc::IbcEvent;
 use anoma::types::transaction::{hash_tx, TxType};
 use borsh::BorshSerialize;
@@ -41,6 +42,8 @@ pub enum EventType {
     Ibc(String),
     // The proposal that has been executed
     Proposal,
+    // An event emitted by an applied transaction
+    Tx(String),
 }
 
 #[cfg(not(feature = "ABCI"))]
@@ -51,6 +54,7 @@ impl Display for EventType {
             EventType::Applied => write!(f, "applied"),
             EventType::Ibc(t) => write!(f, "{}", t),
             EventType::Proposal => write!(f, "proposal"),
+            EventType::Tx(t) => write!(f, "{}", t),
         }?;
         Ok(())
     }
@@ -64,6 +68,7 @@ impl Display for EventType {
             EventType::Applied => write!(f, "applied"),
             EventType::Ibc(t) => write!(f, "{}", t),
             EventType::Proposal => write!(f, "proposal"),
+            EventType::Tx(t) => write!(f, "{}", t),
         }?;
         Ok(())
     }
@@ -160,6 +165,16 @@ impl From<IbcEvent> for Event {
     }
 }
 
+impl From<TxEvent> for Event {
+    fn from(tx_event: TxEvent) -> Self {
+        Self {
+            event_type: EventType::Tx(tx_event.event_type),
+            level: EventLevel::Tx,
+            attributes: tx_event.attributes.into_iter().collect(),
+        }
+    }
+}
+
 impl From<ProposalEvent> for Event {
     fn from(proposal_event: ProposalEvent) -> Self {
         Self {
@@ -111,6 +111,7 @@ where
             let initialized_accounts = write_log.get_initialized_accounts();
             let changed_keys = write_log.get_keys();
             let ibc_event = write_log.take_ibc_event();
+            let events = write_log.take_events();
 
             Ok(TxResult {
                 gas_used,
@@ -118,6 +119,7 @@ where
                 vps_result,
                 initialized_accounts,
                 ibc_event,
+                events,
             })
         }
         _ => {
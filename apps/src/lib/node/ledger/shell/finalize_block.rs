@@ -226,6 +226,8 @@ where
             }
         }
 
+        self.apply_scheduled_txs(height);
+
         for processed_tx in &req.txs {
             let tx = if let Ok(tx) = Tx::try_from(processed_tx.tx.as_ref()) {
                 tx
@@ -348,14 +350,28 @@ where
             .map_err(Error::TxApply)
             {
                 Ok(result) => {
-                    if result.is_accepted() {
+                    let write_limit_err = if result.is_accepted() {
+                        self.write_log
+                            .commit_tx_limited(&mut self.storage)
+                            .err()
+                    } else {
+                        None
+                    };
+                    if let Some(err) = write_limit_err {
+                        tracing::info!(
+                            "transaction {} storage modification exceeded \
+                             the block's write limit: {}",
+                            tx_event["hash"],
+                            err
+                        );
+                        tx_event["code"] = ErrorCodes::InvalidTx.into();
+                    } else if result.is_accepted() {
                         tracing::info!(
                             "all VPs accepted transaction {} storage \
                              modification {:#?}",
                             tx_event["hash"],
                             result
                         );
-                        self.write_log.commit_tx();
                         if !tx_event.contains_key("code") {
                             tx_event["code"] = ErrorCodes::Ok.into();
                         }
@@ -364,6 +380,10 @@ where
                             let event = Event::from(ibc_event.clone());
                             response.events.push(event);
                         }
+                        // Add the events emitted by the tx in their order
+                        response.events.extend(
+                            result.events.iter().cloned().map(Event::from),
+                        );
                         match serde_json::to_string(
                             &result.initialized_accounts,
                         ) {
@@ -458,10 +478,86 @@ where
             .update_epoch(height, time)
             .expect("Must be able to update epoch");
 
+        self.storage
+            .prune_expired()
+            .expect("Must be able to prune expired values");
+
         self.slash();
         (height, new_epoch)
     }
 
+    /// Apply the transactions scheduled for the given height and delete them
+    /// from storage. The changes of a scheduled transaction are only committed
+    /// if it's accepted.
+    fn apply_scheduled_txs(&mut self, height: BlockHeight) {
+        let scheduled = self
+            .storage
+            .scheduled_txs(height)
+            .expect("Must be able to read the scheduled transactions");
+        for (key, scheduled) in scheduled {
+            let tx = Tx::new(scheduled.code, scheduled.data);
+            let tx_type = TxType::Decrypted(DecryptedTx::Decrypted(tx));
+            let tx_result = protocol::apply_tx(
+                tx_type,
+                0, /* this is used to compute the fee based on the code
+                    * size. We dont need it here. */
+                &mut BlockGasMeter::default(),
+                &mut self.write_log,
+                &self.storage,
+                &mut self.vp_wasm_cache,
+                &mut self.tx_wasm_cache,
+            );
+            match tx_result {
+                Ok(result) if result.is_accepted() => {
+                    match self.write_log.commit_tx_limited(&mut self.storage) {
+                        Ok(()) => tracing::info!(
+                            "Scheduled transaction {} was applied: {}",
+                            key,
+                            result
+                        ),
+                        Err(err) => tracing::info!(
+                            "Scheduled transaction {} exceeded the block's \
+                             write limit: {}",
+                            key,
+                            err
+                        ),
+                    }
+                }
+                Ok(result) => {
+                    tracing::info!(
+                        "Scheduled transaction {} was rejected: {}",
+                        key,
+                        result
+                    );
+                    self.write_log.drop_tx();
+                }
+                Err(err) => {
+                    tracing::info!(
+                        "Scheduled transaction {} failed: {}",
+                        key,
+                        err
+                    );
+                    self.write_log.drop_tx();
+                }
+            }
+            if let Some((scheduler, id)) = key.is_scheduled_tx() {
+                let index_key = Key::scheduled_tx_index(height, scheduler, id);
+                self.storage.delete(&index_key).expect(
+                    "Must be able to delete a scheduled transaction's index \
+                     entry",
+                );
+            }
+            self.storage
+                .delete(&key)
+                .expect("Must be able to delete a scheduled transaction");
+        }
+        self.storage
+            .delete(&Key::scheduled_tx_count(height))
+            .expect(
+                "Must be able to delete the count of scheduled transactions",
+            );
+    }
+
     /// If a new epoch begins, we update the response to include
     /// changes to the validator sets and consensus parameters
     fn update_epoch(&self, response: &mut shim::response::FinalizeBlock) {
@@ -83,6 +83,32 @@ where
         // Loaded VP code cache to avoid loading the same files multiple times
         let mut vp_code_cache: HashMap<String, Vec<u8>> = HashMap::default();
 
+        // Initialize genesis VP templates
+        for genesis::VpTemplate { code_path, sha256 } in genesis.vp_templates {
+            let vp_code = vp_code_cache
+                .get_or_insert_with(code_path.clone(), || {
+                    wasm_loader::read_wasm(&self.wasm_dir, &code_path)
+                });
+            let vp_code_hash = anoma::types::hash::Hash::sha256(&vp_code);
+
+            // In dev, we don't check the hash
+            #[cfg(feature = "dev")]
+            let _ = sha256;
+            #[cfg(not(feature = "dev"))]
+            assert_eq!(
+                vp_code_hash.0, sha256,
+                "Invalid VP template's sha256 hash for {}",
+                code_path
+            );
+
+            self.storage
+                .write(
+                    &parameters::storage::get_vp_template_key(&vp_code_hash),
+                    vp_code,
+                )
+                .unwrap();
+        }
+
         // Initialize genesis established accounts
         for genesis::EstablishedAccount {
             address,
@@ -51,8 +51,11 @@ fn new_blake2b() -> Blake2b {
 #[cfg(test)]
 mod tests {
     use anoma::ledger::storage::types;
+    use anoma::ledger::storage::write_log::WriteLog;
+    use anoma::types::address;
     use anoma::types::chain::ChainId;
     use anoma::types::storage::{BlockHash, BlockHeight, Key};
+    use anoma::types::token::{self, Amount};
     use tempfile::TempDir;
 
     use super::*;
@@ -141,6 +144,114 @@ mod tests {
         assert_eq!(val.expect("no value"), value_bytes);
     }
 
+    #[test]
+    fn test_commit_balances() {
+        let db_path =
+            TempDir::new().expect("Unable to create a temporary DB directory");
+        let mut storage =
+            PersistentStorage::open(db_path.path(), ChainId::default(), None);
+        storage
+            .begin_block(BlockHash::default(), BlockHeight(100))
+            .expect("begin_block failed");
+        let token = address::xan();
+        let balances = [
+            (address::testing::established_address_1(), Amount::whole(1)),
+            (address::testing::established_address_2(), Amount::whole(2)),
+            (address::testing::established_address_3(), Amount::whole(3)),
+        ];
+        for (owner, balance) in &balances {
+            let key = token::balance_key(&token, owner);
+            storage
+                .write(&key, types::encode(balance))
+                .expect("write failed");
+        }
+        storage.commit().expect("commit failed");
+
+        // save the root and drop the storage
+        let root = storage.merkle_root();
+        drop(storage);
+
+        // the balances and the root are the same after the state is loaded
+        let mut storage =
+            PersistentStorage::open(db_path.path(), ChainId::default(), None);
+        storage
+            .load_last_state()
+            .expect("loading the last state failed");
+        assert_eq!(storage.merkle_root(), root);
+        for (owner, balance) in &balances {
+            let (val, _) = storage
+                .read(&token::balance_key(&token, owner))
+                .expect("read failed");
+            let val: Amount =
+                types::decode(val.expect("no balance")).expect("decode failed");
+            assert_eq!(&val, balance);
+        }
+    }
+
+    #[test]
+    fn test_at_height() {
+        let db_path =
+            TempDir::new().expect("Unable to create a temporary DB directory");
+        let mut storage =
+            PersistentStorage::open(db_path.path(), ChainId::default(), None);
+        let token = address::xan();
+        let owner = address::testing::established_address_1();
+        let key = token::balance_key(&token, &owner);
+
+        // Change the balance in a few blocks, the last one is not committed
+        let mut write_log = WriteLog::default();
+        for height in 1..=5 {
+            storage
+                .begin_block(BlockHash::default(), BlockHeight(height))
+                .expect("begin_block failed");
+            let amount = |amount| types::encode(&Amount::whole(amount));
+            match height {
+                1 => write_log.write(&key, amount(10)).expect("write failed"),
+                2 => write_log.write(&key, amount(20)).expect("write failed"),
+                4 => write_log.delete(&key).expect("delete failed"),
+                5 => write_log.write(&key, amount(50)).expect("write failed"),
+                _ => (0, 0),
+            };
+            write_log.commit_tx();
+            write_log
+                .commit_block(&mut storage)
+                .expect("commit_block failed");
+            if height < 5 {
+                storage.commit().expect("commit failed");
+            }
+        }
+
+        for (height, expected) in [
+            (1, Some(10)),
+            (2, Some(20)),
+            (3, Some(20)),
+            (4, None),
+            (5, Some(50)),
+        ] {
+            let view = storage
+                .at_height(BlockHeight(height))
+                .expect("the height should be retained");
+            assert_eq!(
+                view.read_balance(&token, &owner).expect("read failed"),
+                expected.map(Amount::whole),
+                "balance at height {}",
+                height
+            );
+        }
+
+        let history = storage
+            .account_history(&token, &owner, BlockHeight(2))
+            .expect("history failed");
+        assert_eq!(
+            history,
+            vec![
+                (BlockHeight(2), Amount::whole(20)),
+                (BlockHeight(4), Amount::default()),
+                (BlockHeight(5), Amount::whole(50)),
+            ]
+        );
+    }
+
     #[test]
     fn test_iter() {
         let db_path =
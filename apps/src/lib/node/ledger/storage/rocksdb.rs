@@ -15,6 +15,8 @@
 //!   - `next_epoch_min_start_time`
 //! - `subspace`: accounts sub-spaces
 //!   - `{address}/{dyn}`: any byte data associated with accounts
+//! - `diff_heights`: index of the diffs in account subspaces' key-vals
+//!   - `{address}/{dyn}/{h}`: the key-val has been changed at block height `h`
 //! - `h`: for each block at height `h`:
 //!   - `tree`: merkle tree
 //!     - `root`: root hash
@@ -194,6 +196,10 @@ impl RocksDB {
                 .put(new_val_key, new_value)
                 .map_err(|e| Error::DBError(e.into_string()))?;
         }
+
+        self.0
+            .put(types::diff_height_key(key, height), [])
+            .map_err(|e| Error::DBError(e.into_string()))?;
         Ok(())
     }
 
@@ -227,6 +233,8 @@ impl RocksDB {
                 .to_string();
             batch.0.put(new_val_key, new_value);
         }
+
+        batch.0.put(types::diff_height_key(key, height), []);
         Ok(())
     }
 
@@ -615,30 +623,83 @@ impl DB for RocksDB {
         key: &Key,
         height: BlockHeight,
     ) -> Result<Option<Vec<u8>>> {
-        if self.read_subspace_val(key)?.is_none() {
-            return Ok(None);
+        // The value before its first change after the height, if any. This is
+        // `None` when the value didn't exist before the change.
+        let heights =
+            self.read_subspace_diff_heights(key, height.next_height())?;
+        match heights.first() {
+            Some(height) => {
+                let (old_value, _) = self.read_subspace_diff(*height, key)?;
+                Ok(old_value)
+            }
+            None => self.read_subspace_val(key),
         }
+    }
 
-        let mut height = height.0;
-        while height > 0 {
-            let key_prefix = Key::from(BlockHeight(height).to_db_key())
-                .push(&"diffs".to_owned())
-                .map_err(Error::KeyError)?;
-            let new_val_key = key_prefix
-                .push(&"new".to_owned())
-                .map_err(Error::KeyError)?
-                .join(key)
-                .to_string();
-            let val = self
-                .0
-                .get(new_val_key)
-                .map_err(|e| Error::DBError(e.into_string()))?;
-            match val {
-                Some(bytes) => return Ok(Some(bytes)),
-                None => height -= 1,
+    fn read_subspace_diff(
+        &self,
+        height: BlockHeight,
+        key: &Key,
+    ) -> Result<(Option<Vec<u8>>, Option<Vec<u8>>)> {
+        let key_prefix = Key::from(height.to_db_key())
+            .push(&"diffs".to_owned())
+            .map_err(Error::KeyError)?;
+        let old_val_key = key_prefix
+            .push(&"old".to_owned())
+            .map_err(Error::KeyError)?
+            .join(key)
+            .to_string();
+        let new_val_key = key_prefix
+            .push(&"new".to_owned())
+            .map_err(Error::KeyError)?
+            .join(key)
+            .to_string();
+        let old_value = self
+            .0
+            .get(old_val_key)
+            .map_err(|e| Error::DBError(e.into_string()))?;
+        let new_value = self
+            .0
+            .get(new_val_key)
+            .map_err(|e| Error::DBError(e.into_string()))?;
+        Ok((old_value, new_value))
+    }
+
+    fn read_subspace_diff_heights(
+        &self,
+        key: &Key,
+        since: BlockHeight,
+    ) -> Result<Vec<BlockHeight>> {
+        let prefix = types::diff_heights_prefix(key);
+        let start = types::diff_height_key(key, since);
+
+        let mut read_opts = ReadOptions::default();
+        // don't use the prefix bloom filter
+        read_opts.set_total_order_seek(true);
+        read_opts.set_iterate_upper_bound(types::diff_heights_upper_bound(key));
+
+        let mut heights = vec![];
+        let iter = self.0.iterator_opt(
+            IteratorMode::From(start.as_bytes(), Direction::Forward),
+            read_opts,
+        );
+        for (db_key, _) in iter {
+            let path = &String::from_utf8((*db_key).to_vec()).map_err(|e| {
+                Error::Temporary {
+                    error: format!(
+                        "Cannot convert path from utf8 bytes to string: {}",
+                        e
+                    ),
+                }
+            })?;
+            if let Some(height) = path
+                .strip_prefix(&prefix)
+                .and_then(types::parse_diff_height)
+            {
+                heights.push(height);
             }
         }
-        Ok(None)
+        Ok(heights)
     }
 
     fn write_subspace_val(
@@ -690,7 +751,7 @@ impl DB for RocksDB {
         // Check the length of previous value, if any
         let prev_len = match self
             .0
-            .get(key.to_string())
+            .get(subspace_key.to_string())
             .map_err(|e| Error::DBError(e.into_string()))?
         {
             Some(prev_value) => {
@@ -774,7 +835,7 @@ impl DB for RocksDB {
         // Check the length of previous value, if any
         let prev_len = match self
             .0
-            .get(key.to_string())
+            .get(subspace_key.to_string())
             .map_err(|e| Error::DBError(e.into_string()))?
         {
             Some(prev_value) => {
@@ -1027,4 +1088,55 @@ mod test {
             db.read_subspace_val(&key).expect("read should succeed");
         assert_eq!(latest_value, Some(vec![2_u8, 2, 2, 2]));
     }
+
+    #[test]
+    fn test_subspace_diffs() {
+        let dir = tempdir().unwrap();
+        let mut db = open(dir.path(), None).unwrap();
+
+        let key = Key::parse("test").unwrap();
+        let sub_key = Key::parse("test/sub").unwrap();
+
+        db.write_subspace_val(BlockHeight(1), &key, vec![1_u8])
+            .unwrap();
+        let mut batch = RocksDB::batch();
+        db.batch_write_subspace_val(&mut batch, BlockHeight(2), &key, [2_u8])
+            .unwrap();
+        db.batch_write_subspace_val(
+            &mut batch,
+            BlockHeight(2),
+            &sub_key,
+            [0_u8],
+        )
+        .unwrap();
+        db.exec_batch(batch.0).unwrap();
+        let mut batch = RocksDB::batch();
+        let deleted = db
+            .batch_delete_subspace_val(&mut batch, BlockHeight(3), &key)
+            .unwrap();
+        db.exec_batch(batch.0).unwrap();
+        assert_eq!(deleted, 1);
+        db.delete_subspace_val(BlockHeight(4), &sub_key).unwrap();
+
+        // The deletions persist the deleted values in the diffs
+        let diff = db.read_subspace_diff(BlockHeight(3), &key).unwrap();
+        assert_eq!(diff, (Some(vec![2_u8]), None));
+        let diff = db.read_subspace_diff(BlockHeight(4), &sub_key).unwrap();
+        assert_eq!(diff, (Some(vec![0_u8]), None));
+
+        // The sub-key's changes are not included in the key's heights
+        let heights =
+            db.read_subspace_diff_heights(&key, BlockHeight(0)).unwrap();
+        assert_eq!(
+            heights,
+            vec![BlockHeight(1), BlockHeight(2), BlockHeight(3)]
+        );
+        let heights =
+            db.read_subspace_diff_heights(&key, BlockHeight(2)).unwrap();
+        assert_eq!(heights, vec![BlockHeight(2), BlockHeight(3)]);
+        let heights = db
+            .read_subspace_diff_heights(&sub_key, BlockHeight(0))
+            .unwrap();
+        assert_eq!(heights, vec![BlockHeight(2), BlockHeight(4)]);
+    }
 }
@@ -62,6 +62,12 @@ pub enum ValidationError {
     TotalVotingPowerTooLarge(u64),
     #[error("Votes per token cannot be greater than 1, got {0}")]
     VotesPerTokenGreaterThanOne(BasisPoints),
+    #[error("Duplicate vote slash rate cannot be greater than 1, got {0}")]
+    DuplicateVoteSlashRateGreaterThanOne(BasisPoints),
+    #[error(
+        "Light client attack slash rate cannot be greater than 1, got {0}"
+    )]
+    LightClientAttackSlashRateGreaterThanOne(BasisPoints),
     #[error("Pipeline length must be >= 2, got {0}")]
     PipelineLenTooShort(u64),
     #[error(
@@ -119,6 +125,20 @@ impl PosParams {
             ))
         }
 
+        // Check that no more than the whole stake can be slashed
+        if self.duplicate_vote_slash_rate > BasisPoints::new(10_000) {
+            errors.push(ValidationError::DuplicateVoteSlashRateGreaterThanOne(
+                self.duplicate_vote_slash_rate,
+            ))
+        }
+        if self.light_client_attack_slash_rate > BasisPoints::new(10_000) {
+            errors.push(
+                ValidationError::LightClientAttackSlashRateGreaterThanOne(
+                    self.light_client_attack_slash_rate,
+                ),
+            )
+        }
+
         errors
     }
 }
@@ -143,6 +163,34 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_validate_slash_rates() {
+        // A full slash is valid
+        let pos_params = PosParams {
+            duplicate_vote_slash_rate: BasisPoints::new(10_000),
+            light_client_attack_slash_rate: BasisPoints::new(10_000),
+            ..Default::default()
+        };
+        assert!(pos_params.validate().is_empty());
+
+        // Slashing more than the whole stake is invalid
+        let pos_params = PosParams {
+            duplicate_vote_slash_rate: BasisPoints::new(10_001),
+            light_client_attack_slash_rate: BasisPoints::new(10_001),
+            ..Default::default()
+        };
+        let errors = pos_params.validate();
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(
+            errors[0],
+            ValidationError::DuplicateVoteSlashRateGreaterThanOne(_)
+        ));
+        assert!(matches!(
+            errors[1],
+            ValidationError::LightClientAttackSlashRateGreaterThanOne(_)
+        ));
+    }
 }
 
 /// Testing helpers
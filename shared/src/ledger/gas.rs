@@ -29,6 +29,52 @@ const TRANSACTION_GAS_LIMIT: u64 = 10_000_000_000;
 /// The minimum gas cost for accessing the storage
 pub const MIN_STORAGE_GAS: u64 = 1;
 
+/// The gas cost per byte of the operations on the storage
+const STORAGE_GAS_PER_BYTE: u64 = MIN_STORAGE_GAS;
+/// The gas cost per byte of hashing
+const HASH_GAS_PER_BYTE: u64 = MIN_STORAGE_GAS;
+
+/// The operations whose gas cost can be queried by the wasm code to choose a
+/// cheaper strategy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u64)]
+pub enum GasOp {
+    /// Read from storage
+    Read = 0,
+    /// Write to storage
+    Write = 1,
+    /// Iterate over storage
+    Iter = 2,
+    /// Hash data
+    Hash = 3,
+}
+
+impl GasOp {
+    /// Get the configured gas cost per byte of the operation
+    pub fn cost(self) -> u64 {
+        match self {
+            GasOp::Read | GasOp::Write | GasOp::Iter => STORAGE_GAS_PER_BYTE,
+            GasOp::Hash => HASH_GAS_PER_BYTE,
+        }
+    }
+
+    /// Convert the operation to `u64`, which can be passed to the host
+    pub fn to_u64(self) -> u64 {
+        self as _
+    }
+
+    /// Try to convert an operation from `u64`
+    pub fn from_u64(op: u64) -> Option<Self> {
+        match op {
+            0 => Some(GasOp::Read),
+            1 => Some(GasOp::Write),
+            2 => Some(GasOp::Iter),
+            3 => Some(GasOp::Hash),
+            _ => None,
+        }
+    }
+}
+
 /// Gas module result for functions that may fail
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -147,12 +193,18 @@ impl VpGasMeter {
             .checked_add(self.current_gas)
             .ok_or(Error::GasOverflow)?;
 
-        if current_total > TRANSACTION_GAS_LIMIT {
+        if current_total > self.tx_gas_limit() {
             return Err(Error::TransactionGasExceedededError);
         }
         Ok(())
     }
 
+    /// Get the gas limit of the transaction that is enforced by this meter.
+    /// The budget is shared by the transaction and all the VPs it triggers.
+    pub fn tx_gas_limit(&self) -> u64 {
+        TRANSACTION_GAS_LIMIT
+    }
+
     /// Add the compiling cost proportionate to the code length
     pub fn add_compiling_fee(&mut self, bytes_len: usize) -> Result<()> {
         self.add(bytes_len as u64 * COMPILE_GAS_PER_BYTE)
@@ -242,6 +294,15 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_gas_op_roundtrip() {
+        for op in [GasOp::Read, GasOp::Write, GasOp::Iter, GasOp::Hash] {
+            assert_eq!(GasOp::from_u64(op.to_u64()), Some(op));
+            assert!(op.cost() > 0);
+        }
+        assert_eq!(GasOp::from_u64(4), None);
+    }
+
     #[test]
     fn test_vp_gas_overflow() {
         let mut meter = VpGasMeter::new(1);
@@ -1,11 +1,13 @@
 //! Parameters storage
 use super::ADDRESS;
+use crate::types::hash::Hash;
 use crate::types::storage::{DbKeySeg, Key};
 
 const EPOCH_DURATION_KEY: &str = "epoch_duration";
 const VP_WHITELIST_KEY: &str = "vp_whitelist";
 const TX_WHITELIST_KEY: &str = "tx_whitelist";
 const MAX_EXPECTED_TIME_PER_BLOCK_KEY: &str = "max_expected_time_per_block";
+const VP_TEMPLATE_KEY: &str = "vp_template";
 
 /// Returns if the key is a parameter key.
 pub fn is_parameter_key(key: &Key) -> bool {
@@ -18,6 +20,7 @@ pub fn is_protocol_parameter_key(key: &Key) -> bool {
         || is_max_expected_time_per_block_key(key)
         || is_tx_whitelist_key(key)
         || is_vp_whitelist_key(key)
+        || is_vp_template_key(key)
 }
 
 /// Returns if the key is an epoch storage key.
@@ -52,6 +55,15 @@ pub fn is_vp_whitelist_key(key: &Key) -> bool {
     ] if addr == &ADDRESS && vp_whitelist == VP_WHITELIST_KEY)
 }
 
+/// Returns if the key is a vp_template key.
+pub fn is_vp_template_key(key: &Key) -> bool {
+    matches!(&key.segments[..], [
+        DbKeySeg::AddressSeg(addr),
+        DbKeySeg::StringSeg(vp_template),
+        DbKeySeg::StringSeg(_code_hash),
+    ] if addr == &ADDRESS && vp_template == VP_TEMPLATE_KEY)
+}
+
 /// Storage key used for epoch parameter.
 pub fn get_epoch_storage_key() -> Key {
     Key {
@@ -72,6 +84,18 @@ pub fn get_vp_whitelist_storage_key() -> Key {
     }
 }
 
+/// Storage key used for the code of a validity predicate template with the
+/// given code hash.
+pub fn get_vp_template_key(code_hash: &Hash) -> Key {
+    Key {
+        segments: vec![
+            DbKeySeg::AddressSeg(ADDRESS),
+            DbKeySeg::StringSeg(VP_TEMPLATE_KEY.to_string()),
+            DbKeySeg::StringSeg(code_hash.to_string()),
+        ],
+    }
+}
+
 /// Storage key used for tx whitelist parameter.
 pub fn get_tx_whitelist_storage_key() -> Key {
     Key {
@@ -91,3 +115,23 @@ pub fn get_max_expected_time_per_block_key() -> Key {
         ],
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::storage::KeySeg;
+
+    #[test]
+    fn test_vp_template_key_is_protocol_parameter() {
+        let key = get_vp_template_key(&Hash::sha256(b"vp"));
+        assert!(is_vp_template_key(&key));
+        assert!(is_protocol_parameter_key(&key));
+
+        // A key without the code hash is not a template key
+        let prefix = Key::from(ADDRESS.to_db_key())
+            .push(&VP_TEMPLATE_KEY.to_owned())
+            .unwrap();
+        assert!(!is_vp_template_key(&prefix));
+        assert!(!is_protocol_parameter_key(&prefix));
+    }
+}
@@ -10,11 +10,13 @@ pub use anoma_proof_of_stake::types::{
     ValidatorVotingPowers,
 };
 use anoma_proof_of_stake::PosBase;
+use borsh::BorshSerialize;
 pub use storage::*;
 pub use vp::PosVP;
 
 use crate::ledger::storage::{self as ledger_storage, Storage, StorageHasher};
 use crate::types::address::{self, Address, InternalAddress};
+use crate::types::hash::Hash;
 use crate::types::storage::Epoch;
 use crate::types::{key, token};
 
@@ -25,6 +27,9 @@ pub const ADDRESS: Address = Address::Internal(InternalAddress::PoS);
 pub const SLASH_POOL_ADDRESS: Address =
     Address::Internal(InternalAddress::PosSlashPool);
 
+/// The domain separator of the validator set hash
+const VALIDATOR_SET_HASH_DOMAIN: &[u8] = b"anoma/pos/validator_set";
+
 /// Address of the staking token (XAN)
 pub fn staking_token_address() -> Address {
     address::xan()
@@ -85,3 +90,385 @@ impl From<anoma_proof_of_stake::types::Epoch> for Epoch {
         Epoch(epoch)
     }
 }
+
+impl<D, H> Storage<D, H>
+where
+    D: ledger_storage::DB + for<'iter> ledger_storage::DBIter<'iter>,
+    H: StorageHasher,
+{
+    /// Get the public keys and voting powers of the active validators in the
+    /// current epoch, suitable for a Tendermint validator set update.
+    /// Validators without a public key in storage are skipped.
+    pub fn tendermint_validators(
+        &self,
+    ) -> ledger_storage::Result<Vec<(key::common::PublicKey, u64)>> {
+        let (current_epoch, _gas) = self.get_current_epoch();
+        let validator_set = match self.try_read_validator_set()? {
+            Some(validator_set) => validator_set,
+            None => return Ok(vec![]),
+        };
+        let active = match validator_set.get(current_epoch) {
+            Some(validator_set) => &validator_set.active,
+            None => return Ok(vec![]),
+        };
+        let mut validators = Vec::with_capacity(active.len());
+        for validator in active {
+            let pk_key = key::pk_key(&validator.address);
+            let (value, _gas) = self.read(&pk_key)?;
+            match value {
+                Some(value) => {
+                    let pk = ledger_storage::types::decode(value)
+                        .map_err(ledger_storage::Error::CodingError)?;
+                    let power: u64 = validator.voting_power.into();
+                    validators.push((pk, power));
+                }
+                None => {
+                    tracing::warn!(
+                        "Skipping validator {} without a public key",
+                        validator.address
+                    );
+                }
+            }
+        }
+        Ok(validators)
+    }
+
+    /// Compute the canonical hash of the active validator set in the current
+    /// epoch, for light clients tracking the validator set. The hash commits
+    /// to the validators' addresses, public keys and voting powers, sorted by
+    /// the address. A missing validator set hashes like an empty one.
+    pub fn validator_set_hash(&self) -> ledger_storage::Result<Hash> {
+        let (current_epoch, _gas) = self.get_current_epoch();
+        let validator_set = self.try_read_validator_set()?;
+        let mut validators = validator_set
+            .iter()
+            .filter_map(|validator_set| validator_set.get(current_epoch))
+            .flat_map(|validator_set| validator_set.active.iter())
+            .map(|validator| {
+                let pk_key = key::pk_key(&validator.address);
+                let (value, _gas) = self.read(&pk_key)?;
+                let pk: Option<key::common::PublicKey> = value
+                    .map(|value| {
+                        ledger_storage::types::decode(value)
+                            .map_err(ledger_storage::Error::CodingError)
+                    })
+                    .transpose()?;
+                let power: u64 = validator.voting_power.into();
+                Ok((validator.address.clone(), pk, power))
+            })
+            .collect::<ledger_storage::Result<Vec<_>>>()?;
+        validators.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+
+        let mut bytes = VALIDATOR_SET_HASH_DOMAIN.to_vec();
+        validators
+            .serialize(&mut bytes)
+            .map_err(ledger_storage::Error::BorshCodingError)?;
+        Ok(Hash::sha256(bytes))
+    }
+
+    /// Read the validator sets, if any. Unlike the
+    /// [`PosBase::read_validator_set`], this doesn't panic on a missing or
+    /// invalid value.
+    fn try_read_validator_set(
+        &self,
+    ) -> ledger_storage::Result<Option<ValidatorSets>> {
+        let (value, _gas) = self.read(&validator_set_key())?;
+        value
+            .map(|value| {
+                ledger_storage::types::decode(value)
+                    .map_err(ledger_storage::Error::CodingError)
+            })
+            .transpose()
+    }
+
+    /// Distribute the `total` amount of the given `token` among the active
+    /// validators of the current epoch, proportionally to their voting power.
+    /// The remainder left after the integer division is credited to the
+    /// validator with the highest voting power. If any of the new balances
+    /// would overflow, none of them is changed.
+    pub fn distribute_rewards(
+        &mut self,
+        token: &Address,
+        total: token::Amount,
+    ) -> ledger_storage::Result<()> {
+        let (current_epoch, _gas) = self.get_current_epoch();
+        let validator_set = match self.try_read_validator_set()? {
+            Some(validator_set) => validator_set,
+            None => return Ok(()),
+        };
+        let active = match validator_set.get(current_epoch) {
+            Some(validator_set) => &validator_set.active,
+            None => return Ok(()),
+        };
+        let total_power: u128 = active
+            .iter()
+            .map(|validator| u64::from(validator.voting_power) as u128)
+            .sum();
+        if total_power == 0 {
+            return Ok(());
+        }
+
+        let total_micro: u64 = total.into();
+        let mut rewards: Vec<(Address, u64)> = active
+            .iter()
+            .map(|validator| {
+                let power = u64::from(validator.voting_power) as u128;
+                let share = total_micro as u128 * power / total_power;
+                // The share is at most `total_micro`, so it fits into `u64`
+                (validator.address.clone(), share as u64)
+            })
+            .collect();
+        let distributed: u64 = rewards.iter().map(|(_, share)| share).sum();
+        // The active set is ordered by voting power, so the last validator has
+        // the highest voting power
+        if let Some((_, share)) = rewards.last_mut() {
+            *share += total_micro - distributed;
+        }
+
+        // Compute all the new balances before writing any of them
+        let mut balances = Vec::with_capacity(rewards.len());
+        for (validator, share) in rewards {
+            let balance_key = token::balance_key(token, &validator);
+            let (value, _gas) = self.read(&balance_key)?;
+            let current: token::Amount = match value {
+                Some(value) => ledger_storage::types::decode(value)
+                    .map_err(ledger_storage::Error::CodingError)?,
+                None => token::Amount::default(),
+            };
+            let balance =
+                u64::from(current).checked_add(share).ok_or_else(|| {
+                    ledger_storage::Error::BalanceOverflow {
+                        owner: validator,
+                        token: token.clone(),
+                        current,
+                        added: token::Amount::from(share),
+                    }
+                })?;
+            balances.push((balance_key, token::Amount::from(balance)));
+        }
+        self.check_write_limit(balances.iter().map(|(key, _)| key))?;
+        for (balance_key, balance) in balances {
+            self.write(&balance_key, ledger_storage::types::encode(&balance))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use anoma_proof_of_stake::epoched::Epoched;
+    use anoma_proof_of_stake::types::{ValidatorSet, WeightedValidator};
+
+    use super::*;
+    use crate::ledger::storage::testing::TestStorage;
+    use crate::ledger::storage::types::{decode, encode};
+    use crate::types::key::RefTo;
+
+    /// Write an active validator set with the given voting powers for the
+    /// current epoch.
+    fn write_active_validators(
+        storage: &mut TestStorage,
+        validators: &[(&Address, u64)],
+    ) {
+        let active: BTreeSet<_> = validators
+            .iter()
+            .map(|(address, power)| WeightedValidator {
+                voting_power: (*power).into(),
+                address: (*address).clone(),
+            })
+            .collect();
+        let validator_set = ValidatorSet {
+            active,
+            inactive: BTreeSet::default(),
+        };
+        let (current_epoch, _gas) = storage.get_current_epoch();
+        storage.write_validator_set(&Epoched::init_at_genesis(
+            validator_set,
+            current_epoch,
+        ));
+    }
+
+    fn read_balance(
+        storage: &TestStorage,
+        token: &Address,
+        owner: &Address,
+    ) -> token::Amount {
+        let (value, _gas) =
+            storage.read(&token::balance_key(token, owner)).unwrap();
+        value
+            .map(|value| decode(value).unwrap())
+            .unwrap_or_default()
+    }
+
+    /// Test that only the validators with a public key are exported for
+    /// Tendermint.
+    #[test]
+    fn test_tendermint_validators() {
+        let mut storage = TestStorage::default();
+        let keyed = address::testing::established_address_1();
+        let unkeyed = address::testing::established_address_2();
+        let pk = key::testing::keypair_1().ref_to();
+
+        write_active_validators(&mut storage, &[(&keyed, 10), (&unkeyed, 20)]);
+        storage.write(&key::pk_key(&keyed), encode(&pk)).unwrap();
+
+        assert_eq!(storage.tendermint_validators().unwrap(), vec![(pk, 10)]);
+
+        // An invalid public key is an error
+        storage.write(&key::pk_key(&unkeyed), vec![0xff]).unwrap();
+        let result = storage.tendermint_validators();
+        assert!(matches!(result, Err(ledger_storage::Error::CodingError(_))));
+    }
+
+    #[test]
+    fn test_validator_set_hash() {
+        let validator_1 = address::testing::established_address_1();
+        let validator_2 = address::testing::established_address_2();
+        let pk = key::testing::keypair_1().ref_to();
+
+        let mut storage = TestStorage::default();
+        write_active_validators(
+            &mut storage,
+            &[(&validator_1, 10), (&validator_2, 20)],
+        );
+        storage
+            .write(&key::pk_key(&validator_1), encode(&pk))
+            .unwrap();
+        let hash = storage.validator_set_hash().unwrap();
+
+        // The hash is deterministic
+        assert_eq!(storage.validator_set_hash().unwrap(), hash);
+
+        // A change of voting power changes the hash
+        write_active_validators(
+            &mut storage,
+            &[(&validator_1, 10), (&validator_2, 21)],
+        );
+        let stake_changed = storage.validator_set_hash().unwrap();
+        assert_ne!(stake_changed, hash);
+
+        // A change of public key changes the hash
+        let other_pk = key::testing::keypair_2().ref_to();
+        storage
+            .write(&key::pk_key(&validator_1), encode(&other_pk))
+            .unwrap();
+        assert_ne!(storage.validator_set_hash().unwrap(), stake_changed);
+
+        // An invalid public key is an error
+        storage
+            .write(&key::pk_key(&validator_1), vec![0xff])
+            .unwrap();
+        let result = storage.validator_set_hash();
+        assert!(matches!(result, Err(ledger_storage::Error::CodingError(_))));
+    }
+
+    #[test]
+    fn test_distribute_rewards() {
+        let token = address::xan();
+        let validator_1 = address::testing::established_address_1();
+        let validator_2 = address::testing::established_address_2();
+
+        // An even split
+        let mut storage = TestStorage::default();
+        write_active_validators(
+            &mut storage,
+            &[(&validator_1, 10), (&validator_2, 10)],
+        );
+        storage
+            .distribute_rewards(&token, token::Amount::from(100))
+            .unwrap();
+        assert_eq!(
+            read_balance(&storage, &token, &validator_1),
+            token::Amount::from(50)
+        );
+        assert_eq!(
+            read_balance(&storage, &token, &validator_2),
+            token::Amount::from(50)
+        );
+
+        // An uneven split, the remainder goes to the highest voting power
+        let mut storage = TestStorage::default();
+        write_active_validators(
+            &mut storage,
+            &[(&validator_1, 1), (&validator_2, 2)],
+        );
+        storage
+            .distribute_rewards(&token, token::Amount::from(10))
+            .unwrap();
+        assert_eq!(
+            read_balance(&storage, &token, &validator_1),
+            token::Amount::from(3)
+        );
+        assert_eq!(
+            read_balance(&storage, &token, &validator_2),
+            token::Amount::from(7)
+        );
+
+        // Zero total
+        storage
+            .distribute_rewards(&token, token::Amount::default())
+            .unwrap();
+        assert_eq!(
+            read_balance(&storage, &token, &validator_1),
+            token::Amount::from(3)
+        );
+        assert_eq!(
+            read_balance(&storage, &token, &validator_2),
+            token::Amount::from(7)
+        );
+    }
+
+    /// Test that a reward overflowing any balance changes none of them and
+    /// that an invalid validator set is an error.
+    #[test]
+    fn test_distribute_rewards_errors() {
+        let token = address::xan();
+        let validator_1 = address::testing::established_address_1();
+        let validator_2 = address::testing::established_address_2();
+
+        // No validator set
+        let mut storage = TestStorage::default();
+        storage
+            .distribute_rewards(&token, token::Amount::from(10))
+            .unwrap();
+        assert_eq!(
+            read_balance(&storage, &token, &validator_1),
+            token::Amount::default()
+        );
+
+        // An overflowing balance
+        write_active_validators(
+            &mut storage,
+            &[(&validator_1, 1), (&validator_2, 1)],
+        );
+        storage
+            .write(
+                &token::balance_key(&token, &validator_1),
+                encode(&token::Amount::max()),
+            )
+            .unwrap();
+        let result =
+            storage.distribute_rewards(&token, token::Amount::from(10));
+        assert!(matches!(
+            result,
+            Err(ledger_storage::Error::BalanceOverflow { owner, .. })
+                if owner == validator_1
+        ));
+        assert_eq!(
+            read_balance(&storage, &token, &validator_1),
+            token::Amount::max()
+        );
+        assert_eq!(
+            read_balance(&storage, &token, &validator_2),
+            token::Amount::default()
+        );
+
+        // An invalid validator set
+        storage.write(&validator_set_key(), vec![0xff]).unwrap();
+        let result =
+            storage.distribute_rewards(&token, token::Amount::from(10));
+        assert!(matches!(result, Err(ledger_storage::Error::CodingError(_))));
+    }
+}
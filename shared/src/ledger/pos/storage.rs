@@ -58,11 +58,16 @@ pub fn is_params_key(key: &Key) -> bool {
     }
 }
 
-/// Storage key prefix for validator data.
-fn validator_prefix(validator: &Address) -> Key {
+/// Storage key prefix for all validators' data.
+pub fn validators_prefix() -> Key {
     Key::from(ADDRESS.to_db_key())
         .push(&VALIDATOR_STORAGE_PREFIX.to_owned())
         .expect("Cannot obtain a storage key")
+}
+
+/// Storage key prefix for validator data.
+fn validator_prefix(validator: &Address) -> Key {
+    validators_prefix()
         .push(&validator.to_db_key())
         .expect("Cannot obtain a storage key")
 }
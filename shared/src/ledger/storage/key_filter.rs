@@ -0,0 +1,106 @@
+//! A bloom filter over the storage keys, used to quickly find out that a key
+//! is absent from storage without looking it up in the merkle tree.
+//!
+//! The filter may report false positives, but never false negatives. Keys are
+//! only ever added to it, so a deleted key may still be reported as possibly
+//! present.
+//!
+//! The filter is sized for a given number of keys, its capacity, for which the
+//! false positive rate is at most about 0.25%. The filter doesn't grow, so
+//! once more keys than its capacity are inserted, the rate rises, e.g. to
+//! about 2.4% at twice the capacity and to about 16% at four times the
+//! capacity.
+
+use sha2::{Digest, Sha256};
+
+use crate::types::storage::Key;
+
+/// The number of bits allocated for each key of the capacity
+const BITS_PER_KEY: usize = 16;
+/// The number of bits set for each key
+const NUM_HASHES: u64 = 4;
+/// The capacity of a [`KeyFilter::default`]
+pub const DEFAULT_CAPACITY: usize = 1 << 16;
+
+/// A bloom filter over the storage keys
+#[derive(Clone, Debug)]
+pub struct KeyFilter {
+    bits: Vec<u64>,
+}
+
+impl Default for KeyFilter {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+}
+
+impl KeyFilter {
+    /// Create an empty filter sized for the given number of keys.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let num_bits = capacity
+            .saturating_mul(BITS_PER_KEY)
+            .max(64)
+            .next_power_of_two();
+        Self {
+            bits: vec![0; num_bits / 64],
+        }
+    }
+
+    /// Add the given key to the filter.
+    pub fn insert(&mut self, key: &Key) {
+        for index in self.indices(key) {
+            self.bits[index / 64] |= 1 << (index % 64);
+        }
+    }
+
+    /// Check if the given key may be present. When this returns `false`, the
+    /// key has definitely never been inserted.
+    pub fn may_contain(&self, key: &Key) -> bool {
+        self.indices(key)
+            .all(|index| self.bits[index / 64] & (1 << (index % 64)) != 0)
+    }
+
+    /// The bit indices of the key, derived from its hash with double hashing
+    fn indices(&self, key: &Key) -> impl Iterator<Item = usize> {
+        let num_bits = (self.bits.len() * 64) as u64;
+        let hash = Sha256::digest(key.to_string().as_bytes());
+        let mut first = [0; 8];
+        first.copy_from_slice(&hash[..8]);
+        let mut second = [0; 8];
+        second.copy_from_slice(&hash[8..16]);
+        let first = u64::from_le_bytes(first);
+        let second = u64::from_le_bytes(second);
+        (0..NUM_HASHES).map(move |i| {
+            (first.wrapping_add(i.wrapping_mul(second)) % num_bits) as usize
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(i: usize) -> Key {
+        Key::parse(format!("key/{}", i)).unwrap()
+    }
+
+    #[test]
+    fn test_key_filter() {
+        let mut filter = KeyFilter::with_capacity(1000);
+        let present = 0..1000;
+        for i in present.clone() {
+            filter.insert(&key(i));
+        }
+
+        // No false negatives
+        for i in present {
+            assert!(filter.may_contain(&key(i)));
+        }
+
+        // Most of the absent keys are filtered out
+        let false_positives = (1000..11000)
+            .filter(|i| filter.may_contain(&key(*i)))
+            .count();
+        assert!(false_positives < 100, "{} false positives", false_positives);
+    }
+}
@@ -1,5 +1,6 @@
 //! The merkle tree in the storage
 
+use std::collections::BTreeMap;
 use std::convert::TryInto;
 use std::fmt;
 use std::str::FromStr;
@@ -14,8 +15,10 @@ use prost::Message;
 use sha2::{Digest, Sha256};
 use sparse_merkle_tree::default_store::DefaultStore;
 use sparse_merkle_tree::error::Error as SmtError;
-use sparse_merkle_tree::traits::Hasher;
-use sparse_merkle_tree::{SparseMerkleTree, H256};
+use sparse_merkle_tree::traits::{Hasher, Store};
+use sparse_merkle_tree::{
+    CompiledMerkleProof, MerkleProof, SparseMerkleTree, H256,
+};
 #[cfg(not(feature = "ABCI"))]
 use tendermint::merkle::proof::{Proof, ProofOp};
 #[cfg(feature = "ABCI")]
@@ -37,11 +40,17 @@ pub enum Error {
     Smt(SmtError),
     #[error("Invalid store type: {0}")]
     StoreType(String),
+    #[error("SMT store error after {retries} retries of the update: {error}")]
+    UpdateRetriesExhausted { retries: usize, error: String },
 }
 
 /// Result for functions that may fail
 type Result<T> = std::result::Result<T, Error>;
 
+/// The default number of times a failed update of a tree is retried on a
+/// recoverable store error before the error is returned
+pub const DEFAULT_UPDATE_RETRIES: usize = 3;
+
 /// Store types for the merkle tree
 #[derive(
     Clone,
@@ -51,6 +60,7 @@ type Result<T> = std::result::Result<T, Error>;
     PartialEq,
     Eq,
     PartialOrd,
+    Ord,
     BorshSerialize,
     BorshDeserialize,
 )]
@@ -126,12 +136,24 @@ impl fmt::Display for StoreType {
 }
 
 /// Merkle tree storage
-#[derive(Default)]
 pub struct MerkleTree<H: StorageHasher + Default> {
     base: SparseMerkleTree<H, H256, DefaultStore<H256>>,
     account: SparseMerkleTree<H, H256, DefaultStore<H256>>,
     ibc: SparseMerkleTree<H, H256, DefaultStore<H256>>,
     pos: SparseMerkleTree<H, H256, DefaultStore<H256>>,
+    update_retries: usize,
+}
+
+impl<H: StorageHasher + Default> Default for MerkleTree<H> {
+    fn default() -> Self {
+        Self {
+            base: Default::default(),
+            account: Default::default(),
+            ibc: Default::default(),
+            pos: Default::default(),
+            update_retries: DEFAULT_UPDATE_RETRIES,
+        }
+    }
 }
 
 impl<H: StorageHasher + Default> core::fmt::Debug for MerkleTree<H> {
@@ -156,9 +178,16 @@ impl<H: StorageHasher + Default> MerkleTree<H> {
             account,
             ibc,
             pos,
+            update_retries: DEFAULT_UPDATE_RETRIES,
         }
     }
 
+    /// Set the number of times a tree update is retried on a recoverable
+    /// store error
+    pub fn set_update_retries(&mut self, retries: usize) {
+        self.update_retries = retries;
+    }
+
     fn tree(
         &self,
         store_type: &StoreType,
@@ -184,12 +213,18 @@ impl<H: StorageHasher + Default> MerkleTree<H> {
             // base tree should not be directly updated
             StoreType::Base => unreachable!(),
         };
-        let sub_root = tree.update(key, value).map_err(Error::Smt)?;
+        let sub_root =
+            update_with_retries(tree, key, value, self.update_retries)?;
 
         // update the base tree with the updated sub root without hashing
         if *store_type != StoreType::Base {
             let base_key = H::hash(&store_type.to_string());
-            self.base.update(base_key, *sub_root)?;
+            update_with_retries(
+                &mut self.base,
+                base_key,
+                sub_root,
+                self.update_retries,
+            )?;
         }
         Ok(())
     }
@@ -227,6 +262,31 @@ impl<H: StorageHasher + Default> MerkleTree<H> {
         (*self.base.root()).into()
     }
 
+    /// Get the total number of the branch and leaf nodes in the stores of the
+    /// base and sub trees
+    pub fn node_count(&self) -> usize {
+        [&self.base, &self.account, &self.ibc, &self.pos]
+            .iter()
+            .map(|tree| {
+                let store = tree.store();
+                store.branches_map().len() + store.leaves_map().len()
+            })
+            .sum()
+    }
+
+    /// Rebuild the base and sub trees from their live leaves only, without
+    /// the nodes that are no longer referenced after updates and deletions.
+    /// The root of the compacted tree is the same.
+    pub fn compacted(&self) -> Result<Self> {
+        Ok(Self {
+            base: compact_tree(&self.base)?,
+            account: compact_tree(&self.account)?,
+            ibc: compact_tree(&self.ibc)?,
+            pos: compact_tree(&self.pos)?,
+            update_retries: self.update_retries,
+        })
+    }
+
     /// Get the stores of the base and sub trees
     pub fn stores(&self) -> MerkleTreeStoresWrite {
         MerkleTreeStoresWrite {
@@ -237,6 +297,61 @@ impl<H: StorageHasher + Default> MerkleTree<H> {
         }
     }
 
+    /// Get a witness of the transition of the root after applying the given
+    /// changes, without modifying the tree. A `None` value is a deletion.
+    pub fn root_witness(
+        &self,
+        changes: &[(Key, Option<Vec<u8>>)],
+    ) -> Result<RootTransition> {
+        // The last change of a key takes precedence
+        let mut sub_changes: BTreeMap<StoreType, BTreeMap<H256, H256>> =
+            BTreeMap::new();
+        for (key, value) in changes {
+            let (store_type, sub_key) = StoreType::sub_key(key)?;
+            let value = match value {
+                Some(value) => H::hash(value),
+                None => H256::zero(),
+            };
+            sub_changes
+                .entry(store_type)
+                .or_default()
+                .insert(H::hash(sub_key.to_string()), value);
+        }
+
+        let mut sub_trees = Vec::with_capacity(sub_changes.len());
+        let mut base_keys = Vec::with_capacity(sub_changes.len());
+        for (store_type, leaves) in sub_changes {
+            let tree = self.tree(&store_type);
+            let mut old_leaves = Vec::with_capacity(leaves.len());
+            for key in leaves.keys() {
+                old_leaves.push((*key, tree.get(key)?));
+            }
+            let proof = tree.merkle_proof(leaves.keys().cloned().collect())?;
+            base_keys.push(H::hash(store_type.to_string()));
+            sub_trees.push(SubTreeTransition {
+                store_type,
+                old_root: *tree.root(),
+                proof,
+                old_leaves,
+                new_leaves: leaves.into_iter().collect(),
+            });
+        }
+        let base_proof = if base_keys.is_empty() {
+            None
+        } else {
+            Some(self.base.merkle_proof(base_keys)?)
+        };
+
+        let mut transition = RootTransition {
+            old_root: *self.base.root(),
+            new_root: *self.base.root(),
+            sub_trees,
+            base_proof,
+        };
+        transition.new_root = transition.compute_new_root::<H>()?;
+        Ok(transition)
+    }
+
     /// Get the existence proof
     pub fn get_existence_proof(
         &self,
@@ -255,7 +370,7 @@ impl<H: StorageHasher + Default> MerkleTree<H> {
                 proof: Some(Ics23Proof::Exist(ExistenceProof {
                     key: sub_key.to_string().as_bytes().to_vec(),
                     value,
-                    leaf: Some(self.leaf_spec()),
+                    leaf: Some(Self::leaf_spec()),
                     ..ep
                 })),
             },
@@ -265,6 +380,51 @@ impl<H: StorageHasher + Default> MerkleTree<H> {
         self.get_proof(key, sub_proof)
     }
 
+    /// Get a proof that the given key is not present in the tree, which can be
+    /// verified without the full tree with [`NonMembershipProof::verify`].
+    /// Returns `None` if the key is present.
+    pub fn non_membership_proof(
+        &self,
+        key: &Key,
+    ) -> Result<Option<NonMembershipProof>> {
+        let (store_type, sub_key) = StoreType::sub_key(key)?;
+        let subtree = self.tree(&store_type);
+        let hashed_sub_key = H::hash(sub_key.to_string());
+        if !subtree.get(&hashed_sub_key)?.is_zero() {
+            return Ok(None);
+        }
+        let sub_proof = subtree.merkle_proof(vec![hashed_sub_key])?;
+        let base_key = H::hash(store_type.to_string());
+        let base_proof = self.base.merkle_proof(vec![base_key])?;
+        Ok(Some(NonMembershipProof {
+            sub_proof,
+            base_proof,
+        }))
+    }
+
+    /// Get a proof of the current value of the given key, which is zero if the
+    /// key is not present. It can be verified without the full tree with
+    /// [`MembershipProof::verify`].
+    pub fn membership_proof(&self, key: &Key) -> Result<MembershipProof> {
+        let (store_type, sub_key) = StoreType::sub_key(key)?;
+        let subtree = self.tree(&store_type);
+        let hashed_sub_key = H::hash(sub_key.to_string());
+        let leaf = (hashed_sub_key, subtree.get(&hashed_sub_key)?);
+        let sub_proof = subtree
+            .merkle_proof(vec![hashed_sub_key])?
+            .compile(vec![leaf])?;
+        let base_key = H::hash(store_type.to_string());
+        let base_leaf = (base_key, *subtree.root());
+        let base_proof = self
+            .base
+            .merkle_proof(vec![base_key])?
+            .compile(vec![base_leaf])?;
+        Ok(MembershipProof {
+            sub_proof: sub_proof.0,
+            base_proof: base_proof.0,
+        })
+    }
+
     /// Get the non-existence proof
     pub fn get_non_existence_proof(&self, key: &Key) -> Result<Proof> {
         let (store_type, sub_key) = StoreType::sub_key(key)?;
@@ -313,7 +473,7 @@ impl<H: StorageHasher + Default> MerkleTree<H> {
             Ics23Proof::Exist(ep) => CommitmentProof {
                 proof: Some(Ics23Proof::Exist(ExistenceProof {
                     key: base_key.as_bytes().to_vec(),
-                    leaf: Some(self.base_leaf_spec()),
+                    leaf: Some(Self::base_leaf_spec()),
                     ..ep
                 })),
             },
@@ -339,13 +499,66 @@ impl<H: StorageHasher + Default> MerkleTree<H> {
 
     /// Get the proof specs
     pub fn proof_specs(&self) -> Vec<ProofSpec> {
+        Self::specs()
+    }
+
+    /// Verify an existence proof of the `key` with the `value` obtained with
+    /// [`MerkleTree::get_existence_proof`] against the given merkle `root`.
+    /// This doesn't require the tree, so it can be used by light clients.
+    pub fn verify_existence_proof(
+        root: &MerkleRoot,
+        key: &Key,
+        value: Vec<u8>,
+        proof: &Proof,
+    ) -> Result<bool> {
+        let (store_type, sub_key) = StoreType::sub_key(key)?;
+        let paths = [sub_key.to_string(), store_type.to_string()];
+        let specs = Self::specs();
+        if proof.ops.len() != specs.len() {
+            return Ok(false);
+        }
+        // First, the sub proof is verified. Next the base proof is verified
+        // with the sub root
+        let mut value = value;
+        for ((op, spec), path) in
+            proof.ops.iter().zip(specs.iter()).zip(paths.iter())
+        {
+            let commitment_proof = match CommitmentProof::decode(&*op.data) {
+                Ok(commitment_proof) => commitment_proof,
+                Err(_) => return Ok(false),
+            };
+            let sub_root = match &commitment_proof.proof {
+                Some(Ics23Proof::Exist(ep)) => {
+                    match ics23::calculate_existence_root(ep) {
+                        Ok(sub_root) => sub_root,
+                        Err(_) => return Ok(false),
+                    }
+                }
+                _ => return Ok(false),
+            };
+            if !ics23::verify_membership(
+                &commitment_proof,
+                spec,
+                &sub_root,
+                path.as_bytes(),
+                &value,
+            ) {
+                return Ok(false);
+            }
+            value = sub_root;
+        }
+        Ok(value == root.0)
+    }
+
+    /// Get the proof specs of the sub-trees and the base tree
+    fn specs() -> Vec<ProofSpec> {
         let spec = sparse_merkle_tree::proof_ics23::get_spec(H::hash_op());
         let sub_tree_spec = ProofSpec {
-            leaf_spec: Some(self.leaf_spec()),
+            leaf_spec: Some(Self::leaf_spec()),
             ..spec.clone()
         };
         let base_tree_spec = ProofSpec {
-            leaf_spec: Some(self.base_leaf_spec()),
+            leaf_spec: Some(Self::base_leaf_spec()),
             ..spec
         };
         vec![sub_tree_spec, base_tree_spec]
@@ -353,7 +566,7 @@ impl<H: StorageHasher + Default> MerkleTree<H> {
 
     /// Get the leaf spec for the base tree. The key is stored after hashing,
     /// but the stored value is the subtree's root without hashing.
-    fn base_leaf_spec(&self) -> LeafOp {
+    fn base_leaf_spec() -> LeafOp {
         LeafOp {
             hash: H::hash_op().into(),
             prehash_key: H::hash_op().into(),
@@ -366,7 +579,7 @@ impl<H: StorageHasher + Default> MerkleTree<H> {
     /// Get the leaf spec for the subtree. Non-hashed values are used for the
     /// verification with this spec because a subtree stores the key-value pairs
     /// after hashing.
-    fn leaf_spec(&self) -> LeafOp {
+    fn leaf_spec() -> LeafOp {
         LeafOp {
             hash: H::hash_op().into(),
             prehash_key: H::hash_op().into(),
@@ -377,7 +590,184 @@ impl<H: StorageHasher + Default> MerkleTree<H> {
     }
 }
 
+/// A witness of a transition of the merkle tree root, which can be verified
+/// without the full tree. It contains the old and the new root and the
+/// proofs of the changed keys in the sub-trees and of the changed sub-trees'
+/// roots in the base tree.
+#[derive(Clone, Debug)]
+pub struct RootTransition {
+    /// The root before the changes
+    pub old_root: H256,
+    /// The root after the changes
+    pub new_root: H256,
+    sub_trees: Vec<SubTreeTransition>,
+    base_proof: Option<MerkleProof>,
+}
+
+/// A proof that a key is not present in the merkle tree, i.e. that its value
+/// in the sub-tree is zero, which can be verified without the full tree.
+#[derive(Clone, Debug)]
+pub struct NonMembershipProof {
+    sub_proof: MerkleProof,
+    base_proof: MerkleProof,
+}
+
+impl NonMembershipProof {
+    /// Verify that the given key is not present in the tree with the given
+    /// root. A proof that doesn't match the key is not valid.
+    pub fn verify<H: StorageHasher>(
+        &self,
+        root: &MerkleRoot,
+        key: &Key,
+    ) -> Result<bool> {
+        let (store_type, sub_key) = StoreType::sub_key(key)?;
+        let leaf = (H::hash(sub_key.to_string()), H256::zero());
+        let sub_proof = self.sub_proof.clone();
+        let sub_root = match sub_proof.compute_root::<H>(vec![leaf]) {
+            Ok(sub_root) => sub_root,
+            Err(_) => return Ok(false),
+        };
+        let base_leaf = (H::hash(store_type.to_string()), sub_root);
+        let base_proof = self.base_proof.clone();
+        match base_proof.compute_root::<H>(vec![base_leaf]) {
+            Ok(base_root) => Ok(&MerkleRoot::from(base_root) == root),
+            Err(_) => Ok(false),
+        }
+    }
+}
+
+/// A proof of the value of a key in the merkle tree, which can be verified
+/// without the full tree. Unlike the other proofs, it is compact and it can be
+/// Borsh encoded to be sent to light clients.
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct MembershipProof {
+    sub_proof: Vec<u8>,
+    base_proof: Vec<u8>,
+}
+
+impl MembershipProof {
+    /// Verify that the given key has the given value in the tree with the
+    /// given root. A `None` value verifies that the key is not present. A
+    /// proof that doesn't match the key or the value is not valid.
+    pub fn verify<H: StorageHasher>(
+        &self,
+        root: &MerkleRoot,
+        key: &Key,
+        value: Option<&[u8]>,
+    ) -> Result<bool> {
+        let (store_type, sub_key) = StoreType::sub_key(key)?;
+        let value = value.map(H::hash).unwrap_or_else(H256::zero);
+        let leaf = (H::hash(sub_key.to_string()), value);
+        let sub_proof = CompiledMerkleProof(self.sub_proof.clone());
+        let sub_root = match sub_proof.compute_root::<H>(vec![leaf]) {
+            Ok(sub_root) => sub_root,
+            Err(_) => return Ok(false),
+        };
+        let base_leaf = (H::hash(store_type.to_string()), sub_root);
+        let base_proof = CompiledMerkleProof(self.base_proof.clone());
+        match base_proof.compute_root::<H>(vec![base_leaf]) {
+            Ok(base_root) => Ok(&MerkleRoot::from(base_root) == root),
+            Err(_) => Ok(false),
+        }
+    }
+}
+
+/// A witness of a transition of a sub-tree root
+#[derive(Clone, Debug)]
+struct SubTreeTransition {
+    store_type: StoreType,
+    old_root: H256,
+    proof: MerkleProof,
+    old_leaves: Vec<(H256, H256)>,
+    new_leaves: Vec<(H256, H256)>,
+}
+
+impl RootTransition {
+    /// Verify that the changed keys' old values are committed in the old root
+    /// and that applying the new values to them results in the new root.
+    pub fn verify<H: StorageHasher>(&self) -> Result<bool> {
+        let mut old_base_leaves = Vec::with_capacity(self.sub_trees.len());
+        for sub_tree in &self.sub_trees {
+            if !sub_tree
+                .proof
+                .clone()
+                .verify::<H>(&sub_tree.old_root, sub_tree.old_leaves.clone())?
+            {
+                return Ok(false);
+            }
+            old_base_leaves.push((
+                H::hash(sub_tree.store_type.to_string()),
+                sub_tree.old_root,
+            ));
+        }
+        if let Some(base_proof) = &self.base_proof {
+            if !base_proof
+                .clone()
+                .verify::<H>(&self.old_root, old_base_leaves)?
+            {
+                return Ok(false);
+            }
+        }
+        Ok(self.compute_new_root::<H>()? == self.new_root)
+    }
+
+    /// Compute the new root from the sub-trees' new leaves
+    fn compute_new_root<H: StorageHasher>(&self) -> Result<H256> {
+        let base_proof = match &self.base_proof {
+            Some(base_proof) => base_proof,
+            None => return Ok(self.old_root),
+        };
+        let mut new_base_leaves = Vec::with_capacity(self.sub_trees.len());
+        for sub_tree in &self.sub_trees {
+            let new_sub_root = sub_tree
+                .proof
+                .clone()
+                .compute_root::<H>(sub_tree.new_leaves.clone())?;
+            new_base_leaves
+                .push((H::hash(sub_tree.store_type.to_string()), new_sub_root));
+        }
+        Ok(base_proof.clone().compute_root::<H>(new_base_leaves)?)
+    }
+}
+
+/// Update the key in the given tree, retrying the update up to `retries`
+/// times when it fails with a recoverable store error. Once the retries are
+/// exhausted, [`Error::UpdateRetriesExhausted`] is returned. Any other error is
+/// returned immediately. Returns the new root of the tree.
+fn update_with_retries<H, S>(
+    tree: &mut SparseMerkleTree<H, H256, S>,
+    key: H256,
+    value: H256,
+    retries: usize,
+) -> Result<H256>
+where
+    H: Hasher + Default,
+    S: Store<H256>,
+{
+    let mut attempt = 0;
+    loop {
+        match tree.update(key, value) {
+            Ok(root) => return Ok(*root),
+            Err(SmtError::Store(error)) if attempt < retries => {
+                attempt += 1;
+                tracing::debug!(
+                    "Retrying the merkle tree update ({}/{}) after a store \
+                     error: {}",
+                    attempt,
+                    retries,
+                    error
+                );
+            }
+            Err(SmtError::Store(error)) => {
+                return Err(Error::UpdateRetriesExhausted { retries, error });
+            }
+            Err(error) => return Err(Error::Smt(error)),
+        }
+    }
+}
+
 /// The root hash of the merkle tree as bytes
+#[derive(Clone, Debug, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
 pub struct MerkleRoot(pub Vec<u8>);
 
 impl From<H256> for MerkleRoot {
@@ -392,6 +782,34 @@ impl fmt::Display for MerkleRoot {
     }
 }
 
+/// Build a new tree with the leaves of the given tree that hold its current
+/// values
+fn compact_tree<H: StorageHasher + Default>(
+    tree: &SparseMerkleTree<H, H256, DefaultStore<H256>>,
+) -> Result<SparseMerkleTree<H, H256, DefaultStore<H256>>> {
+    let mut compacted = SparseMerkleTree::default();
+    for leaf in tree.store().leaves_map().values() {
+        // skip the stale leaves of the overwritten and deleted values
+        if !leaf.value.is_zero() && tree.get(&leaf.key)? == leaf.value {
+            compacted.update(leaf.key, leaf.value)?;
+        }
+    }
+    Ok(compacted)
+}
+
+/// Compute the root of a standalone tree over the given keys and values, which
+/// are hashed in the same way as in the [`MerkleTree`]. This is independent of
+/// the root of the [`MerkleTree`].
+pub fn standalone_root<H: StorageHasher + Default>(
+    entries: impl IntoIterator<Item = (Key, Vec<u8>)>,
+) -> Result<H256> {
+    let mut tree = SparseMerkleTree::<H, H256, DefaultStore<H256>>::default();
+    for (key, value) in entries {
+        tree.update(H::hash(key.to_string()), H::hash(value))?;
+    }
+    Ok(*tree.root())
+}
+
 /// The root and store pairs to restore the trees
 #[derive(Default)]
 pub struct MerkleTreeStoresRead {
@@ -514,6 +932,8 @@ impl From<SmtError> for Error {
 
 #[cfg(test)]
 mod test {
+    use sparse_merkle_tree::tree::{BranchNode, LeafNode};
+
     use super::*;
     use crate::types::storage::KeySeg;
 
@@ -544,6 +964,105 @@ mod test {
         assert!(tree.has_key(&pos_key).unwrap());
     }
 
+    type Smt<S> = SparseMerkleTree<Sha256Hasher, H256, S>;
+
+    /// A store that fails the given number of branch writes, like a store
+    /// backed by a DB that is temporarily unavailable
+    #[derive(Default)]
+    struct FlakyStore {
+        store: DefaultStore<H256>,
+        failures: usize,
+    }
+
+    impl Store<H256> for FlakyStore {
+        fn get_branch(
+            &self,
+            node: &H256,
+        ) -> std::result::Result<Option<BranchNode>, SmtError> {
+            self.store.get_branch(node)
+        }
+
+        fn get_leaf(
+            &self,
+            leaf_hash: &H256,
+        ) -> std::result::Result<Option<LeafNode<H256>>, SmtError> {
+            self.store.get_leaf(leaf_hash)
+        }
+
+        fn insert_branch(
+            &mut self,
+            node: H256,
+            branch: BranchNode,
+        ) -> std::result::Result<(), SmtError> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(SmtError::Store("unavailable".to_owned()));
+            }
+            self.store.insert_branch(node, branch)
+        }
+
+        fn insert_leaf(
+            &mut self,
+            leaf_hash: H256,
+            leaf: LeafNode<H256>,
+        ) -> std::result::Result<(), SmtError> {
+            self.store.insert_leaf(leaf_hash, leaf)
+        }
+
+        fn remove_branch(
+            &mut self,
+            node: &H256,
+        ) -> std::result::Result<(), SmtError> {
+            self.store.remove_branch(node)
+        }
+
+        fn remove_leaf(
+            &mut self,
+            leaf_hash: &H256,
+        ) -> std::result::Result<(), SmtError> {
+            self.store.remove_leaf(leaf_hash)
+        }
+    }
+
+    #[test]
+    fn test_update_with_retries() {
+        let key = Sha256Hasher::hash("key");
+        let value = Sha256Hasher::hash("value");
+        let mut expected = Smt::<DefaultStore<H256>>::default();
+        let expected_root = *expected.update(key, value).unwrap();
+
+        // The update fails midway, after the leaf has been written, and
+        // succeeds when it's retried
+        let mut tree = Smt::new(
+            H256::zero(),
+            FlakyStore {
+                failures: 1,
+                ..Default::default()
+            },
+        );
+        let root =
+            update_with_retries(&mut tree, key, value, DEFAULT_UPDATE_RETRIES)
+                .unwrap();
+        assert_eq!(root, expected_root);
+        assert_eq!(tree.get(&key).unwrap(), value);
+
+        // The store error is returned once the retries are exhausted
+        let mut tree = Smt::new(
+            H256::zero(),
+            FlakyStore {
+                failures: 5,
+                ..Default::default()
+            },
+        );
+        let result = update_with_retries(&mut tree, key, value, 2);
+        assert!(matches!(
+            result,
+            Err(Error::UpdateRetriesExhausted { retries: 2, .. })
+        ));
+        // The first attempt and the two retries have failed
+        assert_eq!(tree.store().failures, 2);
+    }
+
     #[test]
     fn test_restore_tree() {
         let mut tree = MerkleTree::<Sha256Hasher>::default();
@@ -569,6 +1088,67 @@ mod test {
         assert!(restored_tree.has_key(&pos_key).unwrap());
     }
 
+    #[test]
+    fn test_root_witness() {
+        let mut tree = MerkleTree::<Sha256Hasher>::default();
+
+        let key_prefix: Key =
+            Address::Internal(InternalAddress::Ibc).to_db_key().into();
+        let ibc_key = key_prefix.push(&"test".to_string()).unwrap();
+        let key_prefix: Key =
+            Address::Internal(InternalAddress::PoS).to_db_key().into();
+        let pos_key = key_prefix.push(&"test".to_string()).unwrap();
+        let account_key: Key = crate::types::address::xan().to_db_key().into();
+        let account_key = account_key.push(&"balance".to_string()).unwrap();
+
+        tree.update(&ibc_key, [1u8; 8]).unwrap();
+        tree.update(&pos_key, [2u8; 8]).unwrap();
+
+        let changes = vec![
+            (ibc_key.clone(), None),
+            (pos_key.clone(), Some([3u8; 8].to_vec())),
+            (account_key.clone(), Some([4u8; 8].to_vec())),
+        ];
+        let transition = tree.root_witness(&changes).unwrap();
+        assert_eq!(transition.old_root.as_slice(), &tree.root().0[..]);
+        assert!(transition.verify::<Sha256Hasher>().unwrap());
+
+        // Applying the changes must result in the witnessed new root
+        tree.delete(&ibc_key).unwrap();
+        tree.update(&pos_key, [3u8; 8]).unwrap();
+        tree.update(&account_key, [4u8; 8]).unwrap();
+        assert_eq!(transition.new_root.as_slice(), &tree.root().0[..]);
+
+        // A transition with a tampered new root must not verify
+        let mut tampered = transition;
+        tampered.new_root = H256::zero();
+        assert!(!tampered.verify::<Sha256Hasher>().unwrap());
+    }
+
+    #[test]
+    fn test_verify_existence_proof() {
+        let mut tree = MerkleTree::<Sha256Hasher>::default();
+        let key_prefix: Key =
+            Address::Internal(InternalAddress::Ibc).to_db_key().into();
+        let key = key_prefix.push(&"test".to_string()).unwrap();
+        let value = [1u8; 8].to_vec();
+        tree.update(&key, value.clone()).unwrap();
+
+        let root = tree.root();
+        let proof = tree.get_existence_proof(&key, value.clone()).unwrap();
+        let verify = |root: &MerkleRoot, value: Vec<u8>| {
+            MerkleTree::<Sha256Hasher>::verify_existence_proof(
+                root, &key, value, &proof,
+            )
+            .unwrap()
+        };
+        assert!(verify(&root, value.clone()));
+        // A different value
+        assert!(!verify(&root, [2u8; 8].to_vec()));
+        // A different root
+        assert!(!verify(&MerkleRoot(vec![0; 32]), value));
+    }
+
     #[test]
     fn test_proof() {
         let mut tree = MerkleTree::<Sha256Hasher>::default();
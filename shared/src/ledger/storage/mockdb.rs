@@ -138,6 +138,9 @@ impl DB for MockDB {
                             types::decode(bytes).map_err(Error::CodingError)?,
                         );
                     }
+                    "diffs" => {
+                        // ignore the diffs
+                    }
                     _ => unknown_key_error(path)?,
                 },
                 None => unknown_key_error(path)?,
@@ -345,37 +348,50 @@ impl DB for MockDB {
         unimplemented!()
     }
 
+    fn read_subspace_diff(
+        &self,
+        height: BlockHeight,
+        key: &Key,
+    ) -> Result<(Option<Vec<u8>>, Option<Vec<u8>>)> {
+        let (old_val_key, new_val_key) = Self::subspace_diff_keys(height, key)?;
+        let db = self.0.borrow();
+        Ok((db.get(&old_val_key).cloned(), db.get(&new_val_key).cloned()))
+    }
+
+    fn read_subspace_diff_heights(
+        &self,
+        key: &Key,
+        since: BlockHeight,
+    ) -> Result<Vec<BlockHeight>> {
+        let prefix = types::diff_heights_prefix(key);
+        let start = types::diff_height_key(key, since);
+        let upper_prefix = types::diff_heights_upper_bound(key);
+        Ok(self
+            .0
+            .borrow()
+            .range((Included(start), Excluded(upper_prefix)))
+            .filter_map(|(path, _)| {
+                path.strip_prefix(&prefix)
+                    .and_then(types::parse_diff_height)
+            })
+            .collect())
+    }
+
     fn write_subspace_val(
         &mut self,
-        _height: BlockHeight,
+        height: BlockHeight,
         key: &Key,
         value: impl AsRef<[u8]>,
     ) -> Result<i64> {
-        let value = value.as_ref();
-        let key = Key::parse("subspace").map_err(Error::KeyError)?.join(key);
-        let current_len = value.len() as i64;
-        Ok(
-            match self
-                .0
-                .borrow_mut()
-                .insert(key.to_string(), value.to_owned())
-            {
-                Some(prev_value) => current_len - prev_value.len() as i64,
-                None => current_len,
-            },
-        )
+        self.write_subspace_val_and_diff(height, key, value.as_ref())
     }
 
     fn delete_subspace_val(
         &mut self,
-        _height: BlockHeight,
+        height: BlockHeight,
         key: &Key,
     ) -> Result<i64> {
-        let key = Key::parse("subspace").map_err(Error::KeyError)?.join(key);
-        Ok(match self.0.borrow_mut().remove(&key.to_string()) {
-            Some(value) => value.len() as i64,
-            None => 0,
-        })
+        self.delete_subspace_val_and_diff(height, key)
     }
 
     fn batch() -> Self::WriteBatch {
@@ -391,34 +407,93 @@ impl DB for MockDB {
     fn batch_write_subspace_val(
         &self,
         _batch: &mut Self::WriteBatch,
-        _height: BlockHeight,
+        height: BlockHeight,
         key: &Key,
         value: impl AsRef<[u8]>,
     ) -> Result<i64> {
-        let value = value.as_ref();
-        let key = Key::parse("subspace").map_err(Error::KeyError)?.join(key);
-        let current_len = value.len() as i64;
-        Ok(
-            match self
-                .0
-                .borrow_mut()
-                .insert(key.to_string(), value.to_owned())
-            {
-                Some(prev_value) => current_len - prev_value.len() as i64,
-                None => current_len,
-            },
-        )
+        self.write_subspace_val_and_diff(height, key, value.as_ref())
     }
 
     fn batch_delete_subspace_val(
         &self,
         _batch: &mut Self::WriteBatch,
-        _height: BlockHeight,
+        height: BlockHeight,
         key: &Key,
     ) -> Result<i64> {
-        let key = Key::parse("subspace").map_err(Error::KeyError)?.join(key);
-        Ok(match self.0.borrow_mut().remove(&key.to_string()) {
-            Some(value) => value.len() as i64,
+        self.delete_subspace_val_and_diff(height, key)
+    }
+}
+
+impl MockDB {
+    /// The keys of the old and the new value in the diff of an account
+    /// subspace key-val changed at the given height.
+    fn subspace_diff_keys(
+        height: BlockHeight,
+        key: &Key,
+    ) -> Result<(String, String)> {
+        let key_prefix = Key::from(height.to_db_key())
+            .push(&"diffs".to_owned())
+            .map_err(Error::KeyError)?;
+        let old_val_key = key_prefix
+            .push(&"old".to_owned())
+            .map_err(Error::KeyError)?
+            .join(key)
+            .to_string();
+        let new_val_key = key_prefix
+            .push(&"new".to_owned())
+            .map_err(Error::KeyError)?
+            .join(key)
+            .to_string();
+        Ok((old_val_key, new_val_key))
+    }
+
+    /// Write the value with the given height and account subspace key and
+    /// persist its diff under the height. Returns the size difference from
+    /// previous value, if any, or the size of the value otherwise.
+    fn write_subspace_val_and_diff(
+        &self,
+        height: BlockHeight,
+        key: &Key,
+        value: &[u8],
+    ) -> Result<i64> {
+        let (old_val_key, new_val_key) = Self::subspace_diff_keys(height, key)?;
+        let subspace_key =
+            Key::parse("subspace").map_err(Error::KeyError)?.join(key);
+        let current_len = value.len() as i64;
+        let mut db = self.0.borrow_mut();
+        let size_diff =
+            match db.insert(subspace_key.to_string(), value.to_owned()) {
+                Some(prev_value) => {
+                    let size_diff = current_len - prev_value.len() as i64;
+                    db.insert(old_val_key, prev_value);
+                    size_diff
+                }
+                None => current_len,
+            };
+        db.insert(new_val_key, value.to_owned());
+        db.insert(types::diff_height_key(key, height), vec![]);
+        Ok(size_diff)
+    }
+
+    /// Delete the value with the given height and account subspace key and
+    /// persist its diff under the height. Returns the size of the removed
+    /// value, if any, 0 if no previous value was found.
+    fn delete_subspace_val_and_diff(
+        &self,
+        height: BlockHeight,
+        key: &Key,
+    ) -> Result<i64> {
+        let (old_val_key, _) = Self::subspace_diff_keys(height, key)?;
+        let subspace_key =
+            Key::parse("subspace").map_err(Error::KeyError)?.join(key);
+        let mut db = self.0.borrow_mut();
+        Ok(match db.remove(&subspace_key.to_string()) {
+            Some(prev_value) => {
+                let len = prev_value.len() as i64;
+                db.insert(old_val_key, prev_value);
+                db.insert(types::diff_height_key(key, height), vec![]);
+                len
+            }
             None => 0,
         })
     }
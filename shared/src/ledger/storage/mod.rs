@@ -1,5 +1,6 @@
 //! Ledger's state storage with key-value backed store and a merkle tree
 
+pub mod key_filter;
 mod merkle_tree;
 #[cfg(any(test, feature = "testing"))]
 pub mod mockdb;
@@ -7,7 +8,10 @@ pub mod types;
 pub mod write_log;
 
 use core::fmt::Debug;
+use std::collections::{BTreeSet, HashMap};
 
+use borsh::{BorshDeserialize, BorshSerialize};
+use sparse_merkle_tree::H256;
 #[cfg(not(feature = "ABCI"))]
 use tendermint::merkle::proof::Proof;
 #[cfg(feature = "ABCI")]
@@ -18,26 +22,40 @@ use super::parameters;
 use super::parameters::Parameters;
 use crate::ledger::gas::MIN_STORAGE_GAS;
 use crate::ledger::parameters::EpochDuration;
-use crate::ledger::storage::merkle_tree::{
-    Error as MerkleTreeError, MerkleRoot,
-};
+use crate::ledger::pos;
+use crate::ledger::storage::key_filter::KeyFilter;
+use crate::ledger::storage::merkle_tree::Error as MerkleTreeError;
 pub use crate::ledger::storage::merkle_tree::{
-    MerkleTree, MerkleTreeStoresRead, MerkleTreeStoresWrite, Sha256Hasher,
+    MembershipProof, MerkleRoot, MerkleTree, MerkleTreeStoresRead,
+    MerkleTreeStoresWrite, NonMembershipProof, RootTransition, Sha256Hasher,
     StorageHasher, StoreType,
 };
-use crate::types::address::{Address, EstablishedAddressGen, InternalAddress};
+use crate::proto::Signed;
+use crate::types::address::{
+    self, Address, EstablishedAddressGen, InternalAddress,
+};
 use crate::types::chain::{ChainId, CHAIN_ID_LENGTH};
+use crate::types::hash::Hash;
+use crate::types::key::{common, multi_pk_key, pk_key, VerifySigError};
 #[cfg(feature = "ferveo-tpke")]
 use crate::types::storage::TxQueue;
 use crate::types::storage::{
-    BlockHash, BlockHeight, Epoch, Epochs, Header, Key, KeySeg,
+    BlockHash, BlockHeight, DbKeySeg, Epoch, Epochs, Header, Key, KeySeg,
     BLOCK_HASH_LENGTH,
 };
-use crate::types::time::DateTimeUtc;
+use crate::types::time::{DateTimeUtc, Duration};
+use crate::types::token;
+use crate::types::transaction::ScheduledTx;
 
 /// A result of a function that may fail
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// The domain separator of the [`Storage::state_commitment`] hash
+const STATE_COMMITMENT_DOMAIN: &[u8] = b"anoma_state_commitment";
+
+/// The domain separator of the [`Storage::get_block_randomness`] hash
+const BLOCK_RANDOMNESS_DOMAIN: &[u8] = b"anoma_block_randomness";
+
 /// The storage data
 #[derive(Debug)]
 pub struct Storage<D, H>
@@ -57,12 +75,22 @@ where
     pub last_height: BlockHeight,
     /// The epoch of the committed block
     pub last_epoch: Epoch,
+    /// The merkle root of the committed block, if any
+    pub last_root: Option<MerkleRoot>,
     /// Minimum block height at which the next epoch may start
     pub next_epoch_min_start_height: BlockHeight,
     /// Minimum block time at which the next epoch may start
     pub next_epoch_min_start_time: DateTimeUtc,
     /// The current established address generator
     pub address_gen: EstablishedAddressGen,
+    /// The address that receives the collected fees
+    pub fee_sink: Address,
+    /// A filter of the keys present in storage to skip the look-up of keys
+    /// that are definitely absent
+    pub key_filter: KeyFilter,
+    /// The maximum number of writes and deletes in a block attributed to any
+    /// account by [`write_owner`], if limited
+    pub max_writes_per_block: Option<u64>,
     /// Wrapper txs to be decrypted in the next block proposal
     #[cfg(feature = "ferveo-tpke")]
     pub tx_queue: TxQueue,
@@ -81,6 +109,11 @@ pub struct BlockStorage<H: StorageHasher> {
     pub epoch: Epoch,
     /// Predecessor block epochs
     pub pred_epochs: Epochs,
+    /// The keys written or deleted in the block
+    pub changed_keys: BTreeSet<Key>,
+    /// The number of writes and deletes in the block per the account whose
+    /// sub-space they're in
+    pub write_counts: HashMap<Address, u64>,
 }
 
 #[allow(missing_docs)]
@@ -102,6 +135,161 @@ pub enum Error {
     BorshCodingError(std::io::Error),
     #[error("Merkle tree at the height {height} is not stored")]
     NoMerkleTree { height: BlockHeight },
+    #[error("The state at the height {height} is not retained")]
+    HeightNotRetained { height: BlockHeight },
+    #[error(
+        "Insufficient balance of {token} owned by {owner} to pay a fee of \
+         {amount}"
+    )]
+    InsufficientBalance {
+        owner: Address,
+        token: Address,
+        amount: token::Amount,
+    },
+    #[error(
+        "The balance {current} of {token} owned by {owner} would overflow by \
+         adding {added}"
+    )]
+    BalanceOverflow {
+        owner: Address,
+        token: Address,
+        current: token::Amount,
+        added: token::Amount,
+    },
+    #[error("The token {0} is not registered")]
+    UnknownToken(Address),
+    #[error(
+        "The account {owner} has reached the limit of {max} writes in a block"
+    )]
+    WriteRateLimited { owner: Address, max: u64 },
+    #[error("Duplicate entry for the address {0}")]
+    DuplicateAddress(Address),
+    #[error(
+        "The signed chain ID {signed} doesn't match the chain ID {chain_id}"
+    )]
+    ChainIdMismatch { signed: String, chain_id: ChainId },
+    #[error("The merkle root {actual} doesn't match the expected {expected}")]
+    RootMismatch {
+        expected: MerkleRoot,
+        actual: MerkleRoot,
+    },
+    #[error("Token supplies mismatch as (token, expected, actual): {0:?}")]
+    SupplyAudit(Vec<(Address, u64, u64)>),
+    #[error("The total supply of the token {0} overflows")]
+    SupplyOverflow(Address),
+    #[error("Validators without a public key: {0:?}")]
+    ValidatorsWithoutPk(Vec<Address>),
+    #[error("The block header is not set")]
+    NoBlockHeader,
+    #[error("The account {0} has no free index for another public key")]
+    TooManyPks(Address),
+    #[error("The account {0} exists")]
+    AccountExists(Address),
+    #[error(
+        "The tx timestamp {tx_time:?} is not within {tolerance} of the block \
+         time {block_time:?}"
+    )]
+    TimestampOutOfBounds {
+        tx_time: DateTimeUtc,
+        block_time: DateTimeUtc,
+        tolerance: Duration,
+    },
+}
+
+#[allow(missing_docs)]
+#[derive(Error, Debug)]
+pub enum BalanceProofError {
+    #[error("Invalid signature of the merkle root: {0}")]
+    InvalidRootSignature(VerifySigError),
+    #[error("Invalid balance proof: {0}")]
+    InvalidProof(MerkleTreeError),
+    #[error("The balance is not included in the signed merkle root")]
+    NotIncluded,
+}
+
+/// A proof of an update of token balances, which takes the merkle root from
+/// `before` to `after`. It can be verified without the full tree with
+/// [`verify_update_proof`].
+#[derive(Clone, Debug)]
+pub struct UpdateProof {
+    /// The merkle root before the update
+    pub before: MerkleRoot,
+    /// The merkle root after the update
+    pub after: MerkleRoot,
+    /// The witness of the root transition
+    pub transition: RootTransition,
+}
+
+/// A compact summary of a block, which is the minimal header tracked by light
+/// clients.
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct BlockSummary {
+    /// The height of the block
+    pub height: BlockHeight,
+    /// The merkle root of the block's state
+    pub root: MerkleRoot,
+    /// The hash of the block
+    pub block_hash: BlockHash,
+    /// The ID of the chain
+    pub chain_id: ChainId,
+    /// The number of the keys written or deleted in the block
+    pub num_changed_keys: u64,
+    /// The canonical hash of the PoS active validator set in the current
+    /// epoch, see [`Storage::validator_set_hash`]
+    pub validator_set_hash: Hash,
+}
+
+/// A read-only view of the storage values as they were at a past block height.
+/// The values are reconstructed from the diffs of the values changed after
+/// the height.
+#[derive(Debug)]
+pub struct ReadOnlyStorage<'a, D, H>
+where
+    D: DB + for<'iter> DBIter<'iter>,
+    H: StorageHasher,
+{
+    storage: &'a Storage<D, H>,
+    height: BlockHeight,
+}
+
+impl<'a, D, H> ReadOnlyStorage<'a, D, H>
+where
+    D: DB + for<'iter> DBIter<'iter>,
+    H: StorageHasher,
+{
+    /// Get the height of the view
+    pub fn height(&self) -> BlockHeight {
+        self.height
+    }
+
+    /// Returns the value of the given key as it was at the view's height
+    pub fn read(&self, key: &Key) -> Result<Option<Vec<u8>>> {
+        // The value before its first change after the height, if any
+        let heights = self
+            .storage
+            .db
+            .read_subspace_diff_heights(key, self.height.next_height())?;
+        match heights.first() {
+            Some(height) => {
+                let (old_value, _) =
+                    self.storage.db.read_subspace_diff(*height, key)?;
+                Ok(old_value)
+            }
+            None => Ok(self.storage.read(key)?.0),
+        }
+    }
+
+    /// Returns the balance of the `token` owned by the `owner` as it was at
+    /// the view's height
+    pub fn read_balance(
+        &self,
+        token: &Address,
+        owner: &Address,
+    ) -> Result<Option<token::Amount>> {
+        self.read(&token::balance_key(token, owner))?
+            .map(|bytes| types::decode(bytes).map_err(Error::CodingError))
+            .transpose()
+    }
 }
 
 /// The block's state as stored in the database.
@@ -193,6 +381,24 @@ pub trait DB: std::fmt::Debug {
         _height: BlockHeight,
     ) -> Result<Option<Vec<u8>>>;
 
+    /// Read the diff of the value for account subspace key changed at the
+    /// given height from the DB. Returns the old and the new value, or `None`
+    /// for a value that didn't exist before or that has been deleted. Both
+    /// are `None` when the key hasn't been changed at the given height.
+    fn read_subspace_diff(
+        &self,
+        height: BlockHeight,
+        key: &Key,
+    ) -> Result<(Option<Vec<u8>>, Option<Vec<u8>>)>;
+
+    /// Read the block heights from `since` at which the value for account
+    /// subspace key has been changed, in ascending order.
+    fn read_subspace_diff_heights(
+        &self,
+        key: &Key,
+        since: BlockHeight,
+    ) -> Result<Vec<BlockHeight>>;
+
     /// Write the value with the given height and account subspace key to the
     /// DB. Returns the size difference from previous value, if any, or the
     /// size of the value otherwise.
@@ -279,6 +485,8 @@ where
             height: BlockHeight::default(),
             epoch: Epoch::default(),
             pred_epochs: Epochs::default(),
+            changed_keys: BTreeSet::default(),
+            write_counts: HashMap::default(),
         };
         Storage::<D, H> {
             db: D::open(db_path, cache),
@@ -287,11 +495,15 @@ where
             header: None,
             last_height: BlockHeight(0),
             last_epoch: Epoch::default(),
+            last_root: None,
             next_epoch_min_start_height: BlockHeight::default(),
             next_epoch_min_start_time: DateTimeUtc::now(),
             address_gen: EstablishedAddressGen::new(
                 "Privacy is a function of liberty.",
             ),
+            fee_sink: Address::Internal(InternalAddress::Treasury),
+            key_filter: KeyFilter::default(),
+            max_writes_per_block: None,
             #[cfg(feature = "ferveo-tpke")]
             tx_queue: TxQueue::default(),
         }
@@ -320,6 +532,7 @@ where
             self.block.pred_epochs = pred_epochs;
             self.last_height = height;
             self.last_epoch = epoch;
+            self.last_root = Some(self.block.tree.root());
             self.next_epoch_min_start_height = next_epoch_min_start_height;
             self.next_epoch_min_start_time = next_epoch_min_start_time;
             self.address_gen = address_gen;
@@ -327,6 +540,7 @@ where
             {
                 self.tx_queue = tx_queue;
             }
+            self.rebuild_key_filter();
             tracing::debug!("Loaded storage from DB");
         } else {
             tracing::info!("No state could be found");
@@ -361,21 +575,137 @@ where
         };
         self.db.write_block(state)?;
         self.last_height = self.block.height;
+        self.last_root = Some(self.merkle_root());
         self.header = None;
         Ok(())
     }
 
+    /// Compact the merkle tree by rebuilding it without the nodes that are no
+    /// longer referenced, e.g. after many deletions. The tree is replaced only
+    /// if the root of the compacted tree is unchanged.
+    pub fn compact(&mut self) -> Result<()> {
+        let compacted = self.block.tree.compacted()?;
+        let expected = self.block.tree.root();
+        let actual = compacted.root();
+        if actual != expected {
+            return Err(Error::RootMismatch { expected, actual });
+        }
+        self.block.tree = compacted;
+        Ok(())
+    }
+
+    /// Check that the merkle tree is consistent with the values in the DB, by
+    /// rebuilding the tree from all the stored keys and comparing the roots.
+    pub fn verify_consistency(&self) -> Result<()> {
+        let mut tree = MerkleTree::<H>::default();
+        for (key, value) in self.iter_all_keys() {
+            tree.update(&key, value)?;
+        }
+        let expected = tree.root();
+        let actual = self.merkle_root();
+        if actual != expected {
+            return Err(Error::RootMismatch { expected, actual });
+        }
+        Ok(())
+    }
+
+    /// Check the integrity of the whole storage, e.g. after it's loaded from
+    /// disk. This checks the [`Storage::verify_consistency`], the token
+    /// supplies with [`Storage::audit_supplies`] and that there are no
+    /// [`Storage::validators_without_pk`]. Unlike the separate checks, all the
+    /// failures are reported together.
+    pub fn self_check(
+        &self,
+        expected_supplies: &HashMap<Address, u64>,
+    ) -> std::result::Result<(), Vec<Error>> {
+        let mut errors = vec![];
+        if let Err(error) = self.verify_consistency() {
+            errors.push(error);
+        }
+        if let Err(error) = self.audit_supplies(expected_supplies) {
+            errors.push(error);
+        }
+        match self.validators_without_pk() {
+            Ok(validators) if validators.is_empty() => {}
+            Ok(validators) => {
+                errors.push(Error::ValidatorsWithoutPk(validators))
+            }
+            Err(error) => errors.push(error),
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
     /// Find the root hash of the merkle tree
     pub fn merkle_root(&self) -> MerkleRoot {
         self.block.tree.root()
     }
 
+    /// Check that the root hash of the merkle tree is the expected one, e.g.
+    /// to detect a corrupted store after the state is loaded from disk.
+    pub fn verify_root(&self, expected: &MerkleRoot) -> Result<()> {
+        let actual = self.merkle_root();
+        if &actual != expected {
+            return Err(Error::RootMismatch {
+                expected: expected.clone(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Get a commitment to the current block's state, which binds the merkle
+    /// root to the block height, the chain ID and the block hash.
+    pub fn state_commitment(&self) -> H256 {
+        let chain_id = self.chain_id.as_str().as_bytes();
+        let mut bytes = STATE_COMMITMENT_DOMAIN.to_vec();
+        bytes.extend(self.merkle_root().0);
+        bytes.extend(self.block.height.0.to_be_bytes());
+        // The chain ID has a variable length, so it's prefixed with it
+        bytes.extend((chain_id.len() as u64).to_be_bytes());
+        bytes.extend(chain_id);
+        bytes.extend(self.block.hash.0);
+        H::hash(bytes)
+    }
+
+    /// Get a compact summary of the current block for light clients.
+    pub fn block_summary(&self) -> Result<BlockSummary> {
+        Ok(BlockSummary {
+            height: self.block.height,
+            root: self.merkle_root(),
+            block_hash: self.block.hash.clone(),
+            chain_id: self.chain_id.clone(),
+            num_changed_keys: self.block.changed_keys.len() as u64,
+            validator_set_hash: self.validator_set_hash()?,
+        })
+    }
+
     /// Check if the given key is present in storage. Returns the result and the
     /// gas cost.
     pub fn has_key(&self, key: &Key) -> Result<(bool, u64)> {
+        if !self.key_filter.may_contain(key) {
+            return Ok((false, key.len() as _));
+        }
         Ok((self.block.tree.has_key(key)?, key.len() as _))
     }
 
+    /// Rebuild the [`Storage::key_filter`] from all the keys in the DB. The
+    /// filter is sized for twice the number of keys, so that it keeps its
+    /// false positive rate as more keys are written until the next rebuild.
+    fn rebuild_key_filter(&mut self) {
+        let num_keys = self.iter_all_keys().count();
+        let mut key_filter = KeyFilter::with_capacity(
+            num_keys.saturating_mul(2).max(key_filter::DEFAULT_CAPACITY),
+        );
+        for (key, _value) in self.iter_all_keys() {
+            key_filter.insert(&key);
+        }
+        self.key_filter = key_filter;
+    }
+
     /// Returns a value from the specified subspace and the gas cost
     pub fn read(&self, key: &Key) -> Result<(Option<Vec<u8>>, u64)> {
         tracing::debug!("storage read key {}", key);
@@ -413,6 +743,48 @@ where
         }
     }
 
+    /// Returns a read-only view of the storage at the given height, for queries
+    /// of a past state. The state of a height before the current block is
+    /// retained only if the block's merkle tree is stored.
+    pub fn at_height(
+        &self,
+        height: BlockHeight,
+    ) -> Result<ReadOnlyStorage<'_, D, H>> {
+        if height < self.block.height
+            && self.db.read_merkle_tree_stores(height)?.is_none()
+        {
+            return Err(Error::HeightNotRetained { height });
+        }
+        Ok(ReadOnlyStorage {
+            storage: self,
+            height,
+        })
+    }
+
+    /// Returns the balance of the `token` owned by the `owner` at every block
+    /// height from `since` up to the current block at which it has been
+    /// changed, in ascending order of heights. A deleted balance is zero.
+    pub fn account_history(
+        &self,
+        token: &Address,
+        owner: &Address,
+        since: BlockHeight,
+    ) -> Result<Vec<(BlockHeight, token::Amount)>> {
+        let key = token::balance_key(token, owner);
+        let mut history = vec![];
+        for height in self.db.read_subspace_diff_heights(&key, since)? {
+            let balance = match self.db.read_subspace_diff(height, &key)? {
+                (_, Some(new_value)) => {
+                    types::decode(new_value).map_err(Error::CodingError)?
+                }
+                (Some(_old_value), None) => token::Amount::default(),
+                (None, None) => continue,
+            };
+            history.push((height, balance));
+        }
+        Ok(history)
+    }
+
     /// Returns a prefix iterator and the gas cost
     pub fn iter_prefix(
         &self,
@@ -421,6 +793,31 @@ where
         (self.db.iter_prefix(prefix), prefix.len() as _)
     }
 
+    /// Returns an iterator over all the keys and their values in storage,
+    /// ordered by the keys' string representation.
+    pub fn iter_all_keys(&self) -> impl Iterator<Item = (Key, Vec<u8>)> + '_ {
+        let (iter, _gas) = self.iter_prefix(&Key { segments: vec![] });
+        iter.map(|(key, value, _gas)| {
+            let key = Key::parse(key).expect("Stored keys should be valid");
+            (key, value)
+        })
+    }
+
+    /// Estimate the size of the storage in bytes, e.g. for metrics. This is the
+    /// size of all the keys and values in the account subspace plus the size
+    /// of the encoded merkle tree stores.
+    pub fn approx_size_bytes(&self) -> usize {
+        let subspace_size: usize = self
+            .iter_all_keys()
+            .map(|(key, value)| key.to_string().len() + value.len())
+            .sum();
+        let stores = self.block.tree.stores();
+        let tree_size: usize = StoreType::iter()
+            .map(|store_type| types::encode(stores.store(store_type)).len())
+            .sum();
+        subspace_size + tree_size
+    }
+
     /// Write a value to the specified subspace and returns the gas cost and the
     /// size difference
     pub fn write(
@@ -429,7 +826,10 @@ where
         value: impl AsRef<[u8]> + Clone,
     ) -> Result<(u64, i64)> {
         tracing::debug!("storage write key {}", key,);
+        self.count_writes([key])?;
         self.block.tree.update(key, value.clone())?;
+        self.key_filter.insert(key);
+        self.block.changed_keys.insert(key.clone());
 
         let len = value.as_ref().len();
         let gas = key.len() + len;
@@ -443,7 +843,9 @@ where
     pub fn delete(&mut self, key: &Key) -> Result<(u64, i64)> {
         let mut deleted_bytes_len = 0;
         if self.has_key(key)?.0 {
+            self.count_writes([key])?;
             self.block.tree.delete(key)?;
+            self.block.changed_keys.insert(key.clone());
             deleted_bytes_len =
                 self.db.delete_subspace_val(self.last_height, key)?;
         }
@@ -451,6 +853,480 @@ where
         Ok((gas as _, deleted_bytes_len))
     }
 
+    /// Check that writing or deleting all the given keys keeps every account
+    /// that owns any of them, as attributed by [`write_owner`], within the
+    /// [`Storage::max_writes_per_block`]. A key given more than once counts
+    /// as many times.
+    pub fn check_write_limit<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a Key>,
+    ) -> Result<()> {
+        let max = match self.max_writes_per_block {
+            Some(max) => max,
+            None => return Ok(()),
+        };
+        for (owner, added) in writes_per_owner(keys) {
+            let current =
+                self.block.write_counts.get(owner).copied().unwrap_or(0);
+            if current + added > max {
+                return Err(Error::WriteRateLimited {
+                    owner: owner.clone(),
+                    max,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Count writes and deletes of the given keys towards the limits of the
+    /// accounts that own them. The keys are checked all together first with
+    /// [`Storage::check_write_limit`], so when any of the accounts would
+    /// exceed its limit, none of the keys is counted.
+    pub fn count_writes<'a>(
+        &mut self,
+        keys: impl IntoIterator<Item = &'a Key> + Clone,
+    ) -> Result<()> {
+        if self.max_writes_per_block.is_none() {
+            return Ok(());
+        }
+        self.check_write_limit(keys.clone())?;
+        for (owner, added) in writes_per_owner(keys) {
+            *self.block.write_counts.entry(owner.clone()).or_default() += added;
+        }
+        Ok(())
+    }
+
+    /// Transfer a fee of the given token `amount` from the `payer` to the
+    /// [`Storage::fee_sink`]. The token must be registered, i.e. its account
+    /// must exist. Returns the gas cost.
+    pub fn collect_fee(
+        &mut self,
+        payer: &Address,
+        token: &Address,
+        amount: token::Amount,
+    ) -> Result<u64> {
+        let (token_exists, mut gas) = self.exists(token)?;
+        if !token_exists {
+            return Err(Error::UnknownToken(token.clone()));
+        }
+        let payer_key = token::balance_key(token, payer);
+        let (payer_balance, read_gas) = self.read_balance(&payer_key)?;
+        gas += read_gas;
+        let mut payer_balance = match payer_balance {
+            Some(balance) if balance >= amount => balance,
+            _ => {
+                return Err(Error::InsufficientBalance {
+                    owner: payer.clone(),
+                    token: token.clone(),
+                    amount,
+                });
+            }
+        };
+        payer_balance.spend(&amount);
+
+        // The sink's balance is checked before any of the balances is written
+        let sink_key = token::balance_key(token, &self.fee_sink);
+        let (sink_balance, read_gas) = self.read_balance(&sink_key)?;
+        gas += read_gas;
+        let current = if payer == &self.fee_sink {
+            payer_balance
+        } else {
+            sink_balance.unwrap_or_default()
+        };
+        let sink_balance = u64::from(current)
+            .checked_add(amount.into())
+            .map(token::Amount::from)
+            .ok_or_else(|| Error::BalanceOverflow {
+                owner: self.fee_sink.clone(),
+                token: token.clone(),
+                current,
+                added: amount,
+            })?;
+
+        // Both writes must be within the accounts' limits, so that the payer
+        // is never debited without crediting the sink
+        self.check_write_limit([&payer_key, &sink_key])?;
+        let (write_gas, _) =
+            self.write(&payer_key, types::encode(&payer_balance))?;
+        gas += write_gas;
+        let (write_gas, _) =
+            self.write(&sink_key, types::encode(&sink_balance))?;
+        Ok(gas + write_gas)
+    }
+
+    /// Exchange the balances of the given `token` between the accounts `a` and
+    /// `b`. A missing balance is treated as zero and it's swapped as missing,
+    /// so swapping twice restores the original state. Both balances are read
+    /// and decoded before any of them is written. Returns the gas cost.
+    pub fn swap_balances(
+        &mut self,
+        a: &Address,
+        b: &Address,
+        token: &Address,
+    ) -> Result<u64> {
+        let a_key = token::balance_key(token, a);
+        let b_key = token::balance_key(token, b);
+        let (a_balance, a_gas) = self.read_balance(&a_key)?;
+        let (b_balance, b_gas) = self.read_balance(&b_key)?;
+        let mut gas = a_gas + b_gas;
+        // Unless both balances are missing, both keys are written or deleted
+        if a_balance.is_some() || b_balance.is_some() {
+            self.check_write_limit([&a_key, &b_key])?;
+        }
+        for (key, balance) in [(a_key, b_balance), (b_key, a_balance)] {
+            let (write_gas, _) = match balance {
+                Some(balance) => self.write(&key, types::encode(&balance))?,
+                None => self.delete(&key)?,
+            };
+            gas += write_gas;
+        }
+        Ok(gas)
+    }
+
+    /// Check if the account doesn't own any of the known tokens (see
+    /// [`address::tokens`]), i.e. all of its balances of these tokens, if
+    /// any, are zero. Returns the result and the gas cost.
+    pub fn is_empty_account(&self, addr: &Address) -> Result<(bool, u64)> {
+        let mut gas = 0;
+        for token in address::tokens().keys() {
+            let (value, read_gas) =
+                self.read(&token::balance_key(token, addr))?;
+            gas += read_gas;
+            if let Some(value) = value {
+                let balance: token::Amount =
+                    types::decode(value).map_err(Error::CodingError)?;
+                if balance != token::Amount::default() {
+                    return Ok((false, gas));
+                }
+            }
+        }
+        Ok((true, gas))
+    }
+
+    /// Find the validators that have a state or stake in the PoS storage, but
+    /// no public key registered for their account, ordered by the address.
+    /// This is meant for diagnostics of misconfigured validators.
+    pub fn validators_without_pk(&self) -> Result<Vec<Address>> {
+        let (iter, _gas) = self.iter_prefix(&pos::validators_prefix());
+        let mut validators = BTreeSet::new();
+        for (key, _value, _gas) in iter {
+            let key = Key::parse(key).map_err(Error::KeyError)?;
+            if let Some(validator) = pos::is_validator_state_key(&key)
+                .or_else(|| pos::is_validator_total_deltas_key(&key))
+            {
+                validators.insert(validator.clone());
+            }
+        }
+        let mut missing = vec![];
+        for validator in validators {
+            let (has_pk, _gas) = self.has_key(&pk_key(&validator))?;
+            if !has_pk {
+                missing.push(validator);
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Initialize the given genesis accounts with their public key and balance
+    /// of the native token in micro units. Fails without writing anything when
+    /// an address is repeated. Returns the resulting merkle root.
+    pub fn init_accounts(
+        &mut self,
+        accounts: &[(Address, common::PublicKey, u64)],
+    ) -> Result<MerkleRoot> {
+        let addresses: Vec<(Address, ())> = accounts
+            .iter()
+            .map(|(addr, _pk, _balance)| (addr.clone(), ()))
+            .collect();
+        validate_unique_addresses(&addresses)?;
+        let native_token = crate::types::address::xan();
+        for (addr, pk, balance) in accounts {
+            self.write(&pk_key(addr), types::encode(pk))?;
+            let balance = token::Amount::from(*balance);
+            self.write(
+                &token::balance_key(&native_token, addr),
+                types::encode(&balance),
+            )?;
+        }
+        Ok(self.merkle_root())
+    }
+
+    /// Register another public key of the given account under the next free
+    /// [`multi_pk_key`]. Returns the index of the key and the gas cost.
+    pub fn add_pk(
+        &mut self,
+        owner: &Address,
+        pk: &common::PublicKey,
+    ) -> Result<(u8, u64)> {
+        let mut gas = 0;
+        for index in 0..=u8::MAX {
+            let key = multi_pk_key(owner, index);
+            let (present, has_gas) = self.has_key(&key)?;
+            gas += has_gas;
+            if !present {
+                let (write_gas, _) = self.write(&key, types::encode(pk))?;
+                return Ok((index, gas + write_gas));
+            }
+        }
+        Err(Error::TooManyPks(owner.clone()))
+    }
+
+    /// Read the public keys of the given account registered with
+    /// [`Storage::add_pk`], ordered by their index. Returns the keys and the
+    /// gas cost.
+    pub fn pks_of(
+        &self,
+        owner: &Address,
+    ) -> Result<(Vec<common::PublicKey>, u64)> {
+        let mut pks = vec![];
+        let mut gas = 0;
+        for index in 0..=u8::MAX {
+            let (value, read_gas) = self.read(&multi_pk_key(owner, index))?;
+            gas += read_gas;
+            match value {
+                Some(value) => {
+                    pks.push(types::decode(value).map_err(Error::CodingError)?)
+                }
+                None => break,
+            }
+        }
+        Ok((pks, gas))
+    }
+
+    /// Check if the given public key is one of the keys of the given account
+    /// registered with [`Storage::add_pk`]. Returns the result and the gas
+    /// cost.
+    pub fn is_pk_registered(
+        &self,
+        owner: &Address,
+        pk: &common::PublicKey,
+    ) -> Result<(bool, u64)> {
+        let (pks, gas) = self.pks_of(owner)?;
+        Ok((pks.contains(pk), gas))
+    }
+
+    /// Check that the balances of each of the tokens in `expected` sum up to
+    /// its expected total supply. All the mismatched tokens are reported
+    /// together in [`Error::SupplyAudit`], ordered by the token address.
+    pub fn audit_supplies(
+        &self,
+        expected: &HashMap<Address, u64>,
+    ) -> Result<()> {
+        let mut mismatches = vec![];
+        for (token, expected_supply) in expected {
+            let (supply, _gas) = self.token_supply(token)?;
+            if supply != *expected_supply {
+                mismatches.push((token.clone(), *expected_supply, supply));
+            }
+        }
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            mismatches.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+            Err(Error::SupplyAudit(mismatches))
+        }
+    }
+
+    /// Sum up the balances of the given token in micro units. Returns the
+    /// total supply and the gas cost, or [`Error::SupplyOverflow`] if the sum
+    /// doesn't fit in `u64`.
+    fn token_supply(&self, token: &Address) -> Result<(u64, u64)> {
+        let (iter, mut gas) = self.iter_prefix(&token::balance_prefix(token));
+        let mut supply: u64 = 0;
+        for (key, value, iter_gas) in iter {
+            gas += iter_gas;
+            let key = Key::parse(key).map_err(Error::KeyError)?;
+            if token::is_balance_key(token, &key).is_none() {
+                continue;
+            }
+            let balance: token::Amount =
+                types::decode(value).map_err(Error::CodingError)?;
+            supply = supply
+                .checked_add(balance.into())
+                .ok_or_else(|| Error::SupplyOverflow(token.clone()))?;
+        }
+        Ok((supply, gas))
+    }
+
+    /// Compute the root of a tree over just the balances of the given token,
+    /// which is distinct from the [`Storage::merkle_root`]. The proofs of the
+    /// balances of a single token against it are smaller than against the
+    /// root of the whole storage.
+    pub fn token_subroot(&self, token: &Address) -> Result<H256> {
+        let (iter, _gas) = self.iter_prefix(&token::balance_prefix(token));
+        let mut balances = vec![];
+        for (key, value, _gas) in iter {
+            let key = Key::parse(key).map_err(Error::KeyError)?;
+            if token::is_balance_key(token, &key).is_some() {
+                balances.push((key, value));
+            }
+        }
+        Ok(merkle_tree::standalone_root::<H>(balances)?)
+    }
+
+    /// Write the metadata of the given token. Returns the gas cost.
+    pub fn set_token_meta(
+        &mut self,
+        token: &Address,
+        meta: &token::TokenMeta,
+    ) -> Result<u64> {
+        let key = token::token_meta_key(token);
+        let (gas, _) = self.write(&key, types::encode(meta))?;
+        Ok(gas)
+    }
+
+    /// Read the metadata of the given token, if it's registered. Returns the
+    /// metadata and the gas cost.
+    pub fn get_token_meta(
+        &self,
+        token: &Address,
+    ) -> Result<(Option<token::TokenMeta>, u64)> {
+        let (value, gas) = self.read(&token::token_meta_key(token))?;
+        let meta = value
+            .map(|bytes| types::decode(bytes).map_err(Error::CodingError))
+            .transpose()?;
+        Ok((meta, gas))
+    }
+
+    /// Check if the `amount` of the `token` can be minted without its total
+    /// supply exceeding the cap in the token's metadata. Returns the result
+    /// and the gas cost, or [`Error::UnknownToken`] for a token without
+    /// metadata.
+    pub fn can_mint(
+        &self,
+        token: &Address,
+        amount: token::Amount,
+    ) -> Result<(bool, u64)> {
+        let (meta, mut gas) = self.get_token_meta(token)?;
+        let meta = meta.ok_or_else(|| Error::UnknownToken(token.clone()))?;
+        let cap = match meta.cap {
+            Some(cap) => cap,
+            None => return Ok((true, gas)),
+        };
+        let (supply, supply_gas) = self.token_supply(token)?;
+        gas += supply_gas;
+        let within_cap = supply
+            .checked_add(amount.into())
+            .map(|supply| supply <= cap)
+            .unwrap_or(false);
+        Ok((within_cap, gas))
+    }
+
+    /// Delete the values written with a time-to-live that expire at the
+    /// current block height, together with their expiry heights. Only the
+    /// entries at the current height in the index of values written with a
+    /// time-to-live are visited, and they are all deleted. A value whose
+    /// expiry height has changed since its entry was written is kept, and so
+    /// is a value whose expiry height cannot be decoded. Returns the keys of
+    /// the deleted values.
+    pub fn prune_expired(&mut self) -> Result<Vec<Key>> {
+        let height = self.block.height;
+        let (iter, _gas) = self.iter_prefix(&Key::ttl_index_prefix(height));
+        let mut entries = vec![];
+        for (index_key, _value, _gas) in iter {
+            let index_key = match Key::parse(&index_key) {
+                Ok(index_key) => index_key,
+                Err(err) => {
+                    tracing::warn!(
+                        "Skipping an invalid time-to-live index key {}: {}",
+                        index_key,
+                        err
+                    );
+                    continue;
+                }
+            };
+            if let Some((_expiry, key)) = index_key.is_ttl_index() {
+                entries.push((index_key, key));
+            }
+        }
+        let mut pruned = vec![];
+        for (index_key, key) in entries {
+            self.delete(&index_key)?;
+            let ttl_key = match key.ttl() {
+                Some(ttl_key) => ttl_key,
+                None => continue,
+            };
+            let (expiry, _gas) = self.read(&ttl_key)?;
+            let expiry: Option<BlockHeight> =
+                match expiry.map(types::decode).transpose() {
+                    Ok(expiry) => expiry,
+                    Err(err) => {
+                        tracing::warn!(
+                            "Skipping an undecodable expiry height at {}: {}",
+                            ttl_key,
+                            err
+                        );
+                        continue;
+                    }
+                };
+            if expiry == Some(height) {
+                tracing::debug!("Pruning an expired value at {}", key);
+                self.delete(&ttl_key)?;
+                self.delete(&key)?;
+                pruned.push(key);
+            }
+        }
+        Ok(pruned)
+    }
+
+    /// Get the transactions scheduled to be applied at the given height, with
+    /// their keys, ordered by the key of their entry in the index of scheduled
+    /// transactions. The index entries are left in place, they should be
+    /// deleted together with the applied transactions. Invalid index entries
+    /// and transactions that cannot be decoded are logged and skipped.
+    pub fn scheduled_txs(
+        &self,
+        height: BlockHeight,
+    ) -> Result<Vec<(Key, ScheduledTx)>> {
+        let (iter, _gas) =
+            self.iter_prefix(&Key::scheduled_tx_index_prefix(height));
+        let mut scheduled = vec![];
+        for (index_key, _value, _gas) in iter {
+            let parsed = Key::parse(&index_key).ok();
+            let entry = parsed.as_ref().and_then(Key::is_scheduled_tx_index);
+            let key = match entry {
+                Some((_height, scheduler, id)) => {
+                    Key::scheduled_tx(scheduler, id)
+                }
+                None => {
+                    // The number of the scheduled txs is kept at the prefix
+                    if index_key != Key::scheduled_tx_count(height).to_string()
+                    {
+                        tracing::warn!(
+                            "Skipping an invalid scheduled tx index key {}",
+                            index_key
+                        );
+                    }
+                    continue;
+                }
+            };
+            let (value, _gas) = self.read(&key)?;
+            let value = match value {
+                Some(value) => value,
+                None => continue,
+            };
+            match types::decode::<ScheduledTx>(value) {
+                Ok(tx) if tx.height == height => scheduled.push((key, tx)),
+                Ok(_) => {}
+                Err(err) => tracing::warn!(
+                    "Skipping an undecodable scheduled tx at {}: {}",
+                    key,
+                    err
+                ),
+            }
+        }
+        Ok(scheduled)
+    }
+
+    /// Read and decode a token balance with the given key and the gas cost
+    fn read_balance(&self, key: &Key) -> Result<(Option<token::Amount>, u64)> {
+        let (value, gas) = self.read(key)?;
+        let balance = value
+            .map(|bytes| types::decode(bytes).map_err(Error::CodingError))
+            .transpose()?;
+        Ok((balance, gas))
+    }
+
     /// Set the block header.
     /// The header is not in the Merkle tree as it's tracked by Tendermint.
     /// Hence, we don't update the tree when this is set.
@@ -468,6 +1344,8 @@ where
     ) -> Result<()> {
         self.block.hash = hash;
         self.block.height = height;
+        self.block.changed_keys.clear();
+        self.block.write_counts.clear();
         Ok(())
     }
 
@@ -493,6 +1371,19 @@ where
         (self.chain_id.to_string(), CHAIN_ID_LENGTH as _)
     }
 
+    /// Check that the chain ID embedded in some signed data is the ID of this
+    /// chain, to prevent replaying the data from another chain.
+    pub fn verify_chain_binding(&self, signed_chain_id: &str) -> Result<()> {
+        if self.chain_id.as_str() == signed_chain_id {
+            Ok(())
+        } else {
+            Err(Error::ChainIdMismatch {
+                signed: signed_chain_id.to_owned(),
+                chain_id: self.chain_id.clone(),
+            })
+        }
+    }
+
     /// Get the current (yet to be committed) block height
     pub fn get_block_height(&self) -> (BlockHeight, u64) {
         (self.block.height, MIN_STORAGE_GAS)
@@ -503,6 +1394,75 @@ where
         (self.block.hash.clone(), BLOCK_HASH_LENGTH as _)
     }
 
+    /// Get the randomness of the current block, which is the hash of the block
+    /// hash and height. It's deterministic, so that it's the same for all the
+    /// nodes applying the block, but it's not unpredictable, because it can be
+    /// computed as soon as the block is known.
+    pub fn get_block_randomness(&self) -> (H256, u64) {
+        let mut bytes = BLOCK_RANDOMNESS_DOMAIN.to_vec();
+        bytes.extend(self.block.hash.0);
+        bytes.extend(self.block.height.0.to_be_bytes());
+        (H::hash(bytes), BLOCK_HASH_LENGTH as _)
+    }
+
+    /// Get the merkle root of the last committed block, i.e. the block before
+    /// the current one. Returns `None` when no block has been committed yet.
+    pub fn get_prev_block_root(&self) -> (Option<MerkleRoot>, u64) {
+        (self.last_root.clone(), MIN_STORAGE_GAS)
+    }
+
+    /// Get a witness of the transition of the current block's merkle root after
+    /// applying the given changes, which can be verified by a light client
+    /// without the full tree. A `None` value is a deletion.
+    pub fn root_witness(
+        &self,
+        changes: &[(Key, Option<Vec<u8>>)],
+    ) -> Result<RootTransition> {
+        Ok(self.block.tree.root_witness(changes)?)
+    }
+
+    /// Get a proof of an update of the balances of the given `token` to the
+    /// new amounts in `changes`, which takes the current block's merkle root
+    /// from `before` to `after`. Returns an error when `before` is not the
+    /// current root or when the changes don't result in `after`.
+    pub fn update_proof(
+        &self,
+        token: &Address,
+        before: &MerkleRoot,
+        changes: &[(Address, token::Amount)],
+        after: &MerkleRoot,
+    ) -> Result<UpdateProof> {
+        let root = self.merkle_root();
+        if &root != before {
+            return Err(Error::RootMismatch {
+                expected: before.clone(),
+                actual: root,
+            });
+        }
+        let changes: Vec<(Key, Option<Vec<u8>>)> = changes
+            .iter()
+            .map(|(owner, amount)| {
+                (
+                    token::balance_key(token, owner),
+                    Some(types::encode(amount)),
+                )
+            })
+            .collect();
+        let transition = self.root_witness(&changes)?;
+        let new_root = MerkleRoot::from(transition.new_root);
+        if &new_root != after {
+            return Err(Error::RootMismatch {
+                expected: after.clone(),
+                actual: new_root,
+            });
+        }
+        Ok(UpdateProof {
+            before: before.clone(),
+            after: after.clone(),
+            transition,
+        })
+    }
+
     /// Get the existence proof
     pub fn get_existence_proof(
         &self,
@@ -538,6 +1498,49 @@ where
         }
     }
 
+    /// Get a proof that the given account doesn't exist, i.e. that it has no
+    /// validity predicate, in the current merkle tree. It can be verified
+    /// without the storage with [`verify_non_membership`].
+    pub fn non_membership_proof(
+        &self,
+        addr: &Address,
+    ) -> Result<NonMembershipProof> {
+        let key = Key::validity_predicate(addr);
+        self.block
+            .tree
+            .non_membership_proof(&key)?
+            .ok_or_else(|| Error::AccountExists(addr.clone()))
+    }
+
+    /// Check if the validity predicate code with the given hash is in the
+    /// `vp_whitelist` parameter. The whitelisted hashes are hex strings, which
+    /// are compared case-insensitively. Returns the result and the gas cost.
+    pub fn is_allowed_vp_code(&self, code_hash: &Hash) -> Result<(bool, u64)> {
+        let key = parameters::storage::get_vp_whitelist_storage_key();
+        let (value, gas) = self.read(&key)?;
+        let whitelist: Vec<String> = match value {
+            Some(value) => types::decode(value).map_err(Error::CodingError)?,
+            None => return Ok((false, gas)),
+        };
+        let code_hash = code_hash.to_string();
+        let allowed = whitelist
+            .iter()
+            .any(|hash| hash.eq_ignore_ascii_case(&code_hash));
+        Ok((allowed, gas))
+    }
+
+    /// Get a proof of the balance of the `token` owned by the `owner` in the
+    /// current merkle tree, or of its absence if the owner has no balance. It
+    /// can be verified without the storage with [`verify_balance_proof`].
+    pub fn balance_proof(
+        &self,
+        token: &Address,
+        owner: &Address,
+    ) -> Result<MembershipProof> {
+        let key = token::balance_key(token, owner);
+        Ok(self.block.tree.membership_proof(&key)?)
+    }
+
     /// Get the current (yet to be committed) block epoch
     pub fn get_current_epoch(&self) -> (Epoch, u64) {
         (self.block.epoch, MIN_STORAGE_GAS)
@@ -564,6 +1567,33 @@ where
         self.update_epoch_in_merkle_tree()
     }
 
+    /// Check that the given timestamp of a transaction is within the
+    /// `tolerance` of the current block's time, either before or after it, to
+    /// reject stale and far-future transactions.
+    pub fn verify_tx_timestamp(
+        &self,
+        tx_time: &DateTimeUtc,
+        tolerance: Duration,
+    ) -> Result<()> {
+        let block_time = match &self.header {
+            Some(header) => header.time,
+            None => return Err(Error::NoBlockHeader),
+        };
+        let diff = if *tx_time > block_time {
+            tx_time.0 - block_time.0
+        } else {
+            block_time.0 - tx_time.0
+        };
+        if diff > tolerance {
+            return Err(Error::TimestampOutOfBounds {
+                tx_time: *tx_time,
+                block_time,
+                tolerance,
+            });
+        }
+        Ok(())
+    }
+
     /// Get the block header
     pub fn get_block_header(
         &self,
@@ -620,6 +1650,12 @@ where
         Ok(new_epoch)
     }
 
+    /// Check if the current block is the first block of a new epoch.
+    pub fn is_epoch_boundary(&self) -> bool {
+        self.block.pred_epochs.first_block_height(self.block.epoch)
+            == Some(self.block.height)
+    }
+
     /// Update the merkle tree with epoch data
     fn update_epoch_in_merkle_tree(&mut self) -> Result<()> {
         let key_prefix: Key =
@@ -669,6 +1705,8 @@ where
     ) -> Result<i64> {
         let value = value.as_ref();
         self.block.tree.update(key, value)?;
+        self.key_filter.insert(key);
+        self.block.changed_keys.insert(key.clone());
         self.db
             .batch_write_subspace_val(batch, self.block.height, key, value)
     }
@@ -682,6 +1720,7 @@ where
         key: &Key,
     ) -> Result<i64> {
         self.block.tree.delete(key)?;
+        self.block.changed_keys.insert(key.clone());
         self.db
             .batch_delete_subspace_val(batch, self.block.height, key)
     }
@@ -693,9 +1732,112 @@ impl From<MerkleTreeError> for Error {
     }
 }
 
+/// Get the account that a write or a delete of the given key is attributed to
+/// for the [`Storage::max_writes_per_block`]: the owner of a token balance, or
+/// otherwise the account whose sub-space the key is in, if any.
+pub fn write_owner(key: &Key) -> Option<&Address> {
+    token::is_any_token_balance_key(key).or_else(|| match key.segments.get(0) {
+        Some(DbKeySeg::AddressSeg(owner)) => Some(owner),
+        _ => None,
+    })
+}
+
+/// Count the given keys per the accounts that own them.
+fn writes_per_owner<'a>(
+    keys: impl IntoIterator<Item = &'a Key>,
+) -> HashMap<&'a Address, u64> {
+    let mut counts = HashMap::new();
+    for owner in keys.into_iter().filter_map(write_owner) {
+        *counts.entry(owner).or_default() += 1;
+    }
+    counts
+}
+
+/// Verify a `balance` of the `owner` in the `token` against a merkle root
+/// signed by a validator. First, the root's signature is verified with the
+/// validator's public key. Then the balance is verified with its existence
+/// `proof`, obtained with [`Storage::get_existence_proof`], against the root.
+pub fn verify_signed_balance<H: StorageHasher>(
+    signed_root: &Signed<MerkleRoot>,
+    validator_pk: &common::PublicKey,
+    token: &Address,
+    owner: &Address,
+    balance: &token::Amount,
+    proof: &Proof,
+) -> std::result::Result<(), BalanceProofError> {
+    signed_root
+        .verify(validator_pk)
+        .map_err(BalanceProofError::InvalidRootSignature)?;
+    let key = token::balance_key(token, owner);
+    let included = MerkleTree::<H>::verify_existence_proof(
+        &signed_root.data,
+        &key,
+        types::encode(balance),
+        proof,
+    )
+    .map_err(BalanceProofError::InvalidProof)?;
+    if included {
+        Ok(())
+    } else {
+        Err(BalanceProofError::NotIncluded)
+    }
+}
+
+/// Verify a proof obtained with [`Storage::non_membership_proof`] that the
+/// given account doesn't exist in the state with the given merkle `root`.
+pub fn verify_non_membership<H: StorageHasher>(
+    root: &MerkleRoot,
+    addr: &Address,
+    proof: &NonMembershipProof,
+) -> Result<bool> {
+    let key = Key::validity_predicate(addr);
+    Ok(proof.verify::<H>(root, &key)?)
+}
+
+/// Verify a proof obtained with [`Storage::balance_proof`] that the `owner` has
+/// the given `balance` of the `token` in the state with the given merkle
+/// `root`. A `None` balance verifies that the owner has no balance.
+pub fn verify_balance_proof<H: StorageHasher>(
+    root: &MerkleRoot,
+    token: &Address,
+    owner: &Address,
+    balance: Option<&token::Amount>,
+    proof: &MembershipProof,
+) -> Result<bool> {
+    let key = token::balance_key(token, owner);
+    let value = balance.map(types::encode);
+    Ok(proof.verify::<H>(root, &key, value.as_deref())?)
+}
+
+/// Verify a proof of an update obtained with [`Storage::update_proof`], i.e.
+/// that the changed values are committed in the `before` root and that
+/// applying the changes to them results in the `after` root.
+pub fn verify_update_proof<H: StorageHasher>(
+    proof: &UpdateProof,
+) -> Result<bool> {
+    let transition = &proof.transition;
+    Ok(MerkleRoot::from(transition.old_root) == proof.before
+        && MerkleRoot::from(transition.new_root) == proof.after
+        && transition.verify::<H>()?)
+}
+
+/// Check that a batch of entries doesn't contain more than one entry for the
+/// same address. Returns an error with the first repeated address.
+pub fn validate_unique_addresses<T>(entries: &[(Address, T)]) -> Result<()> {
+    let mut seen = std::collections::HashSet::new();
+    for (address, _) in entries {
+        if !seen.insert(address) {
+            return Err(Error::DuplicateAddress(address.clone()));
+        }
+    }
+    Ok(())
+}
+
 /// Helpers for testing components that depend on storage
 #[cfg(any(test, feature = "testing"))]
 pub mod testing {
+    use std::collections::BTreeMap;
+
     use merkle_tree::Sha256Hasher;
 
     use super::mockdb::MockDB;
@@ -714,6 +1856,8 @@ pub mod testing {
                 height: BlockHeight::default(),
                 epoch: Epoch::default(),
                 pred_epochs: Epochs::default(),
+                changed_keys: BTreeSet::default(),
+                write_counts: HashMap::default(),
             };
             Self {
                 db: MockDB::default(),
@@ -722,26 +1866,105 @@ pub mod testing {
                 header: None,
                 last_height: BlockHeight(0),
                 last_epoch: Epoch::default(),
+                last_root: None,
                 next_epoch_min_start_height: BlockHeight::default(),
                 next_epoch_min_start_time: DateTimeUtc::now(),
                 address_gen: EstablishedAddressGen::new(
                     "Test address generator seed",
                 ),
+                fee_sink: Address::Internal(InternalAddress::Treasury),
+                key_filter: KeyFilter::default(),
+                max_writes_per_block: None,
                 #[cfg(feature = "ferveo-tpke")]
                 tx_queue: TxQueue::default(),
             }
         }
     }
+
+    impl TestStorage {
+        /// Create a minimal genesis storage with a single `funded` account
+        /// that owns the given `amount` of the native token (XAN). The
+        /// account's validity predicate is empty. The merkle root only
+        /// depends on the given arguments.
+        pub fn dev_genesis(
+            chain_id: ChainId,
+            funded: &Address,
+            amount: token::Amount,
+        ) -> Self {
+            let mut storage = Self {
+                chain_id,
+                ..Default::default()
+            };
+            storage
+                .write(&Key::validity_predicate(funded), vec![])
+                .expect("Writing a VP shouldn't fail");
+            storage
+                .write(
+                    &token::balance_key(&crate::types::address::xan(), funded),
+                    types::encode(&amount),
+                )
+                .expect("Writing a balance shouldn't fail");
+            storage
+        }
+
+        /// Merge the token balances of a diverged `other` storage into this
+        /// one, e.g. to test fork resolution. For every balance that differs
+        /// between the two, the balance from `other` is taken when
+        /// `prefer_other` returns `true` for its owner, otherwise this
+        /// storage's balance is kept. The merkle tree is updated accordingly.
+        pub fn merge_preferring(
+            &mut self,
+            other: &Self,
+            prefer_other: impl Fn(&Address) -> bool,
+        ) -> Result<()> {
+            let mut balances: BTreeMap<Key, (Option<_>, Option<_>)> =
+                BTreeMap::new();
+            for (key, value) in self.iter_all_keys() {
+                if token::is_any_token_balance_key(&key).is_some() {
+                    balances.entry(key).or_default().0 = Some(value);
+                }
+            }
+            for (key, value) in other.iter_all_keys() {
+                if token::is_any_token_balance_key(&key).is_some() {
+                    balances.entry(key).or_default().1 = Some(value);
+                }
+            }
+            for (key, (ours, theirs)) in balances {
+                let owner = token::is_any_token_balance_key(&key)
+                    .expect("Only balance keys are collected");
+                if ours == theirs || !prefer_other(owner) {
+                    continue;
+                }
+                match theirs {
+                    Some(value) => {
+                        self.write(&key, value)?;
+                    }
+                    None => {
+                        self.delete(&key)?;
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr;
+
     use chrono::{TimeZone, Utc};
     use proptest::prelude::*;
 
     use super::testing::*;
+    use super::write_log::WriteLog;
     use super::*;
     use crate::ledger::parameters::{self, Parameters};
+    use crate::ledger::pos::anoma_proof_of_stake::epoched::Epoched;
+    use crate::ledger::pos::anoma_proof_of_stake::PosBase;
+    use crate::ledger::pos::types::{ValidatorSet, WeightedValidator};
+    use crate::types::chain::ChainIdPrefix;
+    use crate::types::key::{self, RefTo};
     use crate::types::time::{self, Duration};
 
     prop_compose! {
@@ -882,4 +2105,1440 @@ mod tests {
                 time_of_update + parameters.epoch_duration.min_duration);
         }
     }
+
+    #[test]
+    fn test_is_epoch_boundary() {
+        let min_num_of_blocks = 3;
+        let mut storage = TestStorage {
+            next_epoch_min_start_height: BlockHeight(min_num_of_blocks),
+            ..Default::default()
+        };
+        let parameters = Parameters {
+            epoch_duration: EpochDuration {
+                min_num_of_blocks,
+                min_duration: Duration::seconds(0).into(),
+            },
+            max_expected_time_per_block: Duration::seconds(1).into(),
+            vp_whitelist: vec![],
+            tx_whitelist: vec![],
+        };
+        parameters.init_storage(&mut storage);
+        let time = DateTimeUtc::now();
+
+        for height in 1..=10 {
+            let height = BlockHeight(height);
+            storage.begin_block(BlockHash::default(), height).unwrap();
+            let new_epoch = storage.update_epoch(height, time).unwrap();
+            let expected = height.0 % min_num_of_blocks == 0;
+            assert_eq!(new_epoch, expected, "at height {}", height);
+            assert_eq!(storage.is_epoch_boundary(), expected);
+            assert_eq!(
+                storage.block.epoch,
+                Epoch(height.0 / min_num_of_blocks)
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_unique_addresses() {
+        let addr_1 = address::testing::established_address_1();
+        let addr_2 = address::testing::established_address_2();
+
+        let result = validate_unique_addresses(&[
+            (addr_1.clone(), 1),
+            (addr_2.clone(), 2),
+        ]);
+        assert!(result.is_ok());
+
+        let result = validate_unique_addresses(&[
+            (addr_1.clone(), 1),
+            (addr_2, 2),
+            (addr_1.clone(), 3),
+        ]);
+        match result {
+            Err(Error::DuplicateAddress(address)) => {
+                assert_eq!(address, addr_1)
+            }
+            _ => panic!("expected a duplicate address error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_prune_expired() {
+        let mut storage = TestStorage::default();
+        let addr = address::testing::established_address_1();
+        let key = Key::from(addr.to_db_key())
+            .push(&"session".to_string())
+            .unwrap();
+        let other = Key::from(addr.to_db_key())
+            .push(&"other".to_string())
+            .unwrap();
+        let write_with_ttl =
+            |storage: &mut TestStorage, key: &Key, expiry: BlockHeight| {
+                storage.write(key, vec![1_u8]).unwrap();
+                storage
+                    .write(&key.ttl().unwrap(), types::encode(&expiry))
+                    .unwrap();
+                storage.write(&key.ttl_index(expiry), vec![]).unwrap();
+            };
+        write_with_ttl(&mut storage, &key, BlockHeight(3));
+        // The other value's expiry height is extended after its first write
+        write_with_ttl(&mut storage, &other, BlockHeight(3));
+        write_with_ttl(&mut storage, &other, BlockHeight(4));
+        // An undecodable expiry height is skipped
+        let garbage = Key::from(addr.to_db_key())
+            .push(&"garbage".to_string())
+            .unwrap();
+        write_with_ttl(&mut storage, &garbage, BlockHeight(4));
+        storage
+            .write(&garbage.ttl().unwrap(), vec![0xff_u8])
+            .unwrap();
+
+        storage
+            .begin_block(BlockHash::default(), BlockHeight(2))
+            .unwrap();
+        assert!(storage.prune_expired().unwrap().is_empty());
+        assert!(storage.has_key(&key).unwrap().0);
+
+        storage
+            .begin_block(BlockHash::default(), BlockHeight(3))
+            .unwrap();
+        assert_eq!(storage.prune_expired().unwrap(), vec![key.clone()]);
+        assert!(!storage.has_key(&key).unwrap().0);
+        assert!(!storage.has_key(&key.ttl().unwrap()).unwrap().0);
+        assert!(storage.has_key(&other).unwrap().0);
+        // All the index entries at the height are deleted
+        let prefix = Key::ttl_index_prefix(BlockHeight(3));
+        assert!(storage.iter_prefix(&prefix).0.next().is_none());
+
+        storage
+            .begin_block(BlockHash::default(), BlockHeight(4))
+            .unwrap();
+        assert_eq!(storage.prune_expired().unwrap(), vec![other.clone()]);
+        assert!(!storage.has_key(&other).unwrap().0);
+        assert!(storage.has_key(&garbage).unwrap().0);
+    }
+
+    #[test]
+    fn test_state_commitment() {
+        let mut storage = TestStorage::default();
+        let commitment = storage.state_commitment();
+        assert_eq!(commitment, storage.state_commitment());
+
+        // Merkle root
+        let key = Key::parse("key").unwrap();
+        storage.write(&key, vec![1_u8]).unwrap();
+        let with_root = storage.state_commitment();
+        assert_ne!(with_root, commitment);
+
+        // Height
+        storage
+            .begin_block(BlockHash::default(), BlockHeight(1))
+            .unwrap();
+        let with_height = storage.state_commitment();
+        assert_ne!(with_height, with_root);
+
+        // Block hash
+        storage
+            .begin_block(BlockHash([1; 32]), BlockHeight(1))
+            .unwrap();
+        let with_hash = storage.state_commitment();
+        assert_ne!(with_hash, with_height);
+
+        // Chain ID
+        storage.chain_id = ChainId::from_genesis(
+            ChainIdPrefix::from_str("other").unwrap(),
+            [1_u8; 32],
+        );
+        assert_ne!(storage.state_commitment(), with_hash);
+    }
+
+    #[test]
+    fn test_iter_all_keys() {
+        let mut storage = TestStorage::default();
+        let addr = address::testing::established_address_1();
+        let parameters = Parameters {
+            epoch_duration: EpochDuration {
+                min_num_of_blocks: 1,
+                min_duration: Duration::seconds(1).into(),
+            },
+            max_expected_time_per_block: Duration::seconds(1).into(),
+            vp_whitelist: vec![],
+            tx_whitelist: vec![],
+        };
+        parameters.init_storage(&mut storage);
+        let vp_key = Key::validity_predicate(&addr);
+        storage.write(&vp_key, vec![]).unwrap();
+        let balance_key = token::balance_key(&address::xan(), &addr);
+        storage
+            .write(&balance_key, types::encode(&token::Amount::from(1)))
+            .unwrap();
+
+        let keys: Vec<Key> =
+            storage.iter_all_keys().map(|(key, _value)| key).collect();
+        assert!(keys.contains(&vp_key));
+        assert!(keys.contains(&balance_key));
+        assert!(keys.contains(&parameters::storage::get_epoch_storage_key()));
+        let mut sorted = keys.clone();
+        sorted.sort_by_key(|key| key.to_string());
+        assert_eq!(keys, sorted);
+    }
+
+    #[test]
+    fn test_update_proof() {
+        let token = address::xan();
+        let owner_1 = address::testing::established_address_1();
+        let owner_2 = address::testing::established_address_2();
+        let write_balances = |storage: &mut TestStorage, balances: &[u64]| {
+            for (owner, balance) in [&owner_1, &owner_2].iter().zip(balances) {
+                storage
+                    .write(
+                        &token::balance_key(&token, owner),
+                        types::encode(&token::Amount::from(*balance)),
+                    )
+                    .unwrap();
+            }
+        };
+        let mut storage = TestStorage::default();
+        write_balances(&mut storage, &[100, 200]);
+        let before = storage.merkle_root();
+
+        // Get the root after the update from a storage with the same state
+        let mut updated = TestStorage::default();
+        write_balances(&mut updated, &[100, 200]);
+        write_balances(&mut updated, &[50, 250]);
+        let after = updated.merkle_root();
+
+        let changes = [
+            (owner_1.clone(), token::Amount::from(50)),
+            (owner_2.clone(), token::Amount::from(250)),
+        ];
+        let proof = storage
+            .update_proof(&token, &before, &changes, &after)
+            .unwrap();
+        assert!(verify_update_proof::<Sha256Hasher>(&proof).unwrap());
+
+        // A tampered root after the update is rejected
+        let mut tampered = proof.clone();
+        tampered.after = before.clone();
+        assert!(!verify_update_proof::<Sha256Hasher>(&tampered).unwrap());
+
+        // The proof cannot be obtained for a wrong root
+        let result = storage.update_proof(&token, &before, &changes, &before);
+        assert!(matches!(result, Err(Error::RootMismatch { .. })));
+        let result = storage.update_proof(&token, &after, &changes, &after);
+        assert!(matches!(result, Err(Error::RootMismatch { .. })));
+    }
+
+    #[test]
+    fn test_verify_signed_balance() {
+        let mut storage = TestStorage::default();
+        let token = address::xan();
+        let owner = address::testing::established_address_1();
+        let balance = token::Amount::from(100);
+        let key = token::balance_key(&token, &owner);
+        storage.write(&key, types::encode(&balance)).unwrap();
+
+        let keypair = key::testing::keypair_1();
+        let signed_root = Signed::new(&keypair, storage.merkle_root());
+        let (height, _gas) = storage.get_block_height();
+        let proof = storage
+            .get_existence_proof(&key, types::encode(&balance), height)
+            .unwrap();
+
+        verify_signed_balance::<Sha256Hasher>(
+            &signed_root,
+            &keypair.ref_to(),
+            &token,
+            &owner,
+            &balance,
+            &proof,
+        )
+        .expect("the balance should be verified");
+
+        // A wrong validator key
+        let other_pk = key::testing::keypair_2().ref_to();
+        let result = verify_signed_balance::<Sha256Hasher>(
+            &signed_root,
+            &other_pk,
+            &token,
+            &owner,
+            &balance,
+            &proof,
+        );
+        assert!(matches!(
+            result,
+            Err(BalanceProofError::InvalidRootSignature(_))
+        ));
+
+        // A tampered balance
+        let result = verify_signed_balance::<Sha256Hasher>(
+            &signed_root,
+            &keypair.ref_to(),
+            &token,
+            &owner,
+            &token::Amount::from(1_000),
+            &proof,
+        );
+        assert!(matches!(result, Err(BalanceProofError::NotIncluded)));
+    }
+
+    #[test]
+    fn test_dev_genesis() {
+        let funded = address::testing::established_address_1();
+        let amount = token::Amount::whole(1_000);
+        let storage =
+            TestStorage::dev_genesis(ChainId::default(), &funded, amount);
+
+        let key = token::balance_key(&address::xan(), &funded);
+        let (value, _gas) = storage.read(&key).unwrap();
+        let balance: token::Amount = types::decode(value.unwrap()).unwrap();
+        assert_eq!(balance, amount);
+        assert!(!storage.is_empty_account(&funded).unwrap().0);
+
+        // The root is stable
+        let other =
+            TestStorage::dev_genesis(ChainId::default(), &funded, amount);
+        assert_eq!(storage.merkle_root(), other.merkle_root());
+    }
+
+    #[test]
+    fn test_merge_preferring() {
+        let token = address::xan();
+        let alice = address::testing::established_address_1();
+        let bob = address::testing::established_address_2();
+        let alice_key = token::balance_key(&token, &alice);
+        let bob_key = token::balance_key(&token, &bob);
+        let read_balance = |storage: &TestStorage, key: &Key| {
+            let (value, _gas) = storage.read(key).unwrap();
+            value.map(|value| types::decode::<token::Amount>(value).unwrap())
+        };
+
+        let mut storage = TestStorage::default();
+        storage
+            .write(&alice_key, types::encode(&token::Amount::whole(10)))
+            .unwrap();
+        storage
+            .write(&bob_key, types::encode(&token::Amount::whole(20)))
+            .unwrap();
+
+        // Diverge the balances of both accounts in a fork
+        let mut fork = TestStorage::default();
+        fork.write(&alice_key, types::encode(&token::Amount::whole(11)))
+            .unwrap();
+        fork.write(&bob_key, types::encode(&token::Amount::whole(21)))
+            .unwrap();
+
+        storage
+            .merge_preferring(&fork, |owner| owner == &alice)
+            .unwrap();
+        assert_eq!(
+            read_balance(&storage, &alice_key),
+            Some(token::Amount::whole(11))
+        );
+        assert_eq!(
+            read_balance(&storage, &bob_key),
+            Some(token::Amount::whole(20))
+        );
+
+        // A balance missing in the preferred fork is removed
+        let empty = TestStorage::default();
+        storage
+            .merge_preferring(&empty, |owner| owner == &bob)
+            .unwrap();
+        assert_eq!(
+            read_balance(&storage, &alice_key),
+            Some(token::Amount::whole(11))
+        );
+        assert_eq!(read_balance(&storage, &bob_key), None);
+
+        // The tree is rebuilt from the merged balances
+        let mut expected = TestStorage::default();
+        expected
+            .write(&alice_key, types::encode(&token::Amount::whole(11)))
+            .unwrap();
+        assert_eq!(storage.merkle_root(), expected.merkle_root());
+    }
+
+    #[test]
+    fn test_swap_balances() {
+        let token = address::xan();
+        let funded = address::testing::established_address_1();
+        let empty = address::testing::established_address_2();
+        let amount = token::Amount::whole(100);
+        let mut storage =
+            TestStorage::dev_genesis(ChainId::default(), &funded, amount);
+        let root = storage.merkle_root();
+
+        storage.swap_balances(&funded, &empty, &token).unwrap();
+        let funded_key = token::balance_key(&token, &funded);
+        let empty_key = token::balance_key(&token, &empty);
+        let (funded_balance, _) = storage.read_balance(&funded_key).unwrap();
+        let (empty_balance, _) = storage.read_balance(&empty_key).unwrap();
+        assert_eq!(funded_balance, None);
+        assert_eq!(empty_balance, Some(amount));
+        assert_ne!(storage.merkle_root(), root);
+
+        // Swapping back restores the original state
+        storage.swap_balances(&funded, &empty, &token).unwrap();
+        let (funded_balance, _) = storage.read_balance(&funded_key).unwrap();
+        assert_eq!(funded_balance, Some(amount));
+        assert_eq!(storage.merkle_root(), root);
+    }
+
+    #[test]
+    fn test_verify_chain_binding() {
+        let storage = TestStorage::default();
+        let chain_id = storage.chain_id.to_string();
+        assert!(storage.verify_chain_binding(&chain_id).is_ok());
+
+        let other_chain_id = format!("{}-fork", chain_id);
+        match storage.verify_chain_binding(&other_chain_id) {
+            Err(Error::ChainIdMismatch { signed, chain_id }) => {
+                assert_eq!(signed, other_chain_id);
+                assert_eq!(chain_id, storage.chain_id);
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_has_key_with_key_filter() {
+        let mut storage = TestStorage::default();
+        let key = Key::parse("key").unwrap();
+        let absent = Key::parse("absent").unwrap();
+        storage.write(&key, vec![1]).unwrap();
+        assert!(storage.key_filter.may_contain(&key));
+        assert!(storage.has_key(&key).unwrap().0);
+        assert!(!storage.has_key(&absent).unwrap().0);
+
+        // The filter is rebuilt from the DB, e.g. after a restart
+        storage.key_filter = KeyFilter::default();
+        assert!(!storage.has_key(&key).unwrap().0);
+        storage.rebuild_key_filter();
+        assert!(storage.has_key(&key).unwrap().0);
+        assert!(!storage.has_key(&absent).unwrap().0);
+    }
+
+    #[test]
+    fn test_account_history() {
+        let token = address::xan();
+        let owner = address::testing::established_address_1();
+        let key = token::balance_key(&token, &owner);
+        let mut storage = TestStorage::default();
+
+        // Change the balance at a few heights
+        for (height, amount) in [(1, 10), (3, 30), (4, 40)] {
+            storage.last_height = BlockHeight(height);
+            storage.block.height = BlockHeight(height);
+            storage
+                .write(&key, types::encode(&token::Amount::whole(amount)))
+                .unwrap();
+        }
+        storage.last_height = BlockHeight(6);
+        storage.block.height = BlockHeight(6);
+        storage.delete(&key).unwrap();
+        storage.block.height = BlockHeight(7);
+
+        let history = storage.account_history(&token, &owner, BlockHeight(2));
+        let expected = vec![
+            (BlockHeight(3), token::Amount::whole(30)),
+            (BlockHeight(4), token::Amount::whole(40)),
+            (BlockHeight(6), token::Amount::default()),
+        ];
+        assert_eq!(history.unwrap(), expected);
+    }
+
+    #[test]
+    fn test_approx_size_bytes() {
+        let mut storage = TestStorage::default();
+        let mut size = storage.approx_size_bytes();
+        let owners = [
+            address::testing::established_address_1(),
+            address::testing::established_address_2(),
+            address::testing::established_address_3(),
+            address::testing::established_address_4(),
+        ];
+        for owner in owners {
+            let key = token::balance_key(&address::xan(), &owner);
+            storage
+                .write(&key, types::encode(&token::Amount::whole(1)))
+                .unwrap();
+            let new_size = storage.approx_size_bytes();
+            assert!(new_size > size);
+            size = new_size;
+        }
+    }
+
+    #[test]
+    fn test_audit_supplies() {
+        let mut storage = TestStorage::default();
+        let xan = address::xan();
+        let btc = address::btc();
+        let owner_1 = address::testing::established_address_1();
+        let owner_2 = address::testing::established_address_2();
+        for (token, owner, balance) in [
+            (&xan, &owner_1, 100),
+            (&xan, &owner_2, 200),
+            (&btc, &owner_1, 10),
+        ] {
+            storage
+                .write(
+                    &token::balance_key(token, owner),
+                    types::encode(&token::Amount::from(balance)),
+                )
+                .unwrap();
+        }
+
+        let expected = HashMap::from([(xan.clone(), 300)]);
+        storage.audit_supplies(&expected).unwrap();
+
+        // Only the incorrect supply is reported
+        let expected = HashMap::from([(xan, 300), (btc.clone(), 15)]);
+        match storage.audit_supplies(&expected) {
+            Err(Error::SupplyAudit(mismatches)) => {
+                assert_eq!(mismatches, vec![(btc, 15, 10)])
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_audit_supplies_overflow() {
+        let mut storage = TestStorage::default();
+        let xan = address::xan();
+        for owner in [
+            address::testing::established_address_1(),
+            address::testing::established_address_2(),
+        ] {
+            storage
+                .write(
+                    &token::balance_key(&xan, &owner),
+                    types::encode(&token::Amount::max()),
+                )
+                .unwrap();
+        }
+
+        let expected = HashMap::from([(xan.clone(), u64::MAX)]);
+        match storage.audit_supplies(&expected) {
+            Err(Error::SupplyOverflow(token)) => assert_eq!(token, xan),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_scheduled_txs() {
+        let mut storage = TestStorage::default();
+        let scheduler = address::testing::established_address_1();
+        for (id, height) in [(0, 5), (1, 6), (2, 5)] {
+            let tx = ScheduledTx {
+                scheduler: scheduler.clone(),
+                code: vec![id as u8],
+                data: None,
+                height: BlockHeight(height),
+            };
+            storage
+                .write(&Key::scheduled_tx(&scheduler, id), types::encode(&tx))
+                .unwrap();
+            let index_key =
+                Key::scheduled_tx_index(BlockHeight(height), &scheduler, id);
+            storage.write(&index_key, vec![]).unwrap();
+        }
+        // An index entry without the transaction is skipped
+        let index_key = Key::scheduled_tx_index(BlockHeight(5), &scheduler, 3);
+        storage.write(&index_key, vec![]).unwrap();
+        // A transaction that cannot be decoded is skipped
+        let index_key = Key::scheduled_tx_index(BlockHeight(5), &scheduler, 4);
+        storage.write(&index_key, vec![]).unwrap();
+        storage
+            .write(&Key::scheduled_tx(&scheduler, 4), vec![0xff_u8])
+            .unwrap();
+        storage
+            .write(
+                &Key::scheduled_tx_counter(&scheduler),
+                types::encode(&5_u64),
+            )
+            .unwrap();
+        storage
+            .write(
+                &Key::scheduled_tx_count(BlockHeight(5)),
+                types::encode(&4_u64),
+            )
+            .unwrap();
+
+        let scheduled = storage.scheduled_txs(BlockHeight(5)).unwrap();
+        let ids: Vec<u64> = scheduled
+            .iter()
+            .map(|(key, tx)| {
+                assert_eq!(tx.height, BlockHeight(5));
+                key.is_scheduled_tx().unwrap().1
+            })
+            .collect();
+        assert_eq!(ids, vec![0, 2]);
+        assert!(storage.scheduled_txs(BlockHeight(7)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_is_empty_account() {
+        let mut storage = TestStorage::default();
+        let empty = address::testing::established_address_1();
+        let zero = address::testing::established_address_2();
+        let funded = address::testing::established_address_3();
+
+        // Some unrelated data and a balance of another token
+        let key = Key::from(empty.to_db_key())
+            .push(&"data".to_string())
+            .unwrap();
+        storage.write(&key, vec![1_u8]).unwrap();
+        storage
+            .write(
+                &token::balance_key(&address::btc(), &zero),
+                types::encode(&token::Amount::default()),
+            )
+            .unwrap();
+        storage
+            .write(
+                &token::balance_key(&address::xan(), &zero),
+                types::encode(&token::Amount::default()),
+            )
+            .unwrap();
+        storage
+            .write(
+                &token::balance_key(&address::xan(), &funded),
+                types::encode(&token::Amount::default()),
+            )
+            .unwrap();
+        storage
+            .write(
+                &token::balance_key(&address::btc(), &funded),
+                types::encode(&token::Amount::from(1)),
+            )
+            .unwrap();
+
+        assert!(storage.is_empty_account(&empty).unwrap().0);
+        assert!(storage.is_empty_account(&zero).unwrap().0);
+        assert!(!storage.is_empty_account(&funded).unwrap().0);
+    }
+
+    #[test]
+    fn test_collect_fee() {
+        let mut storage = TestStorage::default();
+        let payer = address::testing::established_address_1();
+        let token = address::xan();
+        let payer_key = token::balance_key(&token, &payer);
+        let sink_key = token::balance_key(&token, &storage.fee_sink);
+        storage
+            .write(&Key::validity_predicate(&token), vec![])
+            .unwrap();
+        storage
+            .write(&payer_key, types::encode(&token::Amount::whole(10)))
+            .unwrap();
+
+        storage
+            .collect_fee(&payer, &token, token::Amount::whole(3))
+            .unwrap();
+        assert_eq!(
+            storage.read_balance(&payer_key).unwrap().0,
+            Some(token::Amount::whole(7))
+        );
+        assert_eq!(
+            storage.read_balance(&sink_key).unwrap().0,
+            Some(token::Amount::whole(3))
+        );
+
+        // Paying more than the balance must fail without any changes
+        let result =
+            storage.collect_fee(&payer, &token, token::Amount::whole(8));
+        assert!(matches!(result, Err(Error::InsufficientBalance { .. })));
+        assert_eq!(
+            storage.read_balance(&payer_key).unwrap().0,
+            Some(token::Amount::whole(7))
+        );
+        assert_eq!(
+            storage.read_balance(&sink_key).unwrap().0,
+            Some(token::Amount::whole(3))
+        );
+
+        // An account without any balance cannot pay
+        let result = storage.collect_fee(
+            &address::testing::established_address_2(),
+            &token,
+            token::Amount::whole(1),
+        );
+        assert!(matches!(result, Err(Error::InsufficientBalance { .. })));
+    }
+
+    #[test]
+    fn test_collect_fee_sink_overflow() {
+        let mut storage = TestStorage::default();
+        let payer = address::testing::established_address_1();
+        let token = address::xan();
+        let payer_key = token::balance_key(&token, &payer);
+        let sink_key = token::balance_key(&token, &storage.fee_sink);
+        let payer_balance = token::Amount::whole(10);
+        let sink_balance = token::Amount::from(u64::MAX - 1);
+        storage
+            .write(&Key::validity_predicate(&token), vec![])
+            .unwrap();
+        storage
+            .write(&payer_key, types::encode(&payer_balance))
+            .unwrap();
+        storage
+            .write(&sink_key, types::encode(&sink_balance))
+            .unwrap();
+
+        // A fee that would overflow the sink's balance must fail without any
+        // changes
+        let result =
+            storage.collect_fee(&payer, &token, token::Amount::from(2));
+        assert!(matches!(
+            result,
+            Err(Error::BalanceOverflow { owner, current, .. })
+                if owner == storage.fee_sink && current == sink_balance
+        ));
+        assert_eq!(
+            storage.read_balance(&payer_key).unwrap().0,
+            Some(payer_balance)
+        );
+        assert_eq!(
+            storage.read_balance(&sink_key).unwrap().0,
+            Some(sink_balance)
+        );
+
+        // A fee that fills up the sink's balance exactly is fine
+        storage
+            .collect_fee(&payer, &token, token::Amount::from(1))
+            .unwrap();
+        assert_eq!(
+            storage.read_balance(&sink_key).unwrap().0,
+            Some(token::Amount::max())
+        );
+    }
+
+    /// Balances of different tokens of the same accounts are independent
+    #[test]
+    fn test_multi_token_balances() {
+        let mut storage = TestStorage::default();
+        let payer = address::testing::established_address_1();
+        let other = address::testing::established_address_2();
+        let (xan, btc) = (address::xan(), address::btc());
+        for token in [&xan, &btc] {
+            storage
+                .write(&Key::validity_predicate(token), vec![])
+                .unwrap();
+            storage
+                .write(
+                    &token::balance_key(token, &payer),
+                    types::encode(&token::Amount::whole(10)),
+                )
+                .unwrap();
+        }
+        let balance = |storage: &TestStorage, tok, owner| {
+            let key = token::balance_key(tok, owner);
+            storage.read_balance(&key).unwrap().0
+        };
+
+        // Moving XAN must not touch the BTC balances
+        storage
+            .collect_fee(&payer, &xan, token::Amount::whole(4))
+            .unwrap();
+        storage.swap_balances(&payer, &other, &xan).unwrap();
+        assert_eq!(balance(&storage, &xan, &payer), None);
+        assert_eq!(
+            balance(&storage, &xan, &other),
+            Some(token::Amount::whole(6))
+        );
+        assert_eq!(
+            balance(&storage, &xan, &storage.fee_sink),
+            Some(token::Amount::whole(4))
+        );
+        assert_eq!(
+            balance(&storage, &btc, &payer),
+            Some(token::Amount::whole(10))
+        );
+        assert_eq!(balance(&storage, &btc, &other), None);
+        assert_eq!(balance(&storage, &btc, &storage.fee_sink), None);
+
+        // The balances of the two tokens are kept under different keys
+        assert_ne!(
+            token::balance_key(&xan, &payer),
+            token::balance_key(&btc, &payer)
+        );
+    }
+
+    #[test]
+    fn test_token_meta() {
+        let mut storage = TestStorage::default();
+        let token = address::xan();
+        let owner = address::testing::established_address_1();
+        assert_eq!(storage.get_token_meta(&token).unwrap().0, None);
+        assert!(matches!(
+            storage.can_mint(&token, token::Amount::whole(1)),
+            Err(Error::UnknownToken(_))
+        ));
+
+        let meta = token::TokenMeta {
+            symbol: "XAN".to_owned(),
+            decimals: 6,
+            cap: Some(token::Amount::whole(100).into()),
+        };
+        storage.set_token_meta(&token, &meta).unwrap();
+        assert_eq!(storage.get_token_meta(&token).unwrap().0, Some(meta));
+
+        storage
+            .write(
+                &token::balance_key(&token, &owner),
+                types::encode(&token::Amount::whole(60)),
+            )
+            .unwrap();
+        let can_mint = |amount| storage.can_mint(&token, amount).unwrap().0;
+        assert!(can_mint(token::Amount::whole(40)));
+        // Minting beyond the cap is refused
+        assert!(!can_mint(token::Amount::whole(41)));
+        assert!(!can_mint(token::Amount::max()));
+
+        // A token without a cap can always be minted
+        let uncapped = token::TokenMeta {
+            symbol: "XAN".to_owned(),
+            decimals: 6,
+            cap: None,
+        };
+        storage.set_token_meta(&token, &uncapped).unwrap();
+        assert!(storage.can_mint(&token, token::Amount::max()).unwrap().0);
+    }
+
+    #[test]
+    fn test_can_mint_without_meta() {
+        let mut storage = TestStorage::default();
+        let token = address::btc();
+        let owner = address::testing::established_address_1();
+        // A token with balances, but without metadata, is unknown
+        storage
+            .write(
+                &token::balance_key(&token, &owner),
+                types::encode(&token::Amount::whole(10)),
+            )
+            .unwrap();
+        match storage.can_mint(&token, token::Amount::whole(1)) {
+            Err(Error::UnknownToken(unknown)) => assert_eq!(unknown, token),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_at_height() {
+        let token = address::xan();
+        let owner = address::testing::established_address_1();
+        let key = token::balance_key(&token, &owner);
+        let mut storage = TestStorage::default();
+
+        // Change the balance in a few blocks, the last one is not committed
+        for height in 1..=5 {
+            storage
+                .begin_block(BlockHash::default(), BlockHeight(height))
+                .unwrap();
+            let mut batch = TestStorage::batch();
+            let amount = |amount| types::encode(&token::Amount::whole(amount));
+            match height {
+                1 => storage
+                    .batch_write_subspace_val(&mut batch, &key, amount(10))
+                    .unwrap(),
+                2 => storage
+                    .batch_write_subspace_val(&mut batch, &key, amount(20))
+                    .unwrap(),
+                4 => {
+                    storage.batch_delete_subspace_val(&mut batch, &key).unwrap()
+                }
+                5 => storage
+                    .batch_write_subspace_val(&mut batch, &key, amount(50))
+                    .unwrap(),
+                _ => 0,
+            };
+            storage.exec_batch(batch).unwrap();
+            if height < 5 {
+                storage.commit().unwrap();
+            }
+        }
+
+        for (height, expected) in [
+            (1, Some(10)),
+            (2, Some(20)),
+            (3, Some(20)),
+            (4, None),
+            (5, Some(50)),
+        ] {
+            let view = storage.at_height(BlockHeight(height)).unwrap();
+            assert_eq!(view.height(), BlockHeight(height));
+            assert_eq!(
+                view.read_balance(&token, &owner).unwrap(),
+                expected.map(token::Amount::whole),
+                "balance at height {}",
+                height
+            );
+        }
+
+        // The genesis state has not been committed
+        let result = storage.at_height(BlockHeight(0));
+        assert!(matches!(
+            result,
+            Err(Error::HeightNotRetained { height }) if height == BlockHeight(0)
+        ));
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut storage = TestStorage::default();
+        let token = address::xan();
+        let owners: Vec<Address> = (0..100_u8)
+            .map(|i| address::testing::established_address_1().derive_sub(&[i]))
+            .collect();
+        for owner in &owners {
+            storage
+                .write(
+                    &token::balance_key(&token, owner),
+                    types::encode(&token::Amount::whole(1)),
+                )
+                .unwrap();
+        }
+        for owner in &owners[10..] {
+            storage.delete(&token::balance_key(&token, owner)).unwrap();
+        }
+        let root = storage.merkle_root();
+        let node_count = storage.block.tree.node_count();
+
+        storage.compact().unwrap();
+        assert_eq!(storage.merkle_root(), root);
+        assert!(storage.block.tree.node_count() < node_count);
+        for owner in &owners[..10] {
+            assert!(
+                storage
+                    .has_key(&token::balance_key(&token, owner))
+                    .unwrap()
+                    .0
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify_root() {
+        let mut storage = TestStorage::default();
+        let key = Key::parse("key").unwrap();
+        storage.write(&key, types::encode(&1_u64)).unwrap();
+        let root = storage.merkle_root();
+        storage.verify_root(&root).unwrap();
+
+        // Corrupt the tree by updating it without the DB
+        storage
+            .block
+            .tree
+            .update(&key, types::encode(&2_u64))
+            .unwrap();
+        let result = storage.verify_root(&root);
+        assert!(matches!(
+            result,
+            Err(Error::RootMismatch { expected, actual })
+                if expected == root && actual == storage.merkle_root()
+        ));
+    }
+
+    #[test]
+    fn test_block_summary() {
+        let mut storage = TestStorage::default();
+        let summary = storage.block_summary().unwrap();
+        assert_eq!(
+            summary,
+            BlockSummary {
+                height: storage.block.height,
+                root: storage.merkle_root(),
+                block_hash: storage.block.hash.clone(),
+                chain_id: storage.chain_id.clone(),
+                num_changed_keys: 0,
+                validator_set_hash: storage.validator_set_hash().unwrap(),
+            }
+        );
+        let decoded =
+            BlockSummary::try_from_slice(&summary.try_to_vec().unwrap())
+                .unwrap();
+        assert_eq!(decoded, summary);
+
+        // Committing the keys written by a tx changes the root and the number
+        // of changed keys
+        let key = Key::parse("key").unwrap();
+        let mut write_log = WriteLog::default();
+        write_log.write(&key, types::encode(&1_u64)).unwrap();
+        write_log.commit_tx();
+        write_log.commit_block(&mut storage).unwrap();
+        let written = storage.block_summary().unwrap();
+        assert_ne!(written.root, summary.root);
+        assert_eq!(written.num_changed_keys, 1);
+        // A key changed more than once is counted once
+        write_log.write(&key, types::encode(&2_u64)).unwrap();
+        write_log.commit_tx();
+        write_log.commit_block(&mut storage).unwrap();
+        write_log.delete(&key).unwrap();
+        write_log.commit_tx();
+        write_log.commit_block(&mut storage).unwrap();
+        assert_eq!(storage.block_summary().unwrap().num_changed_keys, 1);
+
+        // Changing the validator set changes its hash
+        let validator = address::testing::established_address_1();
+        let active = [WeightedValidator {
+            voting_power: 10_u64.into(),
+            address: validator,
+        }];
+        let validator_set = ValidatorSet {
+            active: active.into_iter().collect(),
+            inactive: BTreeSet::default(),
+        };
+        let (current_epoch, _gas) = storage.get_current_epoch();
+        storage.write_validator_set(&Epoched::init_at_genesis(
+            validator_set,
+            current_epoch,
+        ));
+        let with_validators = storage.block_summary().unwrap();
+        assert_ne!(
+            with_validators.validator_set_hash,
+            summary.validator_set_hash
+        );
+        assert_eq!(
+            with_validators.validator_set_hash,
+            storage.validator_set_hash().unwrap()
+        );
+        assert_eq!(with_validators.num_changed_keys, 2);
+
+        // A new block changes the height and the hash and resets the changed
+        // keys
+        let block_hash = BlockHash([1; BLOCK_HASH_LENGTH]);
+        storage
+            .begin_block(block_hash.clone(), BlockHeight(1))
+            .unwrap();
+        let next_block = storage.block_summary().unwrap();
+        assert_eq!(next_block.height, BlockHeight(1));
+        assert_eq!(next_block.block_hash, block_hash);
+        assert_eq!(next_block.num_changed_keys, 0);
+        assert_eq!(next_block.root, with_validators.root);
+
+        storage.chain_id = ChainId::from_genesis(
+            ChainIdPrefix::from_str("other").unwrap(),
+            [1_u8; 32],
+        );
+        let other_chain = storage.block_summary().unwrap();
+        assert_ne!(other_chain.chain_id, next_block.chain_id);
+    }
+
+    #[test]
+    fn test_validators_without_pk() {
+        let mut storage = TestStorage::default();
+        let keyed = address::testing::established_address_1();
+        let unkeyed = address::testing::established_address_2();
+        for validator in [&keyed, &unkeyed] {
+            storage
+                .write(&pos::validator_state_key(validator), vec![])
+                .unwrap();
+            storage
+                .write(&pos::validator_total_deltas_key(validator), vec![])
+                .unwrap();
+        }
+        let pk = key::testing::keypair_1().ref_to();
+        storage
+            .write(&key::pk_key(&keyed), types::encode(&pk))
+            .unwrap();
+
+        assert_eq!(storage.validators_without_pk().unwrap(), vec![unkeyed]);
+    }
+
+    #[test]
+    fn test_self_check() {
+        let mut storage = TestStorage::default();
+        let token = address::xan();
+        let validator = address::testing::established_address_1();
+        let balance = token::Amount::whole(100);
+        storage
+            .write(
+                &token::balance_key(&token, &validator),
+                types::encode(&balance),
+            )
+            .unwrap();
+        storage
+            .write(&pos::validator_state_key(&validator), vec![])
+            .unwrap();
+        let pk_key = key::pk_key(&validator);
+        let pk = key::testing::keypair_1().ref_to();
+        storage.write(&pk_key, types::encode(&pk)).unwrap();
+        let supplies = HashMap::from([(token.clone(), u64::from(balance))]);
+
+        // A healthy storage passes
+        storage.verify_consistency().unwrap();
+        storage.self_check(&supplies).unwrap();
+
+        // Break the storage in several ways
+        storage.delete(&pk_key).unwrap();
+        storage
+            .write(
+                &token::balance_key(
+                    &token,
+                    &address::testing::established_address_2(),
+                ),
+                types::encode(&balance),
+            )
+            .unwrap();
+        storage
+            .block
+            .tree
+            .update(&Key::parse("corrupted").unwrap(), [1_u8])
+            .unwrap();
+
+        let errors = storage.self_check(&supplies).unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert!(matches!(errors[0], Error::RootMismatch { .. }));
+        assert!(matches!(errors[1], Error::SupplyAudit(_)));
+        assert!(matches!(
+            &errors[2],
+            Error::ValidatorsWithoutPk(validators) if validators == &[validator]
+        ));
+    }
+
+    #[test]
+    fn test_token_subroot() {
+        let mut storage = TestStorage::default();
+        let xan = address::xan();
+        let btc = address::btc();
+        let owner = address::testing::established_address_1();
+        for token in [&xan, &btc] {
+            storage
+                .write(
+                    &token::balance_key(token, &owner),
+                    types::encode(&token::Amount::whole(1)),
+                )
+                .unwrap();
+        }
+        let subroot = storage.token_subroot(&xan).unwrap();
+        assert_ne!(MerkleRoot::from(subroot), storage.merkle_root());
+
+        // A balance of another token doesn't change the sub-root
+        storage
+            .write(
+                &token::balance_key(&btc, &owner),
+                types::encode(&token::Amount::whole(2)),
+            )
+            .unwrap();
+        assert_eq!(storage.token_subroot(&xan).unwrap(), subroot);
+
+        // A balance of the token changes the sub-root
+        storage
+            .write(
+                &token::balance_key(&xan, &owner),
+                types::encode(&token::Amount::whole(2)),
+            )
+            .unwrap();
+        assert_ne!(storage.token_subroot(&xan).unwrap(), subroot);
+    }
+
+    #[test]
+    fn test_verify_tx_timestamp() {
+        let mut storage = TestStorage::default();
+        let tolerance = Duration::seconds(30);
+        let block_time = DateTimeUtc(Utc.timestamp(1_000_000, 0));
+
+        // The block time is not known yet
+        assert!(matches!(
+            storage.verify_tx_timestamp(&block_time, tolerance),
+            Err(Error::NoBlockHeader)
+        ));
+
+        storage
+            .set_header(Header {
+                hash: crate::types::hash::Hash([0; 32]),
+                time: block_time,
+                next_validators_hash: crate::types::hash::Hash([0; 32]),
+            })
+            .unwrap();
+        // Within the window
+        for offset in [-30, -1, 0, 1, 30] {
+            let tx_time = block_time.0 + Duration::seconds(offset);
+            storage
+                .verify_tx_timestamp(&tx_time.into(), tolerance)
+                .unwrap();
+        }
+        // Too old and too far in the future
+        for offset in [-31, 31] {
+            let tx_time = block_time.0 + Duration::seconds(offset);
+            assert!(matches!(
+                storage.verify_tx_timestamp(&tx_time.into(), tolerance),
+                Err(Error::TimestampOutOfBounds { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_multi_pks() {
+        let mut storage = TestStorage::default();
+        let owner = address::testing::established_address_1();
+        let pks: Vec<common::PublicKey> = (1..=3)
+            .map(|n| key::testing::keypair_n(n).ref_to())
+            .collect();
+        assert_eq!(storage.pks_of(&owner).unwrap().0, vec![]);
+
+        for (index, pk) in pks.iter().enumerate() {
+            let (added_index, _gas) = storage.add_pk(&owner, pk).unwrap();
+            assert_eq!(added_index as usize, index);
+        }
+        assert_eq!(storage.pks_of(&owner).unwrap().0, pks);
+        for pk in &pks {
+            assert!(storage.is_pk_registered(&owner, pk).unwrap().0);
+        }
+        let other_pk = key::testing::keypair_n(4).ref_to();
+        assert!(!storage.is_pk_registered(&owner, &other_pk).unwrap().0);
+
+        // The keys are committed in the merkle tree
+        let multi_pk_key = key::multi_pk_key(&owner, 2);
+        assert!(storage.block.tree.has_key(&multi_pk_key).unwrap());
+        // The keys of another account are separate
+        let other = address::testing::established_address_2();
+        assert_eq!(storage.pks_of(&other).unwrap().0, vec![]);
+    }
+
+    #[test]
+    fn test_non_membership_proof() {
+        let mut storage = TestStorage::default();
+        let addr = address::testing::established_address_1();
+        let other = address::testing::established_address_2();
+        storage
+            .write(&Key::validity_predicate(&other), vec![1_u8])
+            .unwrap();
+
+        let proof = storage.non_membership_proof(&addr).unwrap();
+        let verify = |root: &MerkleRoot, addr: &Address| {
+            verify_non_membership::<Sha256Hasher>(root, addr, &proof).unwrap()
+        };
+        let root = storage.merkle_root();
+        assert!(verify(&root, &addr));
+        // The proof is not valid for another account
+        assert!(!verify(&root, &other));
+
+        // Once the account exists, the proof is no longer valid for the new
+        // root and a new proof cannot be made
+        storage
+            .write(&Key::validity_predicate(&addr), vec![1_u8])
+            .unwrap();
+        let root = storage.merkle_root();
+        assert!(!verify(&root, &addr));
+        assert!(matches!(
+            storage.non_membership_proof(&addr),
+            Err(Error::AccountExists(_))
+        ));
+    }
+
+    #[test]
+    fn test_balance_proof() {
+        let mut storage = TestStorage::default();
+        let token = address::xan();
+        let owner = address::testing::established_address_1();
+        let missing = address::testing::established_address_2();
+        let balance = token::Amount::whole(10);
+        storage
+            .write(&token::balance_key(&token, &owner), types::encode(&balance))
+            .unwrap();
+        let root = storage.merkle_root();
+        let verify = |root: &MerkleRoot,
+                      owner: &Address,
+                      balance: Option<&token::Amount>,
+                      proof: &MembershipProof| {
+            verify_balance_proof::<Sha256Hasher>(
+                root, &token, owner, balance, proof,
+            )
+            .unwrap()
+        };
+
+        // A present balance
+        let proof = storage.balance_proof(&token, &owner).unwrap();
+        let decoded =
+            MembershipProof::try_from_slice(&proof.try_to_vec().unwrap())
+                .unwrap();
+        assert_eq!(decoded, proof);
+        assert!(verify(&root, &owner, Some(&balance), &decoded));
+        let other_balance = token::Amount::whole(11);
+        assert!(!verify(&root, &owner, Some(&other_balance), &proof));
+        assert!(!verify(&root, &owner, None, &proof));
+        assert!(!verify(&root, &missing, Some(&balance), &proof));
+
+        // An absent balance
+        let proof = storage.balance_proof(&token, &missing).unwrap();
+        assert!(verify(&root, &missing, None, &proof));
+        let zero = token::Amount::default();
+        assert!(!verify(&root, &missing, Some(&zero), &proof));
+
+        // The proofs are not valid against another root
+        storage
+            .write(&token::balance_key(&token, &owner), types::encode(&zero))
+            .unwrap();
+        let other_root = storage.merkle_root();
+        assert!(!verify(&other_root, &missing, None, &proof));
+        let proof = storage.balance_proof(&token, &owner).unwrap();
+        assert!(verify(&other_root, &owner, Some(&zero), &proof));
+        assert!(!verify(&root, &owner, Some(&zero), &proof));
+    }
+
+    #[test]
+    fn test_write_rate_limit() {
+        let mut storage = TestStorage::default();
+        storage.max_writes_per_block = Some(3);
+        let limited = address::testing::established_address_1();
+        let other = address::testing::established_address_2();
+        let key_of = |owner: &Address, i: u64| {
+            Key::from(owner.to_db_key()).push(&i.to_string()).unwrap()
+        };
+
+        // Writes and deletes up to the limit are fine
+        for i in 0..2 {
+            storage.write(&key_of(&limited, i), vec![1_u8]).unwrap();
+        }
+        storage.delete(&key_of(&limited, 0)).unwrap();
+        // A write past the limit fails without any changes
+        let result = storage.write(&key_of(&limited, 2), vec![1_u8]);
+        assert!(matches!(
+            result,
+            Err(Error::WriteRateLimited { owner, max: 3 }) if owner == limited
+        ));
+        assert!(!storage.has_key(&key_of(&limited, 2)).unwrap().0);
+        let result = storage.delete(&key_of(&limited, 1));
+        assert!(matches!(result, Err(Error::WriteRateLimited { .. })));
+        assert!(storage.has_key(&key_of(&limited, 1)).unwrap().0);
+
+        // Another account is not affected
+        for i in 0..3 {
+            storage.write(&key_of(&other, i), vec![1_u8]).unwrap();
+        }
+
+        // The counters are reset in the next block
+        storage
+            .begin_block(BlockHash::default(), BlockHeight(1))
+            .unwrap();
+        storage.write(&key_of(&limited, 2), vec![1_u8]).unwrap();
+    }
+
+    #[test]
+    fn test_write_rate_limit_balances() {
+        let mut storage = TestStorage::default();
+        let token = address::xan();
+        let owner = address::testing::established_address_1();
+        let other = address::testing::established_address_2();
+        let owner_key = token::balance_key(&token, &owner);
+        let other_key = token::balance_key(&token, &other);
+        let amount = |n| types::encode(&token::Amount::whole(n));
+        storage
+            .write(&Key::validity_predicate(&token), vec![])
+            .unwrap();
+        storage.write(&owner_key, amount(10)).unwrap();
+        storage.write(&other_key, amount(20)).unwrap();
+        storage.max_writes_per_block = Some(2);
+
+        // The balances of the same token are limited per their owners
+        storage.write(&owner_key, amount(9)).unwrap();
+        storage.write(&other_key, amount(19)).unwrap();
+        storage.write(&other_key, amount(18)).unwrap();
+        let result = storage.write(&other_key, amount(17));
+        assert!(matches!(
+            result,
+            Err(Error::WriteRateLimited { owner, .. }) if owner == other
+        ));
+
+        // A swap that would exceed the limit of one of its accounts must fail
+        // without changing any of the balances
+        let result = storage.swap_balances(&owner, &other, &token);
+        assert!(matches!(result, Err(Error::WriteRateLimited { .. })));
+        let balance = |storage: &TestStorage, key| {
+            storage.read_balance(key).unwrap().0.unwrap()
+        };
+        assert_eq!(balance(&storage, &owner_key), token::Amount::whole(9));
+        assert_eq!(balance(&storage, &other_key), token::Amount::whole(18));
+
+        // A fee that cannot be credited to the sink must not debit the payer
+        storage.fee_sink = other.clone();
+        let result =
+            storage.collect_fee(&owner, &token, token::Amount::whole(1));
+        assert!(matches!(result, Err(Error::WriteRateLimited { .. })));
+        assert_eq!(balance(&storage, &owner_key), token::Amount::whole(9));
+    }
+
+    #[test]
+    fn test_init_accounts() {
+        let accounts: Vec<(Address, common::PublicKey, u64)> = (1..=3_u8)
+            .map(|n| {
+                let addr =
+                    address::testing::established_address_1().derive_sub(&[n]);
+                let pk = key::testing::keypair_n(n).ref_to();
+                (addr, pk, u64::from(n) * 1_000)
+            })
+            .collect();
+
+        let mut storage = TestStorage::default();
+        let root = storage.init_accounts(&accounts).unwrap();
+        assert_eq!(root, storage.merkle_root());
+        for (addr, pk, balance) in &accounts {
+            let (value, _gas) = storage.read(&key::pk_key(addr)).unwrap();
+            let stored_pk: common::PublicKey =
+                types::decode(value.unwrap()).unwrap();
+            assert_eq!(&stored_pk, pk);
+            let key = token::balance_key(&address::xan(), addr);
+            let (value, _gas) = storage.read(&key).unwrap();
+            let stored_balance: token::Amount =
+                types::decode(value.unwrap()).unwrap();
+            assert_eq!(u64::from(stored_balance), *balance);
+        }
+        let (supply, _gas) = storage.token_supply(&address::xan()).unwrap();
+        assert_eq!(supply, 6_000);
+
+        // The root is deterministic
+        let mut other = TestStorage::default();
+        assert_eq!(other.init_accounts(&accounts).unwrap(), root);
+
+        // A repeated address is rejected
+        let mut repeated = accounts.clone();
+        repeated.push(accounts[0].clone());
+        let mut storage = TestStorage::default();
+        assert!(matches!(
+            storage.init_accounts(&repeated),
+            Err(Error::DuplicateAddress(_))
+        ));
+        assert!(storage.iter_all_keys().next().is_none());
+    }
+
+    #[test]
+    fn test_collect_fee_unknown_token() {
+        let mut storage = TestStorage::default();
+        let payer = address::testing::established_address_1();
+        let known = address::xan();
+        let unknown = address::btc();
+        for token in [&known, &unknown] {
+            storage
+                .write(
+                    &token::balance_key(token, &payer),
+                    types::encode(&token::Amount::whole(10)),
+                )
+                .unwrap();
+        }
+        storage
+            .write(&Key::validity_predicate(&known), vec![])
+            .unwrap();
+
+        storage
+            .collect_fee(&payer, &known, token::Amount::whole(1))
+            .unwrap();
+
+        // A token without an account cannot be transferred, even if there's
+        // a balance of it
+        let result =
+            storage.collect_fee(&payer, &unknown, token::Amount::whole(1));
+        assert!(
+            matches!(result, Err(Error::UnknownToken(token)) if token == unknown)
+        );
+        assert_eq!(
+            storage
+                .read_balance(&token::balance_key(&unknown, &payer))
+                .unwrap()
+                .0,
+            Some(token::Amount::whole(10))
+        );
+    }
 }
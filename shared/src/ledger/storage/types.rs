@@ -3,6 +3,8 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use thiserror::Error;
 
+use crate::types::storage::{BlockHeight, Key, KEY_SEGMENT_SEPARATOR};
+
 #[allow(missing_docs)]
 #[derive(Error, Debug)]
 pub enum Error {
@@ -32,6 +34,44 @@ where
     T::try_from_slice(bytes.as_ref()).map_err(Error::DeserializationError)
 }
 
+/// The DB prefix of the index of the block heights at which the account
+/// subspace keys have been changed, i.e. `diff_heights/{dyn}/{height}`. It
+/// allows to find the diffs of a key without walking every block height.
+pub const DIFF_HEIGHTS_PREFIX: &str = "diff_heights";
+
+/// The DB key prefix of the changed heights of the account subspace key
+pub fn diff_heights_prefix(key: &Key) -> String {
+    format!(
+        "{}{}{}{}",
+        DIFF_HEIGHTS_PREFIX, KEY_SEGMENT_SEPARATOR, key, KEY_SEGMENT_SEPARATOR
+    )
+}
+
+/// The exclusive upper bound of the DB keys with the key's
+/// [`diff_heights_prefix`]
+pub fn diff_heights_upper_bound(key: &Key) -> String {
+    let mut upper = diff_heights_prefix(key);
+    upper.pop();
+    upper.push((KEY_SEGMENT_SEPARATOR as u8 + 1) as char);
+    upper
+}
+
+/// The DB key of the entry of the account subspace key changed at the given
+/// height. The height is zero-padded, so that the entries are ordered by it.
+pub fn diff_height_key(key: &Key, height: BlockHeight) -> String {
+    format!("{}{:020}", diff_heights_prefix(key), height.0)
+}
+
+/// Parse the height of an entry from its DB key with the key's
+/// [`diff_heights_prefix`] stripped. Returns `None` for the entries of the
+/// sub-keys of the key.
+pub fn parse_diff_height(suffix: &str) -> Option<BlockHeight> {
+    if suffix.contains(KEY_SEGMENT_SEPARATOR) {
+        return None;
+    }
+    suffix.parse().ok().map(BlockHeight)
+}
+
 /// A key-value pair as raw bytes
 pub type KVBytes = (Box<[u8]>, Box<[u8]>);
 
@@ -8,6 +8,7 @@ use thiserror::Error;
 use crate::ledger;
 use crate::ledger::storage::{Storage, StorageHasher};
 use crate::types::address::{Address, EstablishedAddressGen};
+use crate::types::event::TxEvent;
 use crate::types::ibc::IbcEvent;
 use crate::types::storage;
 
@@ -69,6 +70,8 @@ pub struct WriteLog {
     tx_write_log: HashMap<storage::Key, StorageModification>,
     /// The IBC event for the current transaction
     ibc_event: Option<IbcEvent>,
+    /// The events emitted by the current transaction
+    events: Vec<TxEvent>,
 }
 
 impl Default for WriteLog {
@@ -78,6 +81,7 @@ impl Default for WriteLog {
             block_write_log: HashMap::with_capacity(100_000),
             tx_write_log: HashMap::with_capacity(100),
             ibc_event: None,
+            events: Vec::new(),
         }
     }
 }
@@ -260,6 +264,17 @@ impl WriteLog {
         (addr, gas)
     }
 
+    /// Initialize a new account with the given address and return the gas
+    /// cost. Unlike [`WriteLog::init_account`], the address is chosen by the
+    /// caller, who must ensure that it doesn't exist yet.
+    pub fn init_account_at(&mut self, addr: &Address, vp: Vec<u8>) -> u64 {
+        let key = storage::Key::validity_predicate(addr);
+        let gas = (key.len() + vp.len()) as _;
+        self.tx_write_log
+            .insert(key, StorageModification::InitAccount { vp });
+        gas
+    }
+
     /// Set an IBC event and return the gas cost.
     pub fn set_ibc_event(&mut self, event: IbcEvent) -> u64 {
         let len = event
@@ -270,6 +285,19 @@ impl WriteLog {
         len as _
     }
 
+    /// Add an event emitted by the current transaction and return the gas
+    /// cost. The events are kept in the order of emission.
+    pub fn emit_event(&mut self, event: TxEvent) -> u64 {
+        let len = event
+            .attributes
+            .iter()
+            .fold(event.event_type.len(), |acc, (k, v)| {
+                acc + k.len() + v.len()
+            });
+        self.events.push(event);
+        len as _
+    }
+
     /// Get the storage keys changed and accounts keys initialized in the
     /// current transaction. The account keys point to the validity predicates
     /// of the newly created accounts.
@@ -322,6 +350,11 @@ impl WriteLog {
         self.ibc_event.as_ref()
     }
 
+    /// Take the events emitted by the current transaction
+    pub fn take_events(&mut self) -> Vec<TxEvent> {
+        std::mem::take(&mut self.events)
+    }
+
     /// Commit the current transaction's write log to the block when it's
     /// accepted by all the triggered validity predicates. Starts a new
     /// transaction write log.
@@ -336,6 +369,40 @@ impl WriteLog {
         self.block_write_log.extend(tx_write_log);
     }
 
+    /// Commit the current transaction's write log to the block like
+    /// [`WriteLog::commit_tx`], after counting all of its changed keys
+    /// towards the block's per-account write limits in the `storage`. When
+    /// any account would exceed its limit, the transaction's write log is
+    /// dropped instead and the error is returned.
+    pub fn commit_tx_limited<DB, H>(
+        &mut self,
+        storage: &mut Storage<DB, H>,
+    ) -> Result<()>
+    where
+        DB: 'static
+            + ledger::storage::DB
+            + for<'iter> ledger::storage::DBIter<'iter>,
+        H: StorageHasher,
+    {
+        let keys = self.tx_write_log.iter().filter_map(|(key, entry)| {
+            match entry {
+                // temporary value isn't persisted
+                StorageModification::Temp { .. } => None,
+                _ => Some(key),
+            }
+        });
+        match storage.count_writes(keys) {
+            Ok(()) => {
+                self.commit_tx();
+                Ok(())
+            }
+            Err(err) => {
+                self.drop_tx();
+                Err(Error::StorageError(err))
+            }
+        }
+    }
+
     /// Drop the current transaction's write log when it's declined by any of
     /// the triggered validity predicates. Starts a new transaction write log.
     pub fn drop_tx(&mut self) {
@@ -619,6 +686,44 @@ mod tests {
         assert_eq!(value, None);
     }
 
+    #[test]
+    fn test_commit_tx_limited() {
+        let mut storage =
+            crate::ledger::storage::testing::TestStorage::default();
+        storage.max_writes_per_block = Some(2);
+        let mut write_log = WriteLog::default();
+        let token = address::xan();
+        let owner = address::testing::established_address_1();
+        let other = address::testing::established_address_2();
+        let owner_key = crate::types::token::balance_key(&token, &owner);
+        let other_key = crate::types::token::balance_key(&token, &other);
+        let val = "val".as_bytes().to_vec();
+
+        // A tx within the limits is committed and counted
+        write_log.write(&owner_key, val.clone()).unwrap();
+        write_log.write(&other_key, val.clone()).unwrap();
+        write_log.commit_tx_limited(&mut storage).unwrap();
+        write_log.write(&owner_key, val.clone()).unwrap();
+        write_log.commit_tx_limited(&mut storage).unwrap();
+
+        // A tx that would exceed the owner's limit is dropped, including its
+        // change of the other owner's balance of the same token
+        write_log.write(&owner_key, val.clone()).unwrap();
+        write_log.write(&other_key, val.clone()).unwrap();
+        let result = write_log.commit_tx_limited(&mut storage).unwrap_err();
+        assert_matches!(
+            result,
+            Error::StorageError(
+                crate::ledger::storage::Error::WriteRateLimited { .. }
+            )
+        );
+        assert!(write_log.get_keys().is_empty());
+
+        // The other owner is still within its limit
+        write_log.write(&other_key, val).unwrap();
+        write_log.commit_tx_limited(&mut storage).unwrap();
+    }
+
     prop_compose! {
         fn arb_verifiers_changed_key_tx_all_key()
             (verifiers_from_tx in testing::arb_verifiers_from_tx())
@@ -9,7 +9,9 @@ use super::gas::MIN_STORAGE_GAS;
 use crate::ledger::gas;
 use crate::ledger::gas::VpGasMeter;
 use crate::ledger::storage::write_log::WriteLog;
-use crate::ledger::storage::{self, write_log, Storage, StorageHasher};
+use crate::ledger::storage::{
+    self, write_log, MerkleRoot, Storage, StorageHasher,
+};
 use crate::proto::Tx;
 use crate::types::hash::Hash;
 use crate::types::storage::{BlockHash, BlockHeight, Epoch, Key};
@@ -244,6 +246,21 @@ where
     Ok(hash)
 }
 
+/// Getting the merkle root of the block before the one to which the current
+/// transaction is being applied. Returns `None` if there is no such block.
+pub fn get_prev_block_root<DB, H>(
+    gas_meter: &mut VpGasMeter,
+    storage: &Storage<DB, H>,
+) -> Result<Option<MerkleRoot>>
+where
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+{
+    let (root, gas) = storage.get_prev_block_root();
+    add_gas(gas_meter, gas)?;
+    Ok(root)
+}
+
 /// Getting the block hash. The height is that of the block to which the
 /// current transaction is being applied.
 pub fn get_tx_code_hash(gas_meter: &mut VpGasMeter, tx: &Tx) -> Result<Hash> {
@@ -267,6 +284,17 @@ where
     Ok(epoch)
 }
 
+/// Getting all the storage keys changed by the current transaction.
+pub fn get_changed_keys(
+    gas_meter: &mut VpGasMeter,
+    write_log: &WriteLog,
+) -> Result<Vec<Key>> {
+    let keys: Vec<Key> = write_log.get_keys().into_iter().collect();
+    let gas = keys.iter().map(|key| key.len() as u64).sum::<u64>();
+    add_gas(gas_meter, gas.max(MIN_STORAGE_GAS))?;
+    Ok(keys)
+}
+
 /// Storage prefix iterator. It will try to get an iterator from the storage.
 pub fn iter_prefix<'a, DB, H>(
     gas_meter: &mut VpGasMeter,
@@ -4,7 +4,8 @@ pub mod generated;
 mod types;
 
 pub use types::{
-    Dkg, Error, Intent, IntentGossipMessage, IntentId, Signed, SignedTxData, Tx,
+    Dkg, Error, Intent, IntentGossipMessage, IntentId, SelfDescribedSigned,
+    Signed, SignedMap, SignedTxData, Tx, TxBuilder,
 };
 
 #[cfg(test)]
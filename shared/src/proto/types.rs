@@ -1,7 +1,9 @@
 use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::{TryFrom, TryInto};
 use std::fmt::Display;
 use std::hash::{Hash, Hasher};
+use std::ops::Deref;
 
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use prost::Message;
@@ -50,6 +52,10 @@ pub struct SignedTxData {
 }
 
 /// A generic signed data wrapper for Borsh encode-able data.
+///
+/// The signature is produced and verified on the Borsh encoding of the data,
+/// so the data type must serialize deterministically. For map-like data, use
+/// the [`SignedMap`] wrapper which guarantees a stable ordering of entries.
 #[derive(
     Clone, Debug, BorshSerialize, BorshDeserialize, Serialize, Deserialize,
 )]
@@ -106,17 +112,126 @@ where
         Self { data, sig }
     }
 
+    /// Join the data with its detached signature, e.g. when the signature is
+    /// carried separately from the data. The signature is not verified.
+    pub fn from_parts(data: T, sig: common::Signature) -> Self {
+        Self { data, sig }
+    }
+
+    /// Split into the data and its detached signature.
+    pub fn into_parts(self) -> (T, common::Signature) {
+        (self.data, self.sig)
+    }
+
     /// Verify that the data has been signed by the secret key
     /// counterpart of the given public key.
     pub fn verify(
         &self,
         pk: &common::PublicKey,
     ) -> std::result::Result<(), VerifySigError> {
+        Self::verify_detached(&self.data, &self.sig, pk)
+    }
+
+    /// Verify that the given data has been signed with the detached signature
+    /// by the secret key counterpart of the given public key.
+    pub fn verify_detached(
+        data: &T,
+        sig: &common::Signature,
+        pk: &common::PublicKey,
+    ) -> std::result::Result<(), VerifySigError> {
+        let bytes = data
+            .try_to_vec()
+            .expect("Encoding data for verifying signature shouldn't fail");
+        common::SigScheme::verify_signature_raw(pk, &bytes, sig)
+    }
+}
+
+/// A generic signed data wrapper like [`Signed`], which also carries the
+/// public key of the signer, so that the signature can be verified without
+/// supplying the key out-of-band.
+///
+/// Note that [`SelfDescribedSigned::verify`] only checks that the data has been
+/// signed by the embedded key. It's still the caller's responsibility to check
+/// that the signer is authorized to sign the data.
+#[derive(
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    BorshSerialize,
+    BorshDeserialize,
+    Serialize,
+    Deserialize,
+)]
+pub struct SelfDescribedSigned<T: BorshSerialize + BorshDeserialize> {
+    /// Arbitrary data to be signed
+    pub data: T,
+    /// The signature of the data
+    pub sig: common::Signature,
+    /// The public key of the signer
+    pub signer: common::PublicKey,
+}
+
+impl<T> SelfDescribedSigned<T>
+where
+    T: BorshSerialize + BorshDeserialize,
+{
+    /// Initialize a new signed data with the public key of the given keypair.
+    pub fn new(keypair: &common::SecretKey, data: T) -> Self {
+        let Signed { data, sig } = Signed::new(keypair, data);
+        Self {
+            data,
+            sig,
+            signer: keypair.ref_to(),
+        }
+    }
+
+    /// Verify that the data has been signed by the secret key counterpart of
+    /// the embedded signer's public key.
+    pub fn verify(&self) -> std::result::Result<(), VerifySigError> {
         let bytes = self
             .data
             .try_to_vec()
             .expect("Encoding data for verifying signature shouldn't fail");
-        common::SigScheme::verify_signature_raw(pk, &bytes, &self.sig)
+        common::SigScheme::verify_signature_raw(&self.signer, &bytes, &self.sig)
+    }
+}
+
+/// A map wrapper for data to be [`Signed`]. Its entries are always ordered by
+/// their keys, so the map's Borsh encoding is stable across round-trips and
+/// re-encoding.
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    BorshSerialize,
+    BorshDeserialize,
+    Serialize,
+    Deserialize,
+)]
+pub struct SignedMap<K: Ord, V>(pub BTreeMap<K, V>);
+
+impl<K: Ord, V> From<HashMap<K, V>> for SignedMap<K, V> {
+    fn from(map: HashMap<K, V>) -> Self {
+        Self(map.into_iter().collect())
+    }
+}
+
+impl<K: Ord, V> From<BTreeMap<K, V>> for SignedMap<K, V> {
+    fn from(map: BTreeMap<K, V>) -> Self {
+        Self(map)
+    }
+}
+
+impl<K: Ord, V> Deref for SignedMap<K, V> {
+    type Target = BTreeMap<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
     }
 }
 
@@ -199,17 +314,53 @@ impl Tx {
         }
     }
 
+    /// Sign a batch of transactions with the same key. The result is identical
+    /// to signing each transaction with [`Tx::sign`].
+    pub fn sign_txs(keypair: &common::SecretKey, txs: Vec<Tx>) -> Vec<Tx> {
+        txs.into_iter().map(|tx| tx.sign(keypair)).collect()
+    }
+
+    /// Sign a transaction like [`Tx::sign`] and return it together with its
+    /// id, i.e. the [`Tx::hash`] of the signed transaction.
+    pub fn sign_tx_with_id(
+        keypair: &common::SecretKey,
+        tx: Tx,
+    ) -> (Tx, [u8; 32]) {
+        let signed = tx.sign(keypair);
+        let id = signed.hash();
+        (signed, id)
+    }
+
     /// Verify that the transaction has been signed by the secret key
     /// counterpart of the given public key.
+    ///
+    /// Panics if the transaction's data cannot be decoded as [`SignedTxData`].
+    /// Use [`Tx::try_verify_sig`] for transactions that may be malformed.
     pub fn verify_sig(
         &self,
         pk: &common::PublicKey,
         sig: &common::Signature,
+    ) -> std::result::Result<(), VerifySigError> {
+        match self.try_verify_sig(pk, sig) {
+            Err(VerifySigError::MalformedSignedData(err)) => {
+                panic!("Decoding transaction data shouldn't fail: {}", err)
+            }
+            result => result,
+        }
+    }
+
+    /// Verify that the transaction has been signed by the secret key
+    /// counterpart of the given public key. Unlike [`Tx::verify_sig`], this
+    /// returns an error when the transaction's data is malformed.
+    pub fn try_verify_sig(
+        &self,
+        pk: &common::PublicKey,
+        sig: &common::Signature,
     ) -> std::result::Result<(), VerifySigError> {
         // Try to get the transaction data from decoded `SignedTxData`
         let tx_data = self.data.clone().ok_or(VerifySigError::MissingData)?;
         let signed_tx_data = SignedTxData::try_from_slice(&tx_data[..])
-            .expect("Decoding transaction data shouldn't fail");
+            .map_err(VerifySigError::MalformedSignedData)?;
         let data = signed_tx_data.data;
         let tx = Tx {
             code: self.code.clone(),
@@ -219,6 +370,77 @@ impl Tx {
         let signed_data = tx.hash();
         common::SigScheme::verify_signature_raw(pk, &signed_data, sig)
     }
+
+    /// Verify that the transaction has been signed by the secret key
+    /// counterpart of the given public key, like [`Tx::try_verify_sig`], but
+    /// without returning early on missing or malformed transaction data. The
+    /// signature is always checked and the results of all the checks are
+    /// combined without short-circuiting, so that the time taken doesn't
+    /// reveal which of the checks has failed.
+    pub fn verify_sig_ct(
+        &self,
+        pk: &common::PublicKey,
+        sig: &common::Signature,
+    ) -> bool {
+        let signed_tx_data = self
+            .data
+            .as_ref()
+            .and_then(|data| SignedTxData::try_from_slice(&data[..]).ok());
+        let data_valid = signed_tx_data.is_some();
+        let tx = Tx {
+            code: self.code.clone(),
+            data: signed_tx_data.and_then(|signed| signed.data),
+            timestamp: self.timestamp,
+        };
+        let signed_data = tx.hash();
+        let sig_valid =
+            common::SigScheme::verify_signature_raw(pk, &signed_data, sig)
+                .is_ok();
+        data_valid & sig_valid
+    }
+
+    /// Start building a transaction with [`TxBuilder`].
+    pub fn builder() -> TxBuilder {
+        TxBuilder::default()
+    }
+}
+
+/// A builder of [`Tx`]. Unless set, the timestamp is the current time at
+/// the time of [`TxBuilder::build`].
+#[derive(Clone, Debug, Default)]
+pub struct TxBuilder {
+    code: Vec<u8>,
+    data: Option<Vec<u8>>,
+    timestamp: Option<DateTimeUtc>,
+}
+
+impl TxBuilder {
+    /// Set the transaction's wasm code
+    pub fn code(mut self, code: Vec<u8>) -> Self {
+        self.code = code;
+        self
+    }
+
+    /// Set the transaction's data
+    pub fn data(mut self, data: Option<Vec<u8>>) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Set the transaction's timestamp
+    pub fn timestamp(mut self, timestamp: DateTimeUtc) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Build the transaction
+    pub fn build(self) -> Tx {
+        Tx {
+            code: self.code,
+            data: self.data,
+            timestamp: self.timestamp.unwrap_or_else(DateTimeUtc::now),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -431,6 +653,145 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_sign_txs() {
+        let keypair = crate::types::key::testing::keypair_1();
+        let txs: Vec<Tx> = (0..5_u8)
+            .map(|i| Tx::new(vec![i; 4], Some(vec![i; 8])))
+            .collect();
+
+        let signed = Tx::sign_txs(&keypair, txs.clone());
+        let expected: Vec<Tx> =
+            txs.into_iter().map(|tx| tx.sign(&keypair)).collect();
+        assert_eq!(signed, expected);
+    }
+
+    #[test]
+    fn test_sign_tx_with_id() {
+        let keypair = crate::types::key::testing::keypair_1();
+        let tx = Tx::new(vec![1, 2, 3], Some(vec![4, 5, 6]));
+
+        let (signed, id) = Tx::sign_tx_with_id(&keypair, tx.clone());
+        assert_eq!(signed, tx.sign(&keypair));
+        assert_eq!(id, hash_tx(&signed.to_bytes()).0);
+    }
+
+    #[test]
+    fn test_tx_builder() {
+        let keypair = crate::types::key::testing::keypair_1();
+        let timestamp = DateTimeUtc::now();
+        let tx = Tx::builder()
+            .code(vec![1; 4])
+            .data(Some(vec![2; 8]))
+            .timestamp(timestamp)
+            .build();
+        assert_eq!(tx.code, vec![1; 4]);
+        assert_eq!(tx.data, Some(vec![2; 8]));
+        assert_eq!(tx.timestamp, timestamp);
+
+        let signed = tx.sign(&keypair);
+        let signed_data = SignedTxData::try_from_slice(
+            signed.data.as_ref().expect("signed tx must have data"),
+        )
+        .unwrap();
+        signed
+            .verify_sig(&keypair.ref_to(), &signed_data.sig)
+            .expect("signature must be valid");
+    }
+
+    #[test]
+    fn test_try_verify_sig() {
+        let keypair = crate::types::key::testing::keypair_1();
+        let signed = Tx::new(vec![1; 4], Some(vec![2; 8])).sign(&keypair);
+        let sig = SignedTxData::try_from_slice(signed.data.as_ref().unwrap())
+            .unwrap()
+            .sig;
+
+        let tx = Tx::new(vec![1; 4], None);
+        assert!(matches!(
+            tx.try_verify_sig(&keypair.ref_to(), &sig),
+            Err(VerifySigError::MissingData)
+        ));
+
+        let tx = Tx::new(vec![1; 4], Some(vec![0xff; 3]));
+        assert!(matches!(
+            tx.try_verify_sig(&keypair.ref_to(), &sig),
+            Err(VerifySigError::MalformedSignedData(_))
+        ));
+
+        assert!(signed.try_verify_sig(&keypair.ref_to(), &sig).is_ok());
+    }
+
+    #[test]
+    fn test_verify_sig_ct() {
+        let keypair = crate::types::key::testing::keypair_1();
+        let signed = Tx::new(vec![1; 4], Some(vec![2; 8])).sign(&keypair);
+        let sig = SignedTxData::try_from_slice(signed.data.as_ref().unwrap())
+            .unwrap()
+            .sig;
+
+        assert!(signed.verify_sig_ct(&keypair.ref_to(), &sig));
+        let other_pk = crate::types::key::testing::keypair_2().ref_to();
+        assert!(!signed.verify_sig_ct(&other_pk, &sig));
+        let tx = Tx::new(vec![1; 4], None);
+        assert!(!tx.verify_sig_ct(&keypair.ref_to(), &sig));
+        let tx = Tx::new(vec![1; 4], Some(vec![0xff; 3]));
+        assert!(!tx.verify_sig_ct(&keypair.ref_to(), &sig));
+    }
+
+    #[test]
+    fn test_self_described_signed() {
+        let keypair = crate::types::key::testing::keypair_1();
+        let signed = SelfDescribedSigned::new(&keypair, vec![1_u8, 2, 3]);
+        assert_eq!(signed.signer, keypair.ref_to());
+        assert!(signed.verify().is_ok());
+
+        let mut tampered = signed.clone();
+        tampered.data.push(4);
+        assert!(tampered.verify().is_err());
+
+        let mut tampered = signed;
+        tampered.signer = crate::types::key::testing::keypair_2().ref_to();
+        assert!(tampered.verify().is_err());
+    }
+
+    #[test]
+    fn test_signed_parts() {
+        let keypair = crate::types::key::testing::keypair_1();
+        let pk = keypair.ref_to();
+        let signed = Signed::new(&keypair, vec![1_u8, 2, 3]);
+
+        let (data, sig) = signed.clone().into_parts();
+        Signed::verify_detached(&data, &sig, &pk).unwrap();
+        assert_eq!(Signed::from_parts(data.clone(), sig.clone()), signed);
+
+        // The detached signature doesn't verify other data or a different key
+        assert!(Signed::verify_detached(&vec![1_u8, 2], &sig, &pk).is_err());
+        let other_pk = crate::types::key::testing::keypair_2().ref_to();
+        assert!(Signed::verify_detached(&data, &sig, &other_pk).is_err());
+    }
+
+    #[test]
+    fn test_signed_map() {
+        let keypair = crate::types::key::testing::keypair_1();
+        let map: HashMap<String, u64> =
+            (0..20_u64).map(|i| (format!("key-{}", i), i)).collect();
+        let signed = Signed::new(&keypair, SignedMap::from(map.clone()));
+
+        let bytes = signed.try_to_vec().unwrap();
+        let decoded: Signed<SignedMap<String, u64>> =
+            Signed::try_from_slice(&bytes).unwrap();
+        decoded.verify(&keypair.ref_to()).unwrap();
+
+        // Re-signing the same entries must produce the same data encoding
+        let resigned = Signed::new(&keypair, SignedMap::from(map));
+        assert_eq!(
+            resigned.data.try_to_vec().unwrap(),
+            decoded.data.try_to_vec().unwrap()
+        );
+        assert_eq!(decoded.get("key-7"), Some(&7));
+    }
+
     #[test]
     fn test_intent_gossip_message() {
         let data = "arbitrary data".as_bytes().to_owned();
@@ -163,6 +163,46 @@ impl Address {
         }
     }
 
+    /// Get the canonical identifier of the account. It's the sha256 hash of the
+    /// address' storage key segment, which is equal to the key hash used in
+    /// the merkle tree for the key that consists of only this address
+    /// (except for the internal addresses that have their own sub-tree).
+    pub fn id(&self) -> crate::types::hash::Hash {
+        use crate::types::storage::KeySeg;
+        crate::types::hash::Hash::sha256(self.raw())
+    }
+
+    /// Get a checksum of the address, which is the first 4 bytes of its
+    /// [`Address::id`]. It can be used to detect mistyped addresses.
+    pub fn checksum(&self) -> [u8; 4] {
+        let id = self.id();
+        [id.0[0], id.0[1], id.0[2], id.0[3]]
+    }
+
+    /// Check that the given encoded address is valid and matches the given
+    /// checksum.
+    pub fn verify_checksum(address: &str, checksum: &[u8; 4]) -> bool {
+        match Address::decode(address) {
+            Ok(address) => &address.checksum() == checksum,
+            Err(_) => false,
+        }
+    }
+
+    /// Derive an established address of a sub-account of this address. The
+    /// derived address is the hash of this address and the given `salt`, so
+    /// the same address and salt always derive the same sub-account, e.g. for
+    /// accounts created by transactions deterministically.
+    pub fn derive_sub(&self, salt: &[u8]) -> Address {
+        let parent_bytes = self
+            .try_to_vec()
+            .expect("Encoding an address shouldn't fail");
+        let mut hasher = Sha256::new();
+        hasher.update([&parent_bytes, salt].concat());
+        // hex of the first 40 chars of the hash
+        let hash = format!("{:.width$X}", hasher.finalize(), width = HASH_LEN);
+        Address::Established(EstablishedAddress { hash })
+    }
+
     /// Convert an address to a fixed length 7-bit ascii string bytes
     fn to_fixed_len_string(&self) -> Vec<u8> {
         let mut string = match self {
@@ -575,6 +615,96 @@ pub mod tests {
         assert_eq!(encoded_address, expect);
     }
 
+    #[test]
+    fn test_address_id() {
+        use crate::ledger::storage::{Sha256Hasher, StorageHasher};
+        use crate::types::storage::{Key, KeySeg};
+
+        for address in [
+            testing::established_address_1(),
+            testing::gen_implicit_address(),
+        ] {
+            let key = Key::from(address.to_db_key());
+            let merkle_key = Sha256Hasher::hash(key.to_string());
+            assert_eq!(&address.id().0[..], merkle_key.as_slice());
+        }
+        assert_ne!(
+            testing::established_address_1().id(),
+            testing::established_address_2().id()
+        );
+    }
+
+    /// An established and an implicit address with the same hash must be
+    /// distinct in storage, because their encoding is prefixed with their
+    /// namespace.
+    #[test]
+    fn test_address_namespace_separation() {
+        use crate::ledger::storage::testing::TestStorage;
+        use crate::types::token;
+
+        let hash = "A".repeat(HASH_LEN);
+        let established =
+            Address::Established(EstablishedAddress { hash: hash.clone() });
+        let implicit =
+            Address::Implicit(ImplicitAddress(hash.parse().unwrap()));
+        assert_ne!(established, implicit);
+        assert_ne!(established.encode(), implicit.encode());
+        assert_ne!(established.id(), implicit.id());
+
+        let mut storage = TestStorage::default();
+        let token = xan();
+        let balances = [
+            (&established, token::Amount::whole(1)),
+            (&implicit, token::Amount::whole(2)),
+        ];
+        for (owner, balance) in balances {
+            let key = token::balance_key(&token, owner);
+            storage.write(&key, balance.try_to_vec().unwrap()).unwrap();
+        }
+        for (owner, balance) in balances {
+            let key = token::balance_key(&token, owner);
+            let (value, _gas) = storage.read(&key).unwrap();
+            let value = token::Amount::try_from_slice(&value.unwrap()).unwrap();
+            assert_eq!(value, balance);
+        }
+    }
+
+    #[test]
+    fn test_address_checksum() {
+        let address = testing::established_address_1();
+        let checksum = address.checksum();
+        assert_eq!(checksum, address.clone().checksum());
+        assert_eq!(&checksum[..], &address.id().0[..4]);
+        assert!(Address::verify_checksum(&address.encode(), &checksum));
+
+        // A different address doesn't match the checksum
+        let other = testing::established_address_2();
+        assert!(!Address::verify_checksum(&other.encode(), &checksum));
+
+        // A tampered address is either invalid or doesn't match the checksum
+        let mut tampered = address.encode();
+        let last = tampered.pop().unwrap();
+        tampered.push(if last == 'q' { 'p' } else { 'q' });
+        assert!(!Address::verify_checksum(&tampered, &checksum));
+    }
+
+    #[test]
+    fn test_address_derive_sub() {
+        let parent = testing::established_address_1();
+        let sub = parent.derive_sub(b"vault");
+
+        // The derivation is deterministic
+        assert_eq!(sub, parent.derive_sub(b"vault"));
+        assert!(matches!(sub, Address::Established(_)));
+        assert_ne!(sub, parent);
+        assert_eq!(Address::decode(sub.encode()).unwrap(), sub);
+
+        // Different salts or parents derive different addresses
+        assert_ne!(sub, parent.derive_sub(b"vault2"));
+        assert_ne!(sub, parent.derive_sub(b""));
+        assert_ne!(sub, testing::established_address_2().derive_sub(b"vault"));
+    }
+
     proptest! {
         #[test]
         /// Check that all the address types are of the same length
@@ -0,0 +1,15 @@
+//! Types of the events emitted by transactions.
+
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+
+/// A typed event emitted by a transaction. The events of an applied
+/// transaction are surfaced to indexers in the block's response.
+#[derive(
+    Debug, Clone, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq, Eq,
+)]
+pub struct TxEvent {
+    /// The type of the event
+    pub event_type: String,
+    /// The attributes of the event, in the order in which they were given
+    pub attributes: Vec<(String, String)>,
+}
@@ -26,6 +26,37 @@ impl HostEnvResult {
     }
 }
 
+/// The kind of wasm code that is being executed.
+///
+/// There are no variants for matchmakers or intent filters. Matchmakers are
+/// loaded as native dylibs (see [`crate::types::matchmaker`]) and there is no
+/// wasm environment for filters, so neither has a host function through which
+/// it could query its context.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u64)]
+pub enum ExecContext {
+    /// A transaction
+    Tx = 1,
+    /// A validity predicate
+    Vp = 2,
+}
+
+impl ExecContext {
+    /// Convert the context to `u64`, which can be passed to wasm
+    pub fn to_u64(self) -> u64 {
+        self as _
+    }
+
+    /// Get the context from a `u64` obtained with [`ExecContext::to_u64`]
+    pub fn from_u64(int: u64) -> Option<Self> {
+        match int {
+            1 => Some(Self::Tx),
+            2 => Some(Self::Vp),
+            _ => None,
+        }
+    }
+}
+
 impl From<bool> for HostEnvResult {
     fn from(success: bool) -> Self {
         if success { Self::Success } else { Self::Fail }
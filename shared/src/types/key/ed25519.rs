@@ -296,6 +296,47 @@ impl PartialOrd for Signature {
     }
 }
 
+/// Evidence of double-signing: two valid signatures made with the same key
+/// over conflicting messages, e.g. two different blocks at the same height
+#[derive(Clone, Debug, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct DoubleSignEvidence {
+    /// The public key of the signer
+    pub pk: PublicKey,
+    /// The first signed message
+    pub msg_a: Vec<u8>,
+    /// The signature of the first message
+    pub sig_a: Signature,
+    /// The second signed message, conflicting with the first one
+    pub msg_b: Vec<u8>,
+    /// The signature of the second message
+    pub sig_b: Signature,
+}
+
+/// Check if the two messages signed with the same key are a double-sign,
+/// assuming that the messages have been signed for the same height. Returns
+/// the evidence if both signatures are valid for the `pk` and the messages
+/// differ, otherwise `None`.
+pub fn same_message_double_sign(
+    pk: &PublicKey,
+    msg_a: &[u8],
+    sig_a: &Signature,
+    msg_b: &[u8],
+    sig_b: &Signature,
+) -> Option<DoubleSignEvidence> {
+    if msg_a == msg_b {
+        return None;
+    }
+    SigScheme::verify_signature_raw(pk, msg_a, sig_a).ok()?;
+    SigScheme::verify_signature_raw(pk, msg_b, sig_b).ok()?;
+    Some(DoubleSignEvidence {
+        pk: pk.clone(),
+        msg_a: msg_a.to_vec(),
+        sig_a: sig_a.clone(),
+        msg_b: msg_b.to_vec(),
+        sig_b: sig_b.clone(),
+    })
+}
+
 /// An implementation of the Ed25519 signature scheme
 #[derive(
     Debug,
@@ -353,3 +394,45 @@ impl super::SigScheme for SigScheme {
             .map_err(|err| VerifySigError::SigVerifyError(err.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::key::testing;
+
+    #[test]
+    fn test_same_message_double_sign() {
+        let keypair = testing::gen_keypair::<SigScheme>();
+        let pk = keypair.ref_to();
+        let msg_a = b"block A at height 1";
+        let msg_b = b"block B at height 1";
+        let sig_a = SigScheme::sign(&keypair, msg_a);
+        let sig_b = SigScheme::sign(&keypair, msg_b);
+
+        let evidence =
+            same_message_double_sign(&pk, msg_a, &sig_a, msg_b, &sig_b)
+                .expect("Conflicting signatures should produce evidence");
+        assert_eq!(evidence.pk, pk);
+        assert_eq!(evidence.msg_a, msg_a);
+        assert_eq!(evidence.msg_b, msg_b);
+
+        // Signing the same message twice is not a double-sign
+        let sig_a_again = SigScheme::sign(&keypair, msg_a);
+        assert_eq!(
+            same_message_double_sign(&pk, msg_a, &sig_a, msg_a, &sig_a_again),
+            None
+        );
+
+        // Both signatures must be valid for the key
+        let other = testing::gen_keypair::<SigScheme>();
+        let sig_other = SigScheme::sign(&other, msg_b);
+        assert_eq!(
+            same_message_double_sign(&pk, msg_a, &sig_a, msg_b, &sig_other),
+            None
+        );
+        assert_eq!(
+            same_message_double_sign(&pk, msg_a, &sig_b, msg_b, &sig_a),
+            None
+        );
+    }
+}
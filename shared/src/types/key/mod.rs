@@ -22,6 +22,7 @@ pub mod ed25519;
 
 const PK_STORAGE_KEY: &str = "public_key";
 const PROTOCOL_PK_STORAGE_KEY: &str = "protocol_public_key";
+const MULTI_PK_STORAGE_KEY: &str = "public_keys";
 
 /// Obtain a storage key for user's public key.
 pub fn pk_key(owner: &Address) -> storage::Key {
@@ -61,6 +62,58 @@ pub fn is_protocol_pk_key(key: &Key) -> Option<&Address> {
     }
 }
 
+/// Obtain a storage key for one of the multiple user's public keys with the
+/// given index.
+pub fn multi_pk_key(owner: &Address, index: u8) -> storage::Key {
+    Key::from(owner.to_db_key())
+        .push(&MULTI_PK_STORAGE_KEY.to_owned())
+        .expect("Cannot obtain a storage key")
+        .push(&index.to_string())
+        .expect("Cannot obtain a storage key")
+}
+
+/// Check if the given storage key is one of the multiple public keys. If it
+/// is, returns the owner and the index of the key.
+pub fn is_multi_pk_key(key: &Key) -> Option<(&Address, u8)> {
+    match &key.segments[..] {
+        [
+            DbKeySeg::AddressSeg(owner),
+            DbKeySeg::StringSeg(key),
+            DbKeySeg::StringSeg(index),
+        ] if key == MULTI_PK_STORAGE_KEY => {
+            index.parse().ok().map(|index| (owner, index))
+        }
+        _ => None,
+    }
+}
+
+/// Obtain a storage key `owner/label/sub` for a typed sub-key of the owner's
+/// sub-space, e.g. `owner/allowance/spender`.
+pub fn typed_key<K: KeySeg>(owner: &Address, label: &str, sub: K) -> Key {
+    Key::from(owner.to_db_key())
+        .push(&label.to_owned())
+        .expect("Cannot obtain a storage key")
+        .push(&sub)
+        .expect("Cannot obtain a storage key")
+}
+
+/// Check if the given storage key is a typed sub-key with the given label,
+/// built with [`typed_key`]. If it is, returns the owner and the parsed
+/// sub-key.
+pub fn parse_typed_key<'a, K: KeySeg>(
+    key: &'a Key,
+    label: &str,
+) -> Option<(&'a Address, K)> {
+    match &key.segments[..] {
+        [DbKeySeg::AddressSeg(owner), DbKeySeg::StringSeg(key), sub]
+            if key == label =>
+        {
+            K::parse(sub.raw()).ok().map(|sub| (owner, sub))
+        }
+        _ => None,
+    }
+}
+
 /// Represents an error in signature verification
 #[allow(missing_docs)]
 #[derive(Error, Debug)]
@@ -71,6 +124,8 @@ pub enum VerifySigError {
     DataEncodingError(std::io::Error),
     #[error("Transaction doesn't have any data with a signature.")]
     MissingData,
+    #[error("Transaction's signed data cannot be decoded: {0}")]
+    MalformedSignedData(std::io::Error),
     #[error("Signature belongs to a different scheme from the public key.")]
     MismatchedScheme,
 }
@@ -306,7 +361,11 @@ impl FromStr for PublicKeyHash {
         if s.len() != PKH_HASH_LEN {
             return Err(Self::Err::UnexpectedLen(s.len()));
         }
-        Ok(Self(s.to_owned()))
+        if !s.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Self::Err::NotHex(s.to_owned()));
+        }
+        // The hash is formatted as uppercase hex
+        Ok(Self(s.to_ascii_uppercase()))
     }
 }
 
@@ -315,6 +374,8 @@ impl FromStr for PublicKeyHash {
 pub enum PkhFromStringError {
     #[error("Wrong PKH len. Expected {PKH_HASH_LEN}, got {0}")]
     UnexpectedLen(usize),
+    #[error("Wrong PKH format. Expected hex, got {0}")]
+    NotHex(String),
 }
 
 impl<PK: PublicKey> From<&PK> for PublicKeyHash {
@@ -332,6 +393,16 @@ impl<PK: PublicKey> From<&PK> for PublicKeyHash {
     }
 }
 
+/// Derive the validator address raw hash that is compatible with Tendermint
+/// from the validator's consensus key. Unlike [`PublicKeyHash`] of a
+/// [`common::PublicKey`], this hashes only the key's bytes without the
+/// signature scheme's tag, as Tendermint does.
+pub fn tm_consensus_key_raw_hash(pk: &common::PublicKey) -> String {
+    match pk {
+        common::PublicKey::Ed25519(pk) => PublicKeyHash::from(pk).into(),
+    }
+}
+
 /// Helpers for testing with keys.
 #[cfg(any(test, feature = "testing"))]
 pub mod testing {
@@ -371,6 +442,26 @@ pub mod testing {
             .unwrap()
     }
 
+    /// A deterministic keypair for tests, derived from the given index, so that
+    /// fixtures can use any number of stable keys. The indices `1` and `2` are
+    /// aliases of [`keypair_1`] and [`keypair_2`].
+    pub fn keypair_n(n: u8) -> <common::SigScheme as SigScheme>::SecretKey {
+        match n {
+            1 => keypair_1(),
+            2 => keypair_2(),
+            _ => {
+                let mut hasher = Sha256::new();
+                hasher.update(b"anoma-test-keypair");
+                hasher.update([n]);
+                let bytes = hasher.finalize();
+                ed25519::SecretKey::try_from_slice(bytes.as_ref())
+                    .unwrap()
+                    .try_to_sk()
+                    .unwrap()
+            }
+        }
+    }
+
     /// Generate an arbitrary [`super::SecretKey`].
     pub fn arb_keypair<S: SigScheme>() -> impl Strategy<Value = S::SecretKey> {
         any::<[u8; 32]>().prop_map(move |seed| {
@@ -421,3 +512,91 @@ macro_rules! sigscheme_test {
 
 #[cfg(test)]
 sigscheme_test! {ed25519_test, ed25519::SigScheme}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tm_consensus_key_raw_hash() {
+        let pk_1 = testing::keypair_1().ref_to();
+        let pk_2 = testing::keypair_2().ref_to();
+
+        let raw_hash = tm_consensus_key_raw_hash(&pk_1);
+        assert_eq!(raw_hash.len(), PKH_HASH_LEN);
+        assert_eq!(raw_hash, tm_consensus_key_raw_hash(&pk_1));
+        assert_ne!(raw_hash, tm_consensus_key_raw_hash(&pk_2));
+    }
+
+    #[test]
+    fn test_multi_pk_key() {
+        let owner = address::testing::established_address_1();
+        let key = multi_pk_key(&owner, 7);
+        assert_eq!(is_multi_pk_key(&key), Some((&owner, 7)));
+        assert_eq!(is_pk_key(&key), None);
+        assert_eq!(is_multi_pk_key(&pk_key(&owner)), None);
+    }
+
+    #[test]
+    fn test_pkh_from_str() {
+        let pkh = PublicKeyHash::from(&testing::keypair_1().ref_to());
+        let string = pkh.to_string();
+        assert_eq!(string.len(), PKH_HASH_LEN);
+        assert_eq!(string.parse::<PublicKeyHash>().unwrap(), pkh);
+
+        // Lowercase hex is normalized to uppercase
+        let lowercase = string.to_lowercase();
+        assert_eq!(lowercase.parse::<PublicKeyHash>().unwrap(), pkh);
+        // A string that is not hex is rejected
+        let not_hex = format!("{}G", &string[1..]);
+        assert!(matches!(
+            not_hex.parse::<PublicKeyHash>(),
+            Err(PkhFromStringError::NotHex(_))
+        ));
+        // A string with a wrong length is rejected
+        let err = string[1..].parse::<PublicKeyHash>().unwrap_err();
+        assert!(matches!(
+            err,
+            PkhFromStringError::UnexpectedLen(len) if len == PKH_HASH_LEN - 1
+        ));
+    }
+
+    #[test]
+    fn test_typed_key() {
+        let owner = address::testing::established_address_1();
+        let spender = address::testing::established_address_2();
+        let key = typed_key(&owner, "allowance", spender.clone());
+        assert_eq!(
+            key.to_string(),
+            format!("#{}/allowance/#{}", owner.encode(), spender.encode())
+        );
+        assert_eq!(
+            parse_typed_key::<Address>(&key, "allowance"),
+            Some((&owner, spender))
+        );
+        // A different label doesn't match
+        assert_eq!(parse_typed_key::<Address>(&key, "approval"), None);
+
+        // A sub-key that cannot be parsed as the expected type is rejected
+        let key = typed_key(&owner, "allowance", "spender".to_owned());
+        assert_eq!(parse_typed_key::<Address>(&key, "allowance"), None);
+        // A key with an extra segment is rejected
+        let key = key.push(&"extra".to_owned()).unwrap();
+        assert_eq!(parse_typed_key::<String>(&key, "allowance"), None);
+    }
+
+    #[test]
+    fn test_keypair_n() {
+        let pk_n = |n| testing::keypair_n(n).ref_to();
+        assert_eq!(pk_n(1), testing::keypair_1().ref_to());
+        assert_eq!(pk_n(2), testing::keypair_2().ref_to());
+        let pks: Vec<common::PublicKey> = (0..=u8::MAX).map(pk_n).collect();
+        for (n, pk) in (0..=u8::MAX).zip(&pks) {
+            // The same index yields the same key
+            assert_eq!(pk, &pk_n(n));
+        }
+        // Different indices yield different keys
+        let unique: std::collections::HashSet<_> = pks.iter().collect();
+        assert_eq!(unique.len(), pks.len());
+    }
+}
@@ -27,6 +27,10 @@ pub enum Error {
     ParseAddressFromKey,
     #[error("Reserved prefix or string is specified: {0}")]
     InvalidKeySeg(String),
+    #[error("The key length {len} exceeds the maximum {max}")]
+    KeyTooLong { len: usize, max: usize },
+    #[error("The key has {count} segments, exceeding the maximum {max}")]
+    TooManySegments { count: usize, max: usize },
 }
 
 /// Result for functions that may fail
@@ -43,6 +47,20 @@ pub const RESERVED_ADDRESS_PREFIX: char = '#';
 pub const VP_KEY_PREFIX: char = '?';
 /// The reserved storage key for validity predicates
 pub const RESERVED_VP_KEY: &str = "?";
+/// The reserved storage key segment for the expiry heights of values written
+/// with a time-to-live
+pub const RESERVED_TTL_KEY: &str = "?ttl";
+/// The reserved storage key segment for the markers of immutable values
+pub const RESERVED_IMMUTABLE_KEY: &str = "?immutable";
+/// The reserved storage key segment for the transactions scheduled by an
+/// account
+pub const RESERVED_SCHEDULED_KEY: &str = "?scheduled";
+
+/// The maximum length of a storage key string. Transactions cannot write a
+/// longer key, but any existing over-length keys remain readable.
+pub const MAX_KEY_LEN: usize = 1024;
+/// The maximum number of storage key segments
+pub const MAX_KEY_SEGMENTS: usize = 64;
 
 /// Height of a block, i.e. the level.
 #[derive(
@@ -119,6 +137,23 @@ impl BlockHeight {
     pub fn next_height(&self) -> BlockHeight {
         BlockHeight(self.0 + 1)
     }
+
+    /// Get the height of the previous block, if any
+    pub fn prev_height(&self) -> Option<BlockHeight> {
+        self.0.checked_sub(1).map(BlockHeight)
+    }
+
+    /// Add the given number of blocks to the height, returning `None` on
+    /// overflow
+    pub fn checked_add(&self, blocks: u64) -> Option<BlockHeight> {
+        self.0.checked_add(blocks).map(BlockHeight)
+    }
+
+    /// Get the number of blocks from the `other` height up to this height,
+    /// returning `None` if the `other` height is greater
+    pub fn checked_sub(&self, other: BlockHeight) -> Option<u64> {
+        self.0.checked_sub(other.0)
+    }
 }
 
 impl TryFrom<&[u8]> for BlockHash {
@@ -206,6 +241,66 @@ pub struct Key {
     pub segments: Vec<DbKeySeg>,
 }
 
+/// A builder of a storage [`Key`], which validates the key against the
+/// [`MAX_KEY_LEN`] and [`MAX_KEY_SEGMENTS`] limits when it's built.
+#[derive(Debug, Default)]
+pub struct KeyBuilder {
+    segments: Vec<DbKeySeg>,
+    error: Option<Error>,
+}
+
+impl KeyBuilder {
+    /// Start building a new key
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Push a segment. An invalid segment will be reported on
+    /// [`KeyBuilder::build`].
+    pub fn segment<T: KeySeg>(mut self, seg: &T) -> Self {
+        if self.error.is_none() {
+            match DbKeySeg::parse(seg.raw()) {
+                Ok(seg) => self.segments.push(seg),
+                Err(error) => self.error = Some(error),
+            }
+        }
+        self
+    }
+
+    /// Push an address segment
+    pub fn address(mut self, addr: &Address) -> Self {
+        if self.error.is_none() {
+            self.segments.push(addr.to_db_key());
+        }
+        self
+    }
+
+    /// Build the key. Returns an error if any of the segments is invalid, or
+    /// if the key exceeds the limits.
+    pub fn build(self) -> Result<Key> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if self.segments.len() > MAX_KEY_SEGMENTS {
+            return Err(Error::TooManySegments {
+                count: self.segments.len(),
+                max: MAX_KEY_SEGMENTS,
+            });
+        }
+        let key = Key {
+            segments: self.segments,
+        };
+        let len = key.len();
+        if len > MAX_KEY_LEN {
+            return Err(Error::KeyTooLong {
+                len,
+                max: MAX_KEY_LEN,
+            });
+        }
+        Ok(key)
+    }
+}
+
 impl From<DbKeySeg> for Key {
     fn from(seg: DbKeySeg) -> Self {
         Self {
@@ -295,6 +390,203 @@ impl Key {
         }
     }
 
+    /// Returns a key of the expiry height for a value written with a
+    /// time-to-live at this key. The expiry key is placed in the same
+    /// account's sub-space. Returns `None` for an empty key.
+    pub fn ttl(&self) -> Option<Self> {
+        let (owner, rest) = self.segments.split_first()?;
+        let mut segments = vec![
+            owner.clone(),
+            DbKeySeg::StringSeg(RESERVED_TTL_KEY.to_owned()),
+        ];
+        segments.extend(rest.iter().cloned());
+        Some(Key { segments })
+    }
+
+    /// Check if the given key is a key of an expiry height. If it is, returns
+    /// the key of the value that expires.
+    pub fn is_ttl(&self) -> Option<Self> {
+        match &self.segments[..] {
+            [owner, DbKeySeg::StringSeg(sub_key), rest @ ..]
+                if sub_key == RESERVED_TTL_KEY =>
+            {
+                let mut segments = vec![owner.clone()];
+                segments.extend(rest.iter().cloned());
+                Some(Key { segments })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns a key of the entry of a value at this key that expires at the
+    /// given height in the index of values written with a time-to-live. The
+    /// index is keyed by the expiry height first, so that the values expiring
+    /// at a height can be found by iterating [`Key::ttl_index_prefix`].
+    pub fn ttl_index(&self, expiry: BlockHeight) -> Self {
+        let mut key = Self::ttl_index_prefix(expiry);
+        key.segments.extend(self.segments.iter().cloned());
+        key
+    }
+
+    /// Returns the prefix of the entries in the index of values written with a
+    /// time-to-live that expire at the given height.
+    pub fn ttl_index_prefix(expiry: BlockHeight) -> Self {
+        Key {
+            segments: vec![
+                DbKeySeg::StringSeg(RESERVED_TTL_KEY.to_owned()),
+                height_index_seg(expiry),
+            ],
+        }
+    }
+
+    /// Check if the given key is an entry in the index of values written with
+    /// a time-to-live. If it is, returns the expiry height and the key of the
+    /// value that expires.
+    pub fn is_ttl_index(&self) -> Option<(BlockHeight, Self)> {
+        match &self.segments[..] {
+            [
+                DbKeySeg::StringSeg(prefix),
+                DbKeySeg::StringSeg(expiry),
+                rest @ ..,
+            ] if prefix == RESERVED_TTL_KEY && !rest.is_empty() => {
+                let expiry = BlockHeight(expiry.parse().ok()?);
+                let segments = rest.to_vec();
+                Some((expiry, Key { segments }))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns a key of the marker of an immutable value at this key. The
+    /// marker key is placed in the same account's sub-space. Returns `None`
+    /// for an empty key.
+    pub fn immutable(&self) -> Option<Self> {
+        let (owner, rest) = self.segments.split_first()?;
+        let mut segments = vec![
+            owner.clone(),
+            DbKeySeg::StringSeg(RESERVED_IMMUTABLE_KEY.to_owned()),
+        ];
+        segments.extend(rest.iter().cloned());
+        Some(Key { segments })
+    }
+
+    /// Check if the given key is a key of an immutable value marker. If it is,
+    /// returns the key of the immutable value.
+    pub fn is_immutable(&self) -> Option<Self> {
+        match &self.segments[..] {
+            [owner, DbKeySeg::StringSeg(sub_key), rest @ ..]
+                if sub_key == RESERVED_IMMUTABLE_KEY =>
+            {
+                let mut segments = vec![owner.clone()];
+                segments.extend(rest.iter().cloned());
+                Some(Key { segments })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns a key of the counter of the transactions scheduled by the given
+    /// account, which holds the ID of the next scheduled transaction.
+    pub fn scheduled_tx_counter(scheduler: &Address) -> Self {
+        Key {
+            segments: vec![
+                DbKeySeg::AddressSeg(scheduler.clone()),
+                DbKeySeg::StringSeg(RESERVED_SCHEDULED_KEY.to_owned()),
+            ],
+        }
+    }
+
+    /// Returns a key of the transaction scheduled by the given account with
+    /// the given ID.
+    pub fn scheduled_tx(scheduler: &Address, id: u64) -> Self {
+        let mut key = Self::scheduled_tx_counter(scheduler);
+        key.segments.push(DbKeySeg::StringSeg(id.to_string()));
+        key
+    }
+
+    /// Check if the given key is a key of a scheduled transaction. If it is,
+    /// returns the scheduler's address and the ID of the transaction.
+    pub fn is_scheduled_tx(&self) -> Option<(&Address, u64)> {
+        match &self.segments[..] {
+            [
+                DbKeySeg::AddressSeg(scheduler),
+                DbKeySeg::StringSeg(sub_key),
+                DbKeySeg::StringSeg(id),
+            ] if sub_key == RESERVED_SCHEDULED_KEY => {
+                id.parse().ok().map(|id| (scheduler, id))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns a key of the entry of the transaction scheduled by the given
+    /// account with the given ID in the index of scheduled transactions. The
+    /// index is keyed by the height at which the transaction is scheduled
+    /// first, so that the transactions scheduled at a height can be found by
+    /// iterating [`Key::scheduled_tx_index_prefix`].
+    pub fn scheduled_tx_index(
+        height: BlockHeight,
+        scheduler: &Address,
+        id: u64,
+    ) -> Self {
+        let mut key = Self::scheduled_tx_index_prefix(height);
+        key.segments.push(DbKeySeg::AddressSeg(scheduler.clone()));
+        key.segments.push(DbKeySeg::StringSeg(id.to_string()));
+        key
+    }
+
+    /// Returns the prefix of the entries in the index of scheduled
+    /// transactions that are scheduled at the given height.
+    pub fn scheduled_tx_index_prefix(height: BlockHeight) -> Self {
+        Key {
+            segments: vec![
+                DbKeySeg::StringSeg(RESERVED_SCHEDULED_KEY.to_owned()),
+                height_index_seg(height),
+            ],
+        }
+    }
+
+    /// Returns a key of the number of transactions scheduled at the given
+    /// height. The count is kept at the prefix of the height's entries in the
+    /// index of scheduled transactions.
+    pub fn scheduled_tx_count(height: BlockHeight) -> Self {
+        Self::scheduled_tx_index_prefix(height)
+    }
+
+    /// Check if the given key is any of the keys that keep track of the
+    /// scheduled transactions: a scheduler's counter, a scheduled transaction,
+    /// an entry in the index of scheduled transactions or the number of
+    /// transactions scheduled at a height.
+    pub fn is_scheduled(&self) -> bool {
+        match &self.segments[..] {
+            [DbKeySeg::StringSeg(prefix), ..]
+            | [DbKeySeg::AddressSeg(_), DbKeySeg::StringSeg(prefix), ..] => {
+                prefix == RESERVED_SCHEDULED_KEY
+            }
+            _ => false,
+        }
+    }
+
+    /// Check if the given key is an entry in the index of scheduled
+    /// transactions. If it is, returns the height at which the transaction is
+    /// scheduled, the scheduler's address and the ID of the transaction.
+    pub fn is_scheduled_tx_index(
+        &self,
+    ) -> Option<(BlockHeight, &Address, u64)> {
+        match &self.segments[..] {
+            [
+                DbKeySeg::StringSeg(prefix),
+                DbKeySeg::StringSeg(height),
+                DbKeySeg::AddressSeg(scheduler),
+                DbKeySeg::StringSeg(id),
+            ] if prefix == RESERVED_SCHEDULED_KEY => {
+                let height = BlockHeight(height.parse().ok()?);
+                id.parse().ok().map(|id| (height, scheduler, id))
+            }
+            _ => None,
+        }
+    }
+
     /// Returns a key from the given DB key path that has the height and
     /// the space type
     pub fn parse_db_key(db_key: &str) -> Result<Self> {
@@ -444,6 +736,13 @@ impl KeySeg for String {
     }
 }
 
+/// Get a key segment of the given height in an index keyed by heights. The
+/// height is zero-padded to a fixed width, so that the prefix of the entries at
+/// one height doesn't match the entries at any other height.
+fn height_index_seg(height: BlockHeight) -> DbKeySeg {
+    DbKeySeg::StringSeg(format!("{:020}", height.0))
+}
+
 impl KeySeg for BlockHeight {
     fn parse(string: String) -> Result<Self> {
         let h = string.parse::<u64>().map_err(|e| Error::Temporary {
@@ -656,6 +955,12 @@ impl Epochs {
         self.first_block_heights.push(block_height);
     }
 
+    /// Look-up the height of the first block of a given epoch.
+    pub fn first_block_height(&self, epoch: Epoch) -> Option<BlockHeight> {
+        let index = epoch.0.checked_sub(self.first_known_epoch.0)?;
+        self.first_block_heights.get(index as usize).copied()
+    }
+
     /// Look-up the epoch of a given block height.
     pub fn get_epoch(&self, block_height: BlockHeight) -> Option<Epoch> {
         if let Some((first_known_epoch_height, rest)) =
@@ -782,6 +1087,153 @@ mod tests {
         assert_eq!(key.to_string(), target);
     }
 
+    #[test]
+    fn test_key_builder() {
+        let addr = address::testing::established_address_1();
+        let key = KeyBuilder::new()
+            .address(&addr)
+            .segment(&"balance".to_owned())
+            .segment(&address::testing::established_address_2())
+            .build()
+            .expect("cannot build the key");
+        let expected = Key::from(addr.to_db_key())
+            .push(&"balance".to_owned())
+            .unwrap()
+            .push(&address::testing::established_address_2())
+            .unwrap();
+        assert_eq!(key, expected);
+
+        let result = KeyBuilder::new()
+            .address(&addr)
+            .segment(&"a".repeat(MAX_KEY_LEN))
+            .build();
+        assert!(matches!(result, Err(Error::KeyTooLong { .. })));
+
+        let mut builder = KeyBuilder::new();
+        for i in 0..=MAX_KEY_SEGMENTS {
+            builder = builder.segment(&i.to_string());
+        }
+        let result = builder.build();
+        assert!(matches!(result, Err(Error::TooManySegments { .. })));
+
+        let result = KeyBuilder::new()
+            .address(&addr)
+            .segment(&"a/b".to_owned())
+            .build();
+        assert!(matches!(result, Err(Error::InvalidKeySeg(_))));
+    }
+
+    #[test]
+    fn test_key_ttl() {
+        let addr = address::testing::established_address_1();
+        let key = Key::from(addr.to_db_key())
+            .push(&"session".to_owned())
+            .unwrap();
+        let ttl_key = key.ttl().expect("the key is not empty");
+        assert_eq!(
+            ttl_key.to_string(),
+            format!("#{}/{}/session", addr.encode(), RESERVED_TTL_KEY)
+        );
+        assert_eq!(ttl_key.is_ttl(), Some(key.clone()));
+        assert_eq!(key.is_ttl(), None);
+        assert_eq!(Key { segments: vec![] }.ttl(), None);
+
+        let index_key = key.ttl_index(BlockHeight(5));
+        assert_eq!(
+            index_key.to_string(),
+            format!(
+                "{}/00000000000000000005/#{}/session",
+                RESERVED_TTL_KEY,
+                addr.encode()
+            )
+        );
+        assert_eq!(
+            index_key.is_ttl_index(),
+            Some((BlockHeight(5), key.clone()))
+        );
+        assert_eq!(index_key.is_ttl(), None);
+        assert_eq!(ttl_key.is_ttl_index(), None);
+        assert_eq!(Key::ttl_index_prefix(BlockHeight(5)).is_ttl_index(), None);
+        // The prefix at a height doesn't match the entries at other heights
+        let prefix = Key::ttl_index_prefix(BlockHeight(5)).to_string();
+        assert!(index_key.to_string().starts_with(&prefix));
+        let later = key.ttl_index(BlockHeight(50)).to_string();
+        assert!(!later.starts_with(&prefix));
+    }
+
+    #[test]
+    fn test_key_immutable() {
+        let addr = address::testing::established_address_1();
+        let key = Key::from(addr.to_db_key())
+            .push(&"code_hash".to_owned())
+            .unwrap();
+        let marker_key = key.immutable().expect("the key is not empty");
+        assert_eq!(
+            marker_key.to_string(),
+            format!("#{}/{}/code_hash", addr.encode(), RESERVED_IMMUTABLE_KEY)
+        );
+        assert_eq!(marker_key.is_immutable(), Some(key.clone()));
+        assert_eq!(key.is_immutable(), None);
+        assert_eq!(marker_key.is_ttl(), None);
+        assert_eq!(Key { segments: vec![] }.immutable(), None);
+    }
+
+    #[test]
+    fn test_key_scheduled_tx() {
+        let addr = address::testing::established_address_1();
+        let key = Key::scheduled_tx(&addr, 7);
+        assert_eq!(
+            key.to_string(),
+            format!("#{}/{}/7", addr.encode(), RESERVED_SCHEDULED_KEY)
+        );
+        assert_eq!(key.is_scheduled_tx(), Some((&addr, 7)));
+        assert_eq!(Key::scheduled_tx_counter(&addr).is_scheduled_tx(), None);
+        let other = Key::from(addr.to_db_key())
+            .push(&"balance".to_owned())
+            .unwrap();
+        assert_eq!(other.is_scheduled_tx(), None);
+
+        let index_key = Key::scheduled_tx_index(BlockHeight(5), &addr, 7);
+        assert_eq!(
+            index_key.to_string(),
+            format!(
+                "{}/00000000000000000005/#{}/7",
+                RESERVED_SCHEDULED_KEY,
+                addr.encode()
+            )
+        );
+        assert_eq!(
+            index_key.is_scheduled_tx_index(),
+            Some((BlockHeight(5), &addr, 7))
+        );
+        assert_eq!(index_key.is_scheduled_tx(), None);
+        assert_eq!(key.is_scheduled_tx_index(), None);
+        let prefix = Key::scheduled_tx_index_prefix(BlockHeight(5));
+        assert!(index_key.to_string().starts_with(&prefix.to_string()));
+
+        for scheduled in [
+            &key,
+            &index_key,
+            &Key::scheduled_tx_counter(&addr),
+            &Key::scheduled_tx_count(BlockHeight(5)),
+        ] {
+            assert!(scheduled.is_scheduled(), "{}", scheduled);
+        }
+        assert!(!other.is_scheduled());
+    }
+
+    #[test]
+    fn test_block_height_arithmetic() {
+        assert_eq!(BlockHeight(0).prev_height(), None);
+        assert_eq!(BlockHeight(5).prev_height(), Some(BlockHeight(4)));
+        assert_eq!(BlockHeight(5).next_height(), BlockHeight(6));
+        assert_eq!(BlockHeight(5).checked_add(3), Some(BlockHeight(8)));
+        assert_eq!(BlockHeight(u64::MAX).checked_add(1), None);
+        assert_eq!(BlockHeight(8).checked_sub(BlockHeight(5)), Some(3));
+        assert_eq!(BlockHeight(5).checked_sub(BlockHeight(8)), None);
+        assert!(BlockHeight(5) < BlockHeight(8));
+    }
+
     #[test]
     fn test_key_push_valid() {
         let addr = address::testing::established_address_1();
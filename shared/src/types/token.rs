@@ -82,6 +82,20 @@ impl Amount {
             micro: change as u64,
         }
     }
+
+    /// Encode the amount of micro units as fixed-width 8 bytes in big-endian
+    /// order, independent of Borsh.
+    pub fn to_be_bytes(&self) -> [u8; 8] {
+        self.micro.to_be_bytes()
+    }
+
+    /// Decode the amount of micro units from fixed-width 8 bytes in big-endian
+    /// order.
+    pub fn from_be_bytes(bytes: [u8; 8]) -> Self {
+        Self {
+            micro: u64::from_be_bytes(bytes),
+        }
+    }
 }
 
 impl serde::Serialize for Amount {
@@ -233,6 +247,36 @@ impl From<Amount> for Change {
 
 /// Key segment for a balance key
 pub const BALANCE_STORAGE_KEY: &str = "balance";
+/// Key segment for a token metadata key
+pub const META_STORAGE_KEY: &str = "meta";
+
+/// The metadata of a token
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    BorshSerialize,
+    BorshDeserialize,
+    BorshSchema,
+    Serialize,
+    Deserialize,
+)]
+pub struct TokenMeta {
+    /// The token's symbol
+    pub symbol: String,
+    /// The number of decimal places of the token's amounts
+    pub decimals: u8,
+    /// The maximum total supply in micro units, if any
+    pub cap: Option<u64>,
+}
+
+/// Obtain a storage key for the token's metadata.
+pub fn token_meta_key(token_addr: &Address) -> Key {
+    Key::from(token_addr.to_db_key())
+        .push(&META_STORAGE_KEY.to_owned())
+        .expect("Cannot obtain a storage key")
+}
 
 /// Obtain a storage key for user's balance.
 pub fn balance_key(token_addr: &Address, owner: &Address) -> Key {
@@ -376,6 +420,16 @@ mod tests {
                 let identity = Amount::from(float);
                 assert_eq!(amount, identity);
         }
+
+            /// The big-endian bytes must round-trip and be equal to the
+            /// big-endian bytes of the micro units.
+            #[test]
+            fn test_token_amount_be_bytes(raw_amount: u64) {
+                let amount = Amount::from(raw_amount);
+                let bytes = amount.to_be_bytes();
+                assert_eq!(bytes, raw_amount.to_be_bytes());
+                assert_eq!(Amount::from_be_bytes(bytes), amount);
+        }
     }
 
     #[test]
@@ -12,6 +12,8 @@ pub mod nft;
 pub mod pos;
 /// transaction protocols made by validators
 pub mod protocol;
+/// txs scheduled to be applied at a future block height
+pub mod scheduled;
 /// wrapper txs with encrypted payloads
 pub mod wrapper;
 
@@ -23,10 +25,12 @@ pub use decrypted::*;
 #[cfg(feature = "ferveo-tpke")]
 pub use encrypted::EncryptionKey;
 pub use protocol::UpdateDkgSessionKey;
+pub use scheduled::{ScheduledTx, MAX_SCHEDULED_TXS_PER_HEIGHT};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 pub use wrapper::*;
 
+use super::event::TxEvent;
 use super::ibc::IbcEvent;
 use super::storage;
 use crate::ledger::gas::VpsGas;
@@ -54,6 +58,8 @@ pub struct TxResult {
     pub initialized_accounts: Vec<Address>,
     /// Optional IBC event emitted by the transaction
     pub ibc_event: Option<IbcEvent>,
+    /// The events emitted by the transaction, in the order of emission
+    pub events: Vec<TxEvent>,
 }
 
 impl TxResult {
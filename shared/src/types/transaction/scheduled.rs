@@ -0,0 +1,28 @@
+//! Transactions scheduled to be applied at a future block height.
+
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+
+use crate::types::address::Address;
+use crate::types::storage::BlockHeight;
+
+/// The maximum number of transactions that can be scheduled at a single block
+/// height. Scheduled transactions are applied at the beginning of the block
+/// outside of the block's gas limit, so their number has to be bounded.
+pub const MAX_SCHEDULED_TXS_PER_HEIGHT: u64 = 64;
+
+/// A transaction scheduled by an account to be applied at the beginning of
+/// the block at the given height. It's stored in the scheduler's sub-space,
+/// so scheduling it must be authorized by the scheduler's validity predicate.
+#[derive(
+    Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize, BorshSchema,
+)]
+pub struct ScheduledTx {
+    /// The account that scheduled the transaction
+    pub scheduler: Address,
+    /// The transaction's code
+    pub code: Vec<u8>,
+    /// The transaction's data
+    pub data: Option<Vec<u8>>,
+    /// The height of the block in which the transaction will be applied
+    pub height: BlockHeight,
+}
@@ -13,20 +13,25 @@ use super::wasm::TxCache;
 use super::wasm::VpCache;
 use super::WasmCacheAccess;
 use crate::ledger::gas::{self, BlockGasMeter, VpGasMeter};
+use crate::ledger::parameters;
 use crate::ledger::storage::write_log::{self, WriteLog};
 use crate::ledger::storage::{self, Storage, StorageHasher};
 use crate::ledger::vp_env;
-use crate::proto::Tx;
+use crate::proto::{SignedTxData, Tx};
 use crate::types::address::{self, Address};
+use crate::types::event::TxEvent;
+use crate::types::hash::Hash;
 use crate::types::ibc::IbcEvent;
-use crate::types::internal::HostEnvResult;
+use crate::types::internal::{ExecContext, HostEnvResult};
 use crate::types::key::*;
-use crate::types::storage::Key;
+use crate::types::storage::{BlockHeight, Key, MAX_KEY_LEN};
+use crate::types::transaction::{ScheduledTx, MAX_SCHEDULED_TXS_PER_HEIGHT};
 use crate::vm::memory::VmMemory;
 use crate::vm::prefix_iter::{PrefixIteratorId, PrefixIterators};
 use crate::vm::types::KeyVal;
 use crate::vm::{
-    validate_untrusted_wasm, HostRef, MutHostRef, WasmValidationError,
+    validate_untrusted_vp_wasm, validate_untrusted_wasm, HostRef, MutHostRef,
+    WasmValidationError,
 };
 
 const VERIFY_TX_SIG_GAS_COST: u64 = 1000;
@@ -44,6 +49,21 @@ pub enum TxRuntimeError {
     UpdateVpInvalid(WasmValidationError),
     #[error("A validity predicate of an account cannot be deleted")]
     CannotDeleteVp,
+    #[error("Trying to modify an immutable storage key {0}")]
+    ImmutableKeyModification(Key),
+    #[error(
+        "Trying to schedule a transaction at the height {height}, which is \
+         not after the current height {current}"
+    )]
+    InvalidScheduleHeight {
+        height: BlockHeight,
+        current: BlockHeight,
+    },
+    #[error(
+        "Trying to schedule a transaction at the height {height}, which \
+         already has the maximum of {max} scheduled transactions"
+    )]
+    TooManyScheduledTxs { height: BlockHeight, max: u64 },
     #[error(
         "Trying to initialize an account with an invalid validity predicate \
          WASM {0}"
@@ -519,32 +539,39 @@ where
 
     let key = Key::parse(key).map_err(TxRuntimeError::StorageDataError)?;
 
+    let present = has_key_in_tx(env, &key)?;
+    Ok(HostEnvResult::from(present).to_i64())
+}
+
+/// Check if the given key is present. It will try to check the write log first
+/// and if no entry found then the storage.
+fn has_key_in_tx<MEM, DB, H, CA>(
+    env: &TxEnv<MEM, DB, H, CA>,
+    key: &Key,
+) -> TxResult<bool>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    CA: WasmCacheAccess,
+{
     // try to read from the write log first
     let write_log = unsafe { env.ctx.write_log.get() };
-    let (log_val, gas) = write_log.read(&key);
+    let (log_val, gas) = write_log.read(key);
     tx_add_gas(env, gas)?;
     Ok(match log_val {
-        Some(&write_log::StorageModification::Write { .. }) => {
-            HostEnvResult::Success.to_i64()
-        }
-        Some(&write_log::StorageModification::Delete) => {
-            // the given key has been deleted
-            HostEnvResult::Fail.to_i64()
-        }
-        Some(&write_log::StorageModification::InitAccount { .. }) => {
-            HostEnvResult::Success.to_i64()
-        }
-        Some(&write_log::StorageModification::Temp { .. }) => {
-            HostEnvResult::Success.to_i64()
-        }
+        Some(&write_log::StorageModification::Write { .. }) => true,
+        // the given key has been deleted
+        Some(&write_log::StorageModification::Delete) => false,
+        Some(&write_log::StorageModification::InitAccount { .. }) => true,
+        Some(&write_log::StorageModification::Temp { .. }) => true,
         None => {
             // when not found in write log, try to check the storage
             let storage = unsafe { env.ctx.storage.get() };
-            let (present, gas) = storage
-                .has_key(&key)
-                .map_err(TxRuntimeError::StorageError)?;
+            let (present, gas) =
+                storage.has_key(key).map_err(TxRuntimeError::StorageError)?;
             tx_add_gas(env, gas)?;
-            HostEnvResult::from(present).to_i64()
+            present
         }
     })
 }
@@ -692,7 +719,7 @@ where
     let iterators = unsafe { env.ctx.iterators.get() };
     let (iter, gas) = storage.iter_prefix(&prefix);
     tx_add_gas(env, gas)?;
-    Ok(iterators.insert(iter).id())
+    Ok(iterators.insert(iter, prefix, storage.last_height).id())
 }
 
 /// Storage prefix iterator next function exposed to the wasm VM Tx environment.
@@ -778,6 +805,55 @@ where
     Ok(HostEnvResult::Fail.to_i64())
 }
 
+/// Storage prefix iterator validity check function exposed to the wasm VM Tx
+/// environment. Returns `1` when the iterator with the given ID is known to
+/// the host, `-1` otherwise.
+pub fn tx_iter_is_valid<MEM, DB, H, CA>(
+    env: &TxEnv<MEM, DB, H, CA>,
+    iter_id: u64,
+) -> TxResult<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    CA: WasmCacheAccess,
+{
+    tracing::debug!("tx_iter_is_valid iter_id {}", iter_id);
+
+    tx_add_gas(env, gas::MIN_STORAGE_GAS)?;
+    let iterators = unsafe { env.ctx.iterators.get() };
+    let iter_id = PrefixIteratorId::new(iter_id);
+    Ok(HostEnvResult::from(iterators.contains(iter_id)).to_i64())
+}
+
+/// Storage prefix iterator snapshot height function exposed to the wasm VM Tx
+/// environment. Returns the height of the committed block whose state the
+/// iterator with the given ID reflects, or `-1` when the iterator is not known
+/// to the host.
+pub fn tx_iter_snapshot_height<MEM, DB, H, CA>(
+    env: &TxEnv<MEM, DB, H, CA>,
+    iter_id: u64,
+) -> TxResult<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    CA: WasmCacheAccess,
+{
+    tracing::debug!("tx_iter_snapshot_height iter_id {}", iter_id);
+
+    tx_add_gas(env, gas::MIN_STORAGE_GAS)?;
+    let iterators = unsafe { env.ctx.iterators.get() };
+    let iter_id = PrefixIteratorId::new(iter_id);
+    match iterators.snapshot_height(iter_id) {
+        Some(height) => height
+            .0
+            .try_into()
+            .map_err(TxRuntimeError::NumConversionError),
+        None => Ok(HostEnvResult::Fail.to_i64()),
+    }
+}
+
 /// Storage write function exposed to the wasm VM Tx environment. The given
 /// key/value will be written to the write log.
 pub fn tx_write<MEM, DB, H, CA>(
@@ -808,12 +884,16 @@ where
 
     let key = Key::parse(key).map_err(TxRuntimeError::StorageDataError)?;
 
+    check_key_len(&key)?;
     check_address_existence(env, &key)?;
+    check_key_mutability(env, &key)?;
 
     let write_log = unsafe { env.ctx.write_log.get() };
     let (gas, _size_diff) = write_log
         .write(&key, value)
         .map_err(TxRuntimeError::StorageModificationError)?;
+    let iterators = unsafe { env.ctx.iterators.get() };
+    iterators.invalidate(&key);
     tx_add_gas(env, gas)
     // TODO: charge the size diff
 }
@@ -849,16 +929,394 @@ where
 
     let key = Key::parse(key).map_err(TxRuntimeError::StorageDataError)?;
 
+    check_key_len(&key)?;
     check_address_existence(env, &key)?;
+    check_key_mutability(env, &key)?;
 
     let write_log = unsafe { env.ctx.write_log.get() };
     let (gas, _size_diff) = write_log
         .write_temp(&key, value)
         .map_err(TxRuntimeError::StorageModificationError)?;
+    let iterators = unsafe { env.ctx.iterators.get() };
+    iterators.invalidate(&key);
+    tx_add_gas(env, gas)
+    // TODO: charge the size diff
+}
+
+/// Storage write with a time-to-live function exposed to the wasm VM Tx
+/// environment. The given key/value will be written to the write log together
+/// with its expiry height, which is the current block height plus the given
+/// `ttl_blocks`, but at least the next block height, and an entry in the index
+/// of values written with a time-to-live at the expiry height. The value is
+/// pruned from storage at the beginning of the block at the expiry height.
+pub fn tx_write_with_ttl<MEM, DB, H, CA>(
+    env: &TxEnv<MEM, DB, H, CA>,
+    key_ptr: u64,
+    key_len: u64,
+    val_ptr: u64,
+    val_len: u64,
+    ttl_blocks: u64,
+) -> TxResult<()>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    CA: WasmCacheAccess,
+{
+    let (key, gas) = env
+        .memory
+        .read_string(key_ptr, key_len as _)
+        .map_err(|e| TxRuntimeError::MemoryError(Box::new(e)))?;
+    tx_add_gas(env, gas)?;
+    let (value, gas) = env
+        .memory
+        .read_bytes(val_ptr, val_len as _)
+        .map_err(|e| TxRuntimeError::MemoryError(Box::new(e)))?;
+    tx_add_gas(env, gas)?;
+
+    tracing::debug!("tx_write_with_ttl {}, {:?}, {}", key, value, ttl_blocks);
+
+    let key = Key::parse(key).map_err(TxRuntimeError::StorageDataError)?;
+    let ttl_key = key.ttl().ok_or_else(|| {
+        TxRuntimeError::StorageDataError(
+            crate::types::storage::Error::InvalidKeySeg(key.to_string()),
+        )
+    })?;
+
+    check_key_len(&key)?;
+    check_address_existence(env, &key)?;
+    check_key_mutability(env, &key)?;
+
+    let storage = unsafe { env.ctx.storage.get() };
+    let (height, gas) = storage.get_block_height();
+    tx_add_gas(env, gas)?;
+    // The values expiring at the current height have already been pruned
+    let expiry = height
+        .checked_add(ttl_blocks.max(1))
+        .unwrap_or(crate::types::storage::BlockHeight(u64::MAX));
+    let index_key = key.ttl_index(expiry);
+
+    let write_log = unsafe { env.ctx.write_log.get() };
+    let (gas, _size_diff) = write_log
+        .write(&key, value)
+        .map_err(TxRuntimeError::StorageModificationError)?;
+    tx_add_gas(env, gas)?;
+    let expiry = expiry.try_to_vec().map_err(TxRuntimeError::EncodingError)?;
+    let (gas, _size_diff) = write_log
+        .write(&ttl_key, expiry)
+        .map_err(TxRuntimeError::StorageModificationError)?;
+    tx_add_gas(env, gas)?;
+    let (gas, _size_diff) = write_log
+        .write(&index_key, vec![])
+        .map_err(TxRuntimeError::StorageModificationError)?;
+    let iterators = unsafe { env.ctx.iterators.get() };
+    for key in [&key, &ttl_key, &index_key] {
+        iterators.invalidate(key);
+    }
     tx_add_gas(env, gas)
     // TODO: charge the size diff
 }
 
+/// Storage write of an immutable value function exposed to the wasm VM Tx
+/// environment. The given key/value will be written to the write log only if
+/// the key is not present, together with a marker that makes the key
+/// immutable. Any later modification of the key is rejected.
+///
+/// Returns [`HostEnvResult::Fail`] without modifying the storage when the key
+/// is already present.
+pub fn tx_write_immutable<MEM, DB, H, CA>(
+    env: &TxEnv<MEM, DB, H, CA>,
+    key_ptr: u64,
+    key_len: u64,
+    val_ptr: u64,
+    val_len: u64,
+) -> TxResult<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    CA: WasmCacheAccess,
+{
+    let (key, gas) = env
+        .memory
+        .read_string(key_ptr, key_len as _)
+        .map_err(|e| TxRuntimeError::MemoryError(Box::new(e)))?;
+    tx_add_gas(env, gas)?;
+    let (value, gas) = env
+        .memory
+        .read_bytes(val_ptr, val_len as _)
+        .map_err(|e| TxRuntimeError::MemoryError(Box::new(e)))?;
+    tx_add_gas(env, gas)?;
+
+    tracing::debug!("tx_write_immutable {}, {:?}", key, value);
+
+    let key = Key::parse(key).map_err(TxRuntimeError::StorageDataError)?;
+    let marker_key = key.immutable().ok_or_else(|| {
+        TxRuntimeError::StorageDataError(
+            crate::types::storage::Error::InvalidKeySeg(key.to_string()),
+        )
+    })?;
+
+    check_key_len(&key)?;
+    check_address_existence(env, &key)?;
+    if key.is_immutable().is_some() || has_key_in_tx(env, &key)? {
+        return Ok(HostEnvResult::Fail.to_i64());
+    }
+    check_key_mutability(env, &key)?;
+
+    let write_log = unsafe { env.ctx.write_log.get() };
+    let (gas, _size_diff) = write_log
+        .write(&key, value)
+        .map_err(TxRuntimeError::StorageModificationError)?;
+    tx_add_gas(env, gas)?;
+    let (gas, _size_diff) = write_log
+        .write(&marker_key, vec![])
+        .map_err(TxRuntimeError::StorageModificationError)?;
+    tx_add_gas(env, gas)?;
+    // TODO: charge the size diff
+    let iterators = unsafe { env.ctx.iterators.get() };
+    iterators.invalidate(&key);
+    iterators.invalidate(&marker_key);
+    Ok(HostEnvResult::Success.to_i64())
+}
+
+/// Schedule a transaction function exposed to the wasm VM Tx environment. The
+/// given Borsh encoded [`ScheduledTx`] will be written to the write log under
+/// the scheduler's sub-space, with a new ID from the scheduler's counter, and
+/// added to the index of transactions scheduled at its height. The height at
+/// which it's scheduled must be after the current block height and it must
+/// have fewer than [`MAX_SCHEDULED_TXS_PER_HEIGHT`] scheduled transactions.
+///
+/// Returns the ID of the scheduled transaction.
+pub fn tx_schedule<MEM, DB, H, CA>(
+    env: &TxEnv<MEM, DB, H, CA>,
+    tx_ptr: u64,
+    tx_len: u64,
+) -> TxResult<u64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    CA: WasmCacheAccess,
+{
+    let (tx, gas) = env
+        .memory
+        .read_bytes(tx_ptr, tx_len as _)
+        .map_err(|e| TxRuntimeError::MemoryError(Box::new(e)))?;
+    tx_add_gas(env, gas)?;
+    let scheduled: ScheduledTx = BorshDeserialize::try_from_slice(&tx)
+        .map_err(TxRuntimeError::EncodingError)?;
+
+    let storage = unsafe { env.ctx.storage.get() };
+    let (current, gas) = storage.get_block_height();
+    tx_add_gas(env, gas)?;
+    if scheduled.height <= current {
+        return Err(TxRuntimeError::InvalidScheduleHeight {
+            height: scheduled.height,
+            current,
+        });
+    }
+
+    let counter_key = Key::scheduled_tx_counter(&scheduled.scheduler);
+    check_address_existence(env, &counter_key)?;
+    let id: u64 = match read_in_tx(env, &counter_key)? {
+        Some(value) => BorshDeserialize::try_from_slice(&value)
+            .map_err(TxRuntimeError::EncodingError)?,
+        None => 0,
+    };
+    let count_key = Key::scheduled_tx_count(scheduled.height);
+    let count: u64 = match read_in_tx(env, &count_key)? {
+        Some(value) => BorshDeserialize::try_from_slice(&value)
+            .map_err(TxRuntimeError::EncodingError)?,
+        None => 0,
+    };
+    if count >= MAX_SCHEDULED_TXS_PER_HEIGHT {
+        return Err(TxRuntimeError::TooManyScheduledTxs {
+            height: scheduled.height,
+            max: MAX_SCHEDULED_TXS_PER_HEIGHT,
+        });
+    }
+    let key = Key::scheduled_tx(&scheduled.scheduler, id);
+    let index_key =
+        Key::scheduled_tx_index(scheduled.height, &scheduled.scheduler, id);
+    tracing::debug!("tx_schedule {} at height {}", key, scheduled.height);
+
+    let write_log = unsafe { env.ctx.write_log.get() };
+    let (gas, _size_diff) = write_log
+        .write(&key, tx)
+        .map_err(TxRuntimeError::StorageModificationError)?;
+    tx_add_gas(env, gas)?;
+    let (gas, _size_diff) = write_log
+        .write(&index_key, vec![])
+        .map_err(TxRuntimeError::StorageModificationError)?;
+    tx_add_gas(env, gas)?;
+    let next_id = (id + 1)
+        .try_to_vec()
+        .map_err(TxRuntimeError::EncodingError)?;
+    let (gas, _size_diff) = write_log
+        .write(&counter_key, next_id)
+        .map_err(TxRuntimeError::StorageModificationError)?;
+    tx_add_gas(env, gas)?;
+    let count = (count + 1)
+        .try_to_vec()
+        .map_err(TxRuntimeError::EncodingError)?;
+    let (gas, _size_diff) = write_log
+        .write(&count_key, count)
+        .map_err(TxRuntimeError::StorageModificationError)?;
+    tx_add_gas(env, gas)?;
+    // TODO: charge the size diff
+    Ok(id)
+}
+
+/// Cancel a scheduled transaction function exposed to the wasm VM Tx
+/// environment. The transaction with the given ID, as returned from
+/// [`tx_schedule`], is deleted from the scheduler's sub-space, so the
+/// scheduler's validity predicate must authorize it, and from the index of
+/// scheduled transactions.
+///
+/// Returns `1` if the transaction was found and canceled, or `-1` otherwise.
+pub fn tx_cancel_scheduled<MEM, DB, H, CA>(
+    env: &TxEnv<MEM, DB, H, CA>,
+    scheduler_ptr: u64,
+    scheduler_len: u64,
+    id: u64,
+) -> TxResult<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    CA: WasmCacheAccess,
+{
+    let (scheduler, gas) = env
+        .memory
+        .read_string(scheduler_ptr, scheduler_len as _)
+        .map_err(|e| TxRuntimeError::MemoryError(Box::new(e)))?;
+    tx_add_gas(env, gas)?;
+    let scheduler =
+        Address::decode(scheduler).map_err(TxRuntimeError::AddressError)?;
+
+    let key = Key::scheduled_tx(&scheduler, id);
+    tracing::debug!("tx_cancel_scheduled {}", key);
+    let scheduled: ScheduledTx = match read_in_tx(env, &key)? {
+        Some(value) => BorshDeserialize::try_from_slice(&value)
+            .map_err(TxRuntimeError::EncodingError)?,
+        None => return Ok(HostEnvResult::Fail.to_i64()),
+    };
+    let index_key = Key::scheduled_tx_index(scheduled.height, &scheduler, id);
+    let count_key = Key::scheduled_tx_count(scheduled.height);
+    let count: u64 = match read_in_tx(env, &count_key)? {
+        Some(value) => BorshDeserialize::try_from_slice(&value)
+            .map_err(TxRuntimeError::EncodingError)?,
+        None => 0,
+    };
+
+    let write_log = unsafe { env.ctx.write_log.get() };
+    let (gas, _size_diff) = write_log
+        .delete(&key)
+        .map_err(TxRuntimeError::StorageModificationError)?;
+    let iterators = unsafe { env.ctx.iterators.get() };
+    iterators.invalidate(&key);
+    tx_add_gas(env, gas)?;
+    let (gas, _size_diff) = write_log
+        .delete(&index_key)
+        .map_err(TxRuntimeError::StorageModificationError)?;
+    tx_add_gas(env, gas)?;
+    let count = count
+        .saturating_sub(1)
+        .try_to_vec()
+        .map_err(TxRuntimeError::EncodingError)?;
+    let (gas, _size_diff) = write_log
+        .write(&count_key, count)
+        .map_err(TxRuntimeError::StorageModificationError)?;
+    tx_add_gas(env, gas)?;
+    // The deletions only shrink the storage and the count's value has a
+    // constant size, so there's no size diff to charge for
+    Ok(HostEnvResult::Success.to_i64())
+}
+
+/// Read the value of the given key. It will try to read from the write log
+/// first and if no entry found then from the storage.
+fn read_in_tx<MEM, DB, H, CA>(
+    env: &TxEnv<MEM, DB, H, CA>,
+    key: &Key,
+) -> TxResult<Option<Vec<u8>>>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    CA: WasmCacheAccess,
+{
+    let write_log = unsafe { env.ctx.write_log.get() };
+    let (log_val, gas) = write_log.read(key);
+    tx_add_gas(env, gas)?;
+    Ok(match log_val {
+        Some(write_log::StorageModification::Write { value })
+        | Some(write_log::StorageModification::Temp { value }) => {
+            Some(value.clone())
+        }
+        Some(write_log::StorageModification::InitAccount { vp, .. }) => {
+            Some(vp.clone())
+        }
+        Some(write_log::StorageModification::Delete) => None,
+        None => {
+            let storage = unsafe { env.ctx.storage.get() };
+            let (value, gas) =
+                storage.read(key).map_err(TxRuntimeError::StorageError)?;
+            tx_add_gas(env, gas)?;
+            value
+        }
+    })
+}
+
+/// Check that the given key to be written doesn't exceed the [`MAX_KEY_LEN`].
+fn check_key_len(key: &Key) -> TxResult<()> {
+    let len = key.len();
+    if len > MAX_KEY_LEN {
+        tracing::info!("Trying to write a storage key of length {}", len);
+        return Err(TxRuntimeError::StorageDataError(
+            crate::types::storage::Error::KeyTooLong {
+                len,
+                max: MAX_KEY_LEN,
+            },
+        ));
+    }
+    Ok(())
+}
+
+/// Check that the given key is neither an immutable value, nor a marker of an
+/// immutable value, nor an expiry height of a value written with a
+/// time-to-live, nor an entry of the index of values written with a
+/// time-to-live, nor any of the keys of scheduled transactions, which are only
+/// maintained by the host.
+fn check_key_mutability<MEM, DB, H, CA>(
+    env: &TxEnv<MEM, DB, H, CA>,
+    key: &Key,
+) -> TxResult<()>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    CA: WasmCacheAccess,
+{
+    let is_reserved = key.is_immutable().is_some()
+        || key.is_ttl().is_some()
+        || key.is_ttl_index().is_some()
+        || key.is_scheduled();
+    let is_immutable = if is_reserved {
+        true
+    } else {
+        match key.immutable() {
+            Some(marker_key) => has_key_in_tx(env, &marker_key)?,
+            None => false,
+        }
+    };
+    if is_immutable {
+        tracing::info!("Trying to modify an immutable storage key {}", key);
+        return Err(TxRuntimeError::ImmutableKeyModification(key.clone()));
+    }
+    Ok(())
+}
+
 fn check_address_existence<MEM, DB, H, CA>(
     env: &TxEnv<MEM, DB, H, CA>,
     key: &Key,
@@ -926,6 +1384,7 @@ where
     if key.is_validity_predicate().is_some() {
         return Err(TxRuntimeError::CannotDeleteVp);
     }
+    check_key_mutability(env, &key)?;
 
     let write_log = unsafe { env.ctx.write_log.get() };
     let (gas, _size_diff) = write_log
@@ -960,10 +1419,36 @@ where
     tx_add_gas(env, gas)
 }
 
-/// Storage read prior state (before tx execution) function exposed to the wasm
-/// VM VP environment. It will try to read from the storage.
-///
-/// Returns `-1` when the key is not present, or the length of the data when
+/// Emitting an event function exposed to the wasm VM Tx environment.
+/// The given event will be appended to the events of the transaction in the
+/// write log.
+pub fn tx_emit_event<MEM, DB, H, CA>(
+    env: &TxEnv<MEM, DB, H, CA>,
+    event_ptr: u64,
+    event_len: u64,
+) -> TxResult<()>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    CA: WasmCacheAccess,
+{
+    let (event, gas) = env
+        .memory
+        .read_bytes(event_ptr, event_len as _)
+        .map_err(|e| TxRuntimeError::MemoryError(Box::new(e)))?;
+    tx_add_gas(env, gas)?;
+    let event: TxEvent = BorshDeserialize::try_from_slice(&event)
+        .map_err(TxRuntimeError::EncodingError)?;
+    let write_log = unsafe { env.ctx.write_log.get() };
+    let gas = write_log.emit_event(event);
+    tx_add_gas(env, gas)
+}
+
+/// Storage read prior state (before tx execution) function exposed to the wasm
+/// VM VP environment. It will try to read from the storage.
+///
+/// Returns `-1` when the key is not present, or the length of the data when
 /// the key is present (the length may be `0`).
 pub fn vp_read_pre<MEM, DB, H, EVAL, CA>(
     env: &VpEnv<MEM, DB, H, EVAL, CA>,
@@ -1057,6 +1542,66 @@ where
     })
 }
 
+/// Storage read posterior state range (after tx execution) function exposed to
+/// the wasm VM VP environment. It will try to read from the write log first
+/// and if no entry found then from the storage. Only the bytes of the value in
+/// the range starting at the given `offset` with at most `len` bytes are placed
+/// in the result buffer.
+///
+/// Returns `-1` when the key is not present or the `offset` is beyond the end
+/// of the value, or the length of the range otherwise (the length may be `0`).
+pub fn vp_read_post_range<MEM, DB, H, EVAL, CA>(
+    env: &VpEnv<MEM, DB, H, EVAL, CA>,
+    key_ptr: u64,
+    key_len: u64,
+    offset: u64,
+    len: u64,
+) -> vp_env::Result<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    EVAL: VpEvaluator,
+    CA: WasmCacheAccess,
+{
+    let (key, gas) = env
+        .memory
+        .read_string(key_ptr, key_len as _)
+        .map_err(|e| vp_env::RuntimeError::MemoryError(Box::new(e)))?;
+    let gas_meter = unsafe { env.ctx.gas_meter.get() };
+    vp_env::add_gas(gas_meter, gas)?;
+
+    tracing::debug!(
+        "vp_read_post_range {}, offset {}, len {}",
+        key,
+        offset,
+        len
+    );
+
+    let key =
+        Key::parse(key).map_err(vp_env::RuntimeError::StorageDataError)?;
+    let storage = unsafe { env.ctx.storage.get() };
+    let write_log = unsafe { env.ctx.write_log.get() };
+    let value = vp_env::read_post(gas_meter, storage, write_log, &key)?;
+    let range = value.and_then(|value| {
+        let start = offset as usize;
+        let end = start.saturating_add(len as usize).min(value.len());
+        value.get(start..end).map(|range| range.to_vec())
+    });
+    Ok(match range {
+        Some(range) => {
+            let len: i64 = range
+                .len()
+                .try_into()
+                .map_err(vp_env::RuntimeError::NumConversionError)?;
+            let result_buffer = unsafe { env.ctx.result_buffer.get() };
+            result_buffer.replace(range);
+            len
+        }
+        None => HostEnvResult::Fail.to_i64(),
+    })
+}
+
 /// Storage read temporary state (after tx execution) function exposed to the
 /// wasm VM VP environment. It will try to read from only the write log.
 ///
@@ -1222,7 +1767,7 @@ where
     let storage = unsafe { env.ctx.storage.get() };
     let iter = vp_env::iter_prefix(gas_meter, storage, &prefix)?;
     let iterators = unsafe { env.ctx.iterators.get() };
-    Ok(iterators.insert(iter).id())
+    Ok(iterators.insert(iter, prefix, storage.last_height).id())
 }
 
 /// Storage prefix iterator for prior state (before tx execution) function
@@ -1365,6 +1910,7 @@ where
 
     tx_add_gas(env, code.len() as u64 * WASM_VALIDATION_GAS_PER_BYTE)?;
     validate_untrusted_wasm(&code).map_err(TxRuntimeError::UpdateVpInvalid)?;
+    check_key_mutability(env, &key)?;
 
     let write_log = unsafe { env.ctx.write_log.get() };
     let (gas, _size_diff) = write_log
@@ -1374,6 +1920,59 @@ where
     // TODO: charge the size diff
 }
 
+/// Update a validity predicate function exposed to the wasm VM Tx
+/// environment, which checks that the given code is a valid VP wasm that
+/// exports the VP entrypoint before writing it. Like [`tx_write`], the account
+/// must exist and its VP must not be immutable.
+///
+/// Returns [`HostEnvResult::Fail`] without modifying the storage when the code
+/// is not a valid VP wasm.
+pub fn tx_update_validity_predicate_validated<MEM, DB, H, CA>(
+    env: &TxEnv<MEM, DB, H, CA>,
+    addr_ptr: u64,
+    addr_len: u64,
+    code_ptr: u64,
+    code_len: u64,
+) -> TxResult<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    CA: WasmCacheAccess,
+{
+    let (addr, gas) = env
+        .memory
+        .read_string(addr_ptr, addr_len as _)
+        .map_err(|e| TxRuntimeError::MemoryError(Box::new(e)))?;
+    tx_add_gas(env, gas)?;
+
+    let addr = Address::decode(addr).map_err(TxRuntimeError::AddressError)?;
+    tracing::debug!("tx_update_validity_predicate_validated for addr {}", addr);
+
+    let key = Key::validity_predicate(&addr);
+    let (code, gas) = env
+        .memory
+        .read_bytes(code_ptr, code_len as _)
+        .map_err(|e| TxRuntimeError::MemoryError(Box::new(e)))?;
+    tx_add_gas(env, gas)?;
+
+    tx_add_gas(env, code.len() as u64 * WASM_VALIDATION_GAS_PER_BYTE)?;
+    if let Err(err) = validate_untrusted_vp_wasm(&code) {
+        tracing::debug!("Rejected invalid VP code for addr {}: {}", addr, err);
+        return Ok(HostEnvResult::Fail.to_i64());
+    }
+    check_address_existence(env, &key)?;
+    check_key_mutability(env, &key)?;
+
+    let write_log = unsafe { env.ctx.write_log.get() };
+    let (gas, _size_diff) = write_log
+        .write(&key, code)
+        .map_err(TxRuntimeError::StorageModificationError)?;
+    tx_add_gas(env, gas)?;
+    // TODO: charge the size diff
+    Ok(HostEnvResult::Success.to_i64())
+}
+
 /// Initialize a new account established address.
 pub fn tx_init_account<MEM, DB, H, CA>(
     env: &TxEnv<MEM, DB, H, CA>,
@@ -1417,6 +2016,139 @@ where
     tx_add_gas(env, gas)
 }
 
+/// Initialize a new account at an address derived from the creator's address
+/// and the given salt (see [`Address::derive_sub`]), function exposed to the
+/// wasm VM Tx environment. The creator is added to the verifiers of the
+/// transaction. Returns `1` if the account has been initialized or `-1` if the
+/// derived address already exists.
+pub fn tx_init_account_at<MEM, DB, H, CA>(
+    env: &TxEnv<MEM, DB, H, CA>,
+    creator_ptr: u64,
+    creator_len: u64,
+    code_ptr: u64,
+    code_len: u64,
+    salt_ptr: u64,
+    salt_len: u64,
+) -> TxResult<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    CA: WasmCacheAccess,
+{
+    let (creator, gas) = env
+        .memory
+        .read_string(creator_ptr, creator_len as _)
+        .map_err(|e| TxRuntimeError::MemoryError(Box::new(e)))?;
+    tx_add_gas(env, gas)?;
+    let creator =
+        Address::decode(creator).map_err(TxRuntimeError::AddressError)?;
+
+    let (code, gas) = env
+        .memory
+        .read_bytes(code_ptr, code_len as _)
+        .map_err(|e| TxRuntimeError::MemoryError(Box::new(e)))?;
+    tx_add_gas(env, gas)?;
+
+    let (salt, gas) = env
+        .memory
+        .read_bytes(salt_ptr, salt_len as _)
+        .map_err(|e| TxRuntimeError::MemoryError(Box::new(e)))?;
+    tx_add_gas(env, gas)?;
+
+    let addr = creator.derive_sub(&salt);
+    tracing::debug!("tx_init_account_at {} for creator {}", addr, creator);
+    let vp_key = Key::validity_predicate(&addr);
+    if has_key_in_tx(env, &vp_key)? {
+        return Ok(HostEnvResult::Fail.to_i64());
+    }
+    check_key_mutability(env, &vp_key)?;
+
+    tx_add_gas(env, code.len() as u64 * WASM_VALIDATION_GAS_PER_BYTE)?;
+    validate_untrusted_wasm(&code)
+        .map_err(TxRuntimeError::InitAccountInvalidVpWasm)?;
+    #[cfg(feature = "wasm-runtime")]
+    {
+        let vp_wasm_cache = unsafe { env.ctx.vp_wasm_cache.get() };
+        vp_wasm_cache.pre_compile(&code);
+    }
+
+    let verifiers = unsafe { env.ctx.verifiers.get() };
+    verifiers.insert(creator);
+    let write_log = unsafe { env.ctx.write_log.get() };
+    let gas = write_log.init_account_at(&addr, code);
+    tx_add_gas(env, gas)?;
+    Ok(HostEnvResult::Success.to_i64())
+}
+
+/// Initialize a new account with a validity predicate template function
+/// exposed to the wasm VM Tx environment. The template's code is resolved by
+/// the given Borsh encoded code hash from the storage, instead of being
+/// passed by the transaction. Returns `1` and writes the new address into
+/// memory at the `result_ptr` if the account has been initialized or `-1` if
+/// the hash is not in the `vp_whitelist` parameter or there's no template
+/// with this hash.
+pub fn tx_init_account_from_template<MEM, DB, H, CA>(
+    env: &TxEnv<MEM, DB, H, CA>,
+    hash_ptr: u64,
+    hash_len: u64,
+    result_ptr: u64,
+) -> TxResult<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    CA: WasmCacheAccess,
+{
+    let (hash, gas) = env
+        .memory
+        .read_bytes(hash_ptr, hash_len as _)
+        .map_err(|e| TxRuntimeError::MemoryError(Box::new(e)))?;
+    tx_add_gas(env, gas)?;
+    let hash = Hash::try_from_slice(&hash[..])
+        .map_err(TxRuntimeError::EncodingError)?;
+
+    let storage = unsafe { env.ctx.storage.get() };
+    let (allowed, gas) = storage
+        .is_allowed_vp_code(&hash)
+        .map_err(TxRuntimeError::StorageError)?;
+    tx_add_gas(env, gas)?;
+    if !allowed {
+        return Ok(HostEnvResult::Fail.to_i64());
+    }
+    let (code, gas) = storage
+        .read(&parameters::storage::get_vp_template_key(&hash))
+        .map_err(TxRuntimeError::StorageError)?;
+    tx_add_gas(env, gas)?;
+    let code = match code {
+        Some(code) if Hash::sha256(&code) == hash => code,
+        _ => return Ok(HostEnvResult::Fail.to_i64()),
+    };
+
+    tx_add_gas(env, code.len() as u64 * WASM_VALIDATION_GAS_PER_BYTE)?;
+    validate_untrusted_wasm(&code)
+        .map_err(TxRuntimeError::InitAccountInvalidVpWasm)?;
+    #[cfg(feature = "wasm-runtime")]
+    {
+        let vp_wasm_cache = unsafe { env.ctx.vp_wasm_cache.get() };
+        vp_wasm_cache.pre_compile(&code);
+    }
+
+    tracing::debug!("tx_init_account_from_template {}", hash);
+
+    let write_log = unsafe { env.ctx.write_log.get() };
+    let (addr, gas) = write_log.init_account(&storage.address_gen, code);
+    let addr_bytes =
+        addr.try_to_vec().map_err(TxRuntimeError::EncodingError)?;
+    tx_add_gas(env, gas)?;
+    let gas = env
+        .memory
+        .write_bytes(result_ptr, addr_bytes)
+        .map_err(|e| TxRuntimeError::MemoryError(Box::new(e)))?;
+    tx_add_gas(env, gas)?;
+    Ok(HostEnvResult::Success.to_i64())
+}
+
 /// Getting the chain ID function exposed to the wasm VM Tx environment.
 pub fn tx_get_chain_id<MEM, DB, H, CA>(
     env: &TxEnv<MEM, DB, H, CA>,
@@ -1478,6 +2210,30 @@ where
     tx_add_gas(env, gas)
 }
 
+/// Getting the block randomness function exposed to the wasm VM Tx
+/// environment. The randomness is derived from the hash and height of the
+/// block to which the current transaction is being applied, see
+/// [`Storage::get_block_randomness`].
+pub fn tx_get_block_randomness<MEM, DB, H, CA>(
+    env: &TxEnv<MEM, DB, H, CA>,
+    result_ptr: u64,
+) -> TxResult<()>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    CA: WasmCacheAccess,
+{
+    let storage = unsafe { env.ctx.storage.get() };
+    let (randomness, gas) = storage.get_block_randomness();
+    tx_add_gas(env, gas)?;
+    let gas = env
+        .memory
+        .write_bytes(result_ptr, randomness.as_slice())
+        .map_err(|e| TxRuntimeError::MemoryError(Box::new(e)))?;
+    tx_add_gas(env, gas)
+}
+
 /// Getting the block epoch function exposed to the wasm VM Tx
 /// environment. The epoch is that of the block to which the current
 /// transaction is being applied.
@@ -1496,6 +2252,42 @@ where
     Ok(epoch.0)
 }
 
+/// Getting the gas cost per byte of an operation function exposed to the wasm
+/// VM Tx environment.
+///
+/// Returns `-1` when the operation is unknown.
+pub fn tx_gas_cost<MEM, DB, H, CA>(
+    env: &TxEnv<MEM, DB, H, CA>,
+    op: u64,
+) -> TxResult<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    CA: WasmCacheAccess,
+{
+    tx_add_gas(env, gas::MIN_STORAGE_GAS)?;
+    Ok(match gas::GasOp::from_u64(op) {
+        Some(op) => gas::as_i64(op.cost()),
+        None => HostEnvResult::Fail.to_i64(),
+    })
+}
+
+/// Getting the execution context function exposed to the wasm VM Tx
+/// environment. Always returns [`ExecContext::Tx`].
+pub fn tx_exec_context<MEM, DB, H, CA>(
+    env: &TxEnv<MEM, DB, H, CA>,
+) -> TxResult<u64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    CA: WasmCacheAccess,
+{
+    tx_add_gas(env, gas::MIN_STORAGE_GAS)?;
+    Ok(ExecContext::Tx.to_u64())
+}
+
 /// Getting the chain ID function exposed to the wasm VM VP environment.
 pub fn vp_get_chain_id<MEM, DB, H, EVAL, CA>(
     env: &VpEnv<MEM, DB, H, EVAL, CA>,
@@ -1618,6 +2410,38 @@ where
     vp_env::add_gas(gas_meter, gas)
 }
 
+/// Getting the merkle root of the previous block function exposed to the wasm
+/// VM VP environment. The previous block is the one before the block to which
+/// the current transaction is being applied.
+///
+/// Returns `1` and writes the root to the `result_ptr` when the previous block
+/// exists, `-1` otherwise.
+pub fn vp_get_prev_block_root<MEM, DB, H, EVAL, CA>(
+    env: &VpEnv<MEM, DB, H, EVAL, CA>,
+    result_ptr: u64,
+) -> vp_env::Result<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    EVAL: VpEvaluator,
+    CA: WasmCacheAccess,
+{
+    let gas_meter = unsafe { env.ctx.gas_meter.get() };
+    let storage = unsafe { env.ctx.storage.get() };
+    match vp_env::get_prev_block_root(gas_meter, storage)? {
+        Some(root) => {
+            let gas = env
+                .memory
+                .write_bytes(result_ptr, root.0)
+                .map_err(|e| vp_env::RuntimeError::MemoryError(Box::new(e)))?;
+            vp_env::add_gas(gas_meter, gas)?;
+            Ok(HostEnvResult::Success.to_i64())
+        }
+        None => Ok(HostEnvResult::Fail.to_i64()),
+    }
+}
+
 /// Getting the block epoch function exposed to the wasm VM VP
 /// environment. The epoch is that of the block to which the current
 /// transaction is being applied.
@@ -1637,6 +2461,99 @@ where
     Ok(epoch.0)
 }
 
+/// Getting the gas cost per byte of an operation function exposed to the wasm
+/// VM VP environment.
+///
+/// Returns `-1` when the operation is unknown.
+pub fn vp_gas_cost<MEM, DB, H, EVAL, CA>(
+    env: &VpEnv<MEM, DB, H, EVAL, CA>,
+    op: u64,
+) -> vp_env::Result<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    EVAL: VpEvaluator,
+    CA: WasmCacheAccess,
+{
+    let gas_meter = unsafe { env.ctx.gas_meter.get() };
+    vp_env::add_gas(gas_meter, gas::MIN_STORAGE_GAS)?;
+    Ok(match gas::GasOp::from_u64(op) {
+        Some(op) => gas::as_i64(op.cost()),
+        None => HostEnvResult::Fail.to_i64(),
+    })
+}
+
+/// Getting the gas limit of the transaction being validated function exposed
+/// to the wasm VM VP environment, as enforced by the VP's gas meter (see
+/// [`VpGasMeter::tx_gas_limit`]). The budget is shared by the transaction and
+/// all the VPs triggered by it.
+pub fn vp_tx_gas_limit<MEM, DB, H, EVAL, CA>(
+    env: &VpEnv<MEM, DB, H, EVAL, CA>,
+) -> vp_env::Result<u64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    EVAL: VpEvaluator,
+    CA: WasmCacheAccess,
+{
+    let gas_meter = unsafe { env.ctx.gas_meter.get() };
+    vp_env::add_gas(gas_meter, gas::MIN_STORAGE_GAS)?;
+    Ok(gas_meter.tx_gas_limit())
+}
+
+/// Getting the execution context function exposed to the wasm VM VP
+/// environment. Always returns [`ExecContext::Vp`].
+pub fn vp_exec_context<MEM, DB, H, EVAL, CA>(
+    env: &VpEnv<MEM, DB, H, EVAL, CA>,
+) -> vp_env::Result<u64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    EVAL: VpEvaluator,
+    CA: WasmCacheAccess,
+{
+    let gas_meter = unsafe { env.ctx.gas_meter.get() };
+    vp_env::add_gas(gas_meter, gas::MIN_STORAGE_GAS)?;
+    Ok(ExecContext::Vp.to_u64())
+}
+
+/// Getting all the storage keys changed by the current transaction function
+/// exposed to the wasm VM VP environment. Unlike the `keys_changed` input of a
+/// VP, the keys are not limited to the ones relevant to the VP's account.
+///
+/// Returns the length of the Borsh encoded keys, which are placed in the result
+/// buffer.
+pub fn vp_get_changed_keys<MEM, DB, H, EVAL, CA>(
+    env: &VpEnv<MEM, DB, H, EVAL, CA>,
+) -> vp_env::Result<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    EVAL: VpEvaluator,
+    CA: WasmCacheAccess,
+{
+    let gas_meter = unsafe { env.ctx.gas_meter.get() };
+    let write_log = unsafe { env.ctx.write_log.get() };
+    let keys: Vec<String> = vp_env::get_changed_keys(gas_meter, write_log)?
+        .iter()
+        .map(|key| key.to_string())
+        .collect();
+    let keys = keys
+        .try_to_vec()
+        .map_err(vp_env::RuntimeError::EncodingError)?;
+    let len: i64 = keys
+        .len()
+        .try_into()
+        .map_err(vp_env::RuntimeError::NumConversionError)?;
+    let result_buffer = unsafe { env.ctx.result_buffer.get() };
+    result_buffer.replace(keys);
+    Ok(len)
+}
+
 /// Verify a transaction signature.
 pub fn vp_verify_tx_signature<MEM, DB, H, EVAL, CA>(
     env: &VpEnv<MEM, DB, H, EVAL, CA>,
@@ -1645,6 +2562,106 @@ pub fn vp_verify_tx_signature<MEM, DB, H, EVAL, CA>(
     sig_ptr: u64,
     sig_len: u64,
 ) -> vp_env::Result<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    EVAL: VpEvaluator,
+    CA: WasmCacheAccess,
+{
+    let (pk, sig) = read_pk_and_sig(env, pk_ptr, pk_len, sig_ptr, sig_len)?;
+    let gas_meter = unsafe { env.ctx.gas_meter.get() };
+    vp_env::add_gas(gas_meter, VERIFY_TX_SIG_GAS_COST)?;
+    let tx = unsafe { env.ctx.tx.get() };
+    Ok(HostEnvResult::from(tx.verify_sig(&pk, &sig).is_ok()).to_i64())
+}
+
+/// Verify a transaction signature function exposed to the wasm VM VP
+/// environment, like [`vp_verify_tx_signature`], which also logs the reason of
+/// a failed verification at the `tracing::Level::Warn`. A transaction with
+/// missing or malformed data fails the verification (see
+/// [`Tx::try_verify_sig`]) instead of panicking.
+pub fn vp_verify_tx_signature_logged<MEM, DB, H, EVAL, CA>(
+    env: &VpEnv<MEM, DB, H, EVAL, CA>,
+    pk_ptr: u64,
+    pk_len: u64,
+    sig_ptr: u64,
+    sig_len: u64,
+) -> vp_env::Result<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    EVAL: VpEvaluator,
+    CA: WasmCacheAccess,
+{
+    let (pk, sig) = read_pk_and_sig(env, pk_ptr, pk_len, sig_ptr, sig_len)?;
+    let gas_meter = unsafe { env.ctx.gas_meter.get() };
+    vp_env::add_gas(gas_meter, VERIFY_TX_SIG_GAS_COST)?;
+    let tx = unsafe { env.ctx.tx.get() };
+    Ok(match tx.try_verify_sig(&pk, &sig) {
+        Ok(()) => HostEnvResult::Success.to_i64(),
+        Err(
+            err @ (VerifySigError::MissingData
+            | VerifySigError::MalformedSignedData(_)),
+        ) => {
+            tracing::warn!(
+                "The tx signature with the public key {} cannot be verified: \
+                 {}",
+                pk,
+                err
+            );
+            HostEnvResult::Fail.to_i64()
+        }
+        Err(err) => {
+            tracing::warn!(
+                "The tx signature with the public key {} is invalid: {}",
+                pk,
+                err
+            );
+            HostEnvResult::Fail.to_i64()
+        }
+    })
+}
+
+/// Verify a transaction signature in constant time function exposed to the
+/// wasm VM VP environment. Unlike [`vp_verify_tx_signature`], the signature
+/// is always checked, even when the transaction's data is missing or
+/// malformed, and the results are combined without an early return (see
+/// [`Tx::verify_sig_ct`]). The gas cost is the same for valid and invalid
+/// signatures.
+///
+/// [`Tx::verify_sig_ct`]: crate::proto::Tx::verify_sig_ct
+pub fn vp_verify_tx_signature_ct<MEM, DB, H, EVAL, CA>(
+    env: &VpEnv<MEM, DB, H, EVAL, CA>,
+    pk_ptr: u64,
+    pk_len: u64,
+    sig_ptr: u64,
+    sig_len: u64,
+) -> vp_env::Result<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    EVAL: VpEvaluator,
+    CA: WasmCacheAccess,
+{
+    let (pk, sig) = read_pk_and_sig(env, pk_ptr, pk_len, sig_ptr, sig_len)?;
+    let gas_meter = unsafe { env.ctx.gas_meter.get() };
+    vp_env::add_gas(gas_meter, VERIFY_TX_SIG_GAS_COST)?;
+    let tx = unsafe { env.ctx.tx.get() };
+    Ok(HostEnvResult::from(tx.verify_sig_ct(&pk, &sig)).to_i64())
+}
+
+/// Read the Borsh encoded public key and signature of the transaction
+/// signature verification functions from the VP's memory.
+fn read_pk_and_sig<MEM, DB, H, EVAL, CA>(
+    env: &VpEnv<MEM, DB, H, EVAL, CA>,
+    pk_ptr: u64,
+    pk_len: u64,
+    sig_ptr: u64,
+    sig_len: u64,
+) -> vp_env::Result<(common::PublicKey, common::Signature)>
 where
     MEM: VmMemory,
     DB: storage::DB + for<'iter> storage::DBIter<'iter>,
@@ -1669,9 +2686,54 @@ where
     let sig: common::Signature = BorshDeserialize::try_from_slice(&sig)
         .map_err(vp_env::RuntimeError::EncodingError)?;
 
-    vp_env::add_gas(gas_meter, VERIFY_TX_SIG_GAS_COST)?;
+    Ok((pk, sig))
+}
+
+/// Verify multiple transaction signatures function exposed to the wasm VM VP
+/// environment. The given pairs of public keys and signatures are Borsh
+/// encoded. Each of the signatures is verified over the current transaction
+/// against its public key. A public key that has already been counted with a
+/// valid signature is skipped, so repeating a pair cannot inflate the count.
+/// A transaction without valid signed data has no valid signatures.
+///
+/// Returns the number of distinct public keys with a valid signature.
+pub fn vp_verify_tx_signatures<MEM, DB, H, EVAL, CA>(
+    env: &VpEnv<MEM, DB, H, EVAL, CA>,
+    sigs_ptr: u64,
+    sigs_len: u64,
+) -> vp_env::Result<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    EVAL: VpEvaluator,
+    CA: WasmCacheAccess,
+{
+    let (pairs, gas) = env
+        .memory
+        .read_bytes(sigs_ptr, sigs_len as _)
+        .map_err(|e| vp_env::RuntimeError::MemoryError(Box::new(e)))?;
+    let gas_meter = unsafe { env.ctx.gas_meter.get() };
+    vp_env::add_gas(gas_meter, gas)?;
+    let pairs: Vec<(common::PublicKey, common::Signature)> =
+        BorshDeserialize::try_from_slice(&pairs)
+            .map_err(vp_env::RuntimeError::EncodingError)?;
+
     let tx = unsafe { env.ctx.tx.get() };
-    Ok(HostEnvResult::from(tx.verify_sig(&pk, &sig).is_ok()).to_i64())
+    let mut signers: BTreeSet<&common::PublicKey> = BTreeSet::new();
+    for (pk, sig) in &pairs {
+        if signers.contains(pk) {
+            continue;
+        }
+        vp_env::add_gas(gas_meter, VERIFY_TX_SIG_GAS_COST)?;
+        if tx.try_verify_sig(pk, sig).is_ok() {
+            signers.insert(pk);
+        }
+    }
+    signers
+        .len()
+        .try_into()
+        .map_err(vp_env::RuntimeError::NumConversionError)
 }
 
 /// Log a string from exposed to the wasm VM Tx environment. The message will be
@@ -1696,6 +2758,113 @@ where
     Ok(())
 }
 
+/// Find the signer of the transaction function exposed to the wasm VM VP
+/// environment. The candidate signers' addresses are Borsh encoded and the
+/// signer is the first of them with a public key registered in the prior state
+/// that verifies the transaction's signature. If the transaction is not signed
+/// or none of the candidates' keys verifies its signature, returns `-1`.
+/// Otherwise, the signer's address is placed in the result buffer and the
+/// length of its encoding is returned.
+pub fn vp_get_tx_signer<MEM, DB, H, EVAL, CA>(
+    env: &VpEnv<MEM, DB, H, EVAL, CA>,
+    candidates_ptr: u64,
+    candidates_len: u64,
+) -> vp_env::Result<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    EVAL: VpEvaluator,
+    CA: WasmCacheAccess,
+{
+    let candidates = read_candidates(env, candidates_ptr, candidates_len)?;
+    Ok(match find_tx_signer(env, &candidates)? {
+        Some(signer) => {
+            tracing::debug!("vp_get_tx_signer found the signer {}", signer);
+            let signer = signer
+                .try_to_vec()
+                .map_err(vp_env::RuntimeError::EncodingError)?;
+            let len: i64 = signer
+                .len()
+                .try_into()
+                .map_err(vp_env::RuntimeError::NumConversionError)?;
+            let result_buffer = unsafe { env.ctx.result_buffer.get() };
+            result_buffer.replace(signer);
+            len
+        }
+        None => HostEnvResult::Fail.to_i64(),
+    })
+}
+
+/// Read the Borsh encoded addresses of the candidate signers of the
+/// transaction from the VP's memory.
+fn read_candidates<MEM, DB, H, EVAL, CA>(
+    env: &VpEnv<MEM, DB, H, EVAL, CA>,
+    candidates_ptr: u64,
+    candidates_len: u64,
+) -> vp_env::Result<Vec<Address>>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    EVAL: VpEvaluator,
+    CA: WasmCacheAccess,
+{
+    let (candidates, gas) = env
+        .memory
+        .read_bytes(candidates_ptr, candidates_len as _)
+        .map_err(|e| vp_env::RuntimeError::MemoryError(Box::new(e)))?;
+    let gas_meter = unsafe { env.ctx.gas_meter.get() };
+    vp_env::add_gas(gas_meter, gas)?;
+    BorshDeserialize::try_from_slice(&candidates)
+        .map_err(vp_env::RuntimeError::EncodingError)
+}
+
+/// Find the signer of the transaction among the given candidates, which is the
+/// first of them with a public key registered in the prior state that verifies
+/// the transaction's signature. Only the candidates' keys are read, so the
+/// cost is bounded by the number of candidates.
+fn find_tx_signer<MEM, DB, H, EVAL, CA>(
+    env: &VpEnv<MEM, DB, H, EVAL, CA>,
+    candidates: &[Address],
+) -> vp_env::Result<Option<Address>>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    EVAL: VpEvaluator,
+    CA: WasmCacheAccess,
+{
+    let tx = unsafe { env.ctx.tx.get() };
+    let signed = match tx
+        .data
+        .as_ref()
+        .and_then(|data| SignedTxData::try_from_slice(&data[..]).ok())
+    {
+        Some(signed) => signed,
+        None => return Ok(None),
+    };
+
+    let gas_meter = unsafe { env.ctx.gas_meter.get() };
+    let storage = unsafe { env.ctx.storage.get() };
+    let write_log = unsafe { env.ctx.write_log.get() };
+    for candidate in candidates {
+        let key = pk_key(candidate);
+        let value = vp_env::read_pre(gas_meter, storage, write_log, &key)?;
+        let pk = match value
+            .and_then(|value| common::PublicKey::try_from_slice(&value).ok())
+        {
+            Some(pk) => pk,
+            None => continue,
+        };
+        vp_env::add_gas(gas_meter, VERIFY_TX_SIG_GAS_COST)?;
+        if tx.try_verify_sig(&pk, &signed.sig).is_ok() {
+            return Ok(Some(candidate.clone()));
+        }
+    }
+    Ok(None)
+}
+
 /// Evaluate a validity predicate with the given input data.
 pub fn vp_eval<MEM, DB, H, EVAL, CA>(
     env: &VpEnv<'static, MEM, DB, H, EVAL, CA>,
@@ -1730,6 +2899,53 @@ where
         .to_i64())
 }
 
+/// Evaluate the current validity predicate of the given account with the
+/// given input data. The account's VP code is read from the prior state. If
+/// the account has no VP, the evaluation fails.
+pub fn vp_would_accept<MEM, DB, H, EVAL, CA>(
+    env: &VpEnv<'static, MEM, DB, H, EVAL, CA>,
+    addr_ptr: u64,
+    addr_len: u64,
+    input_data_ptr: u64,
+    input_data_len: u64,
+) -> vp_env::Result<i64>
+where
+    MEM: VmMemory,
+    DB: storage::DB + for<'iter> storage::DBIter<'iter>,
+    H: StorageHasher,
+    EVAL: VpEvaluator<Db = DB, H = H, Eval = EVAL, CA = CA>,
+    CA: WasmCacheAccess,
+{
+    let (addr, gas) = env
+        .memory
+        .read_bytes(addr_ptr, addr_len as _)
+        .map_err(|e| vp_env::RuntimeError::MemoryError(Box::new(e)))?;
+    let gas_meter = unsafe { env.ctx.gas_meter.get() };
+    vp_env::add_gas(gas_meter, gas)?;
+    let addr: Address = BorshDeserialize::try_from_slice(&addr)
+        .map_err(vp_env::RuntimeError::EncodingError)?;
+
+    let (input_data, gas) = env
+        .memory
+        .read_bytes(input_data_ptr, input_data_len as _)
+        .map_err(|e| vp_env::RuntimeError::MemoryError(Box::new(e)))?;
+    vp_env::add_gas(gas_meter, gas)?;
+
+    let key = Key::validity_predicate(&addr);
+    let storage = unsafe { env.ctx.storage.get() };
+    let write_log = unsafe { env.ctx.write_log.get() };
+    let vp_code = match vp_env::read_pre(gas_meter, storage, write_log, &key)? {
+        Some(vp_code) => vp_code,
+        None => return Ok(HostEnvResult::Fail.to_i64()),
+    };
+    tracing::debug!("vp_would_accept evaluating the VP of addr {}", addr);
+
+    let eval_runner = unsafe { env.ctx.eval_runner.get() };
+    Ok(eval_runner
+        .eval(env.ctx.clone(), vp_code, input_data)
+        .to_i64())
+}
+
 /// Log a string from exposed to the wasm VM VP environment. The message will be
 /// printed at the [`tracing::Level::INFO`]. This function is for development
 /// only.
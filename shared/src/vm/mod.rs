@@ -5,7 +5,7 @@ use std::ffi::c_void;
 use std::marker::PhantomData;
 use std::slice;
 
-use wasmparser::{Validator, WasmFeatures};
+use wasmparser::{ExternalKind, Parser, Payload, Validator, WasmFeatures};
 
 pub mod host_env;
 pub mod memory;
@@ -42,6 +42,8 @@ pub enum WasmValidationError {
          {UNTRUSTED_WASM_FEATURES:?}"
     )]
     ForbiddenWasmFeatures(wasmparser::BinaryReaderError),
+    #[error("Invalid validity predicate WASM, missing export: {0}")]
+    MissingExport(&'static str),
 }
 
 /// WASM Cache access level, used to limit dry-ran transactions to read-only
@@ -239,3 +241,32 @@ pub fn validate_untrusted_wasm(
         .validate_all(wasm_code.as_ref())
         .map_err(WasmValidationError::ForbiddenWasmFeatures)
 }
+
+/// The name of the function that validity predicate's wasm must export
+pub const VP_ENTRYPOINT: &str = "_validate_tx";
+
+/// Validate an untrusted validity predicate wasm code. On top of the checks
+/// of [`validate_untrusted_wasm`], the code must export the
+/// [`VP_ENTRYPOINT`] function.
+pub fn validate_untrusted_vp_wasm(
+    wasm_code: impl AsRef<[u8]>,
+) -> Result<(), WasmValidationError> {
+    let wasm_code = wasm_code.as_ref();
+    validate_untrusted_wasm(wasm_code)?;
+    for payload in Parser::new(0).parse_all(wasm_code) {
+        if let Payload::ExportSection(exports) =
+            payload.map_err(WasmValidationError::ForbiddenWasmFeatures)?
+        {
+            for export in exports {
+                let export = export
+                    .map_err(WasmValidationError::ForbiddenWasmFeatures)?;
+                if export.field == VP_ENTRYPOINT
+                    && export.kind == ExternalKind::Function
+                {
+                    return Ok(());
+                }
+            }
+        }
+    }
+    Err(WasmValidationError::MissingExport(VP_ENTRYPOINT))
+}
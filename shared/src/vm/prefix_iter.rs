@@ -4,9 +4,16 @@
 use std::collections::HashMap;
 
 use crate::ledger::storage;
+use crate::types::storage::{BlockHeight, Key};
 
 /// A temporary iterators storage, used during a wasm run after which it's
 /// dropped. Each iterator is assigned a [`PrefixIteratorId`].
+///
+/// An iterator reflects a snapshot of the storage taken at its creation, i.e.
+/// the state committed at its snapshot height. Keys written after the
+/// iterator has been created are not observed by it, so a write or a delete
+/// of a key under its prefix invalidates the iterator (see
+/// [`PrefixIterators::invalidate`]).
 #[derive(Debug)]
 pub struct PrefixIterators<'iter, DB>
 where
@@ -14,20 +21,48 @@ where
 {
     index: PrefixIteratorId,
     iterators: HashMap<PrefixIteratorId, DB::PrefixIter>,
+    snapshot_heights: HashMap<PrefixIteratorId, BlockHeight>,
+    prefixes: HashMap<PrefixIteratorId, Key>,
 }
 
 impl<'iter, DB> PrefixIterators<'iter, DB>
 where
     DB: storage::DBIter<'iter>,
 {
-    /// Insert a new prefix iterator to the temporary storage.
-    pub fn insert(&mut self, iter: DB::PrefixIter) -> PrefixIteratorId {
+    /// Insert a new prefix iterator over the given `prefix` to the temporary
+    /// storage. The `snapshot_height` is the height of the committed block
+    /// whose state the iterator reflects.
+    pub fn insert(
+        &mut self,
+        iter: DB::PrefixIter,
+        prefix: Key,
+        snapshot_height: BlockHeight,
+    ) -> PrefixIteratorId {
         let id = self.index;
         self.iterators.insert(id, iter);
+        self.snapshot_heights.insert(id, snapshot_height);
+        self.prefixes.insert(id, prefix);
         self.index = id.next_id();
         id
     }
 
+    /// Drop the iterators whose prefix contains the given key, which has been
+    /// written or deleted. The IDs of the dropped iterators are no longer
+    /// known, i.e. [`PrefixIterators::contains`] returns `false` for them.
+    pub fn invalidate(&mut self, key: &Key) {
+        let invalid: Vec<PrefixIteratorId> = self
+            .prefixes
+            .iter()
+            .filter(|(_, prefix)| key.segments.starts_with(&prefix.segments))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in invalid {
+            self.iterators.remove(&id);
+            self.snapshot_heights.remove(&id);
+            self.prefixes.remove(&id);
+        }
+    }
+
     /// Get the next item in the given prefix iterator.
     pub fn next(
         &mut self,
@@ -36,6 +71,16 @@ where
         self.iterators.get_mut(&id).and_then(|i| i.next())
     }
 
+    /// Check if a prefix iterator with the given ID exists.
+    pub fn contains(&self, id: PrefixIteratorId) -> bool {
+        self.iterators.contains_key(&id)
+    }
+
+    /// Get the snapshot height of a prefix iterator with the given ID.
+    pub fn snapshot_height(&self, id: PrefixIteratorId) -> Option<BlockHeight> {
+        self.snapshot_heights.get(&id).copied()
+    }
+
     /// Get prefix iterator with the given ID.
     pub fn get_mut(
         &mut self,
@@ -53,6 +98,8 @@ where
         Self {
             index: PrefixIteratorId::default(),
             iterators: HashMap::default(),
+            snapshot_heights: HashMap::default(),
+            prefixes: HashMap::default(),
         }
     }
 }
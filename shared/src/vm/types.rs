@@ -40,3 +40,45 @@ pub struct KeyVal {
     /// The value as arbitrary bytes
     pub val: Vec<u8>,
 }
+
+impl KeyVal {
+    /// Create a new key-value pair
+    pub fn new(key: String, val: Vec<u8>) -> Self {
+        Self { key, val }
+    }
+
+    /// Get the storage key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the value as arbitrary bytes
+    pub fn val(&self) -> &[u8] {
+        &self.val
+    }
+
+    /// Try to decode the value with Borsh. Returns `None` if the value cannot
+    /// be decoded into the given type.
+    pub fn decode_val<T: BorshDeserialize>(&self) -> Option<T> {
+        T::try_from_slice(&self.val[..]).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_val_roundtrip() {
+        let value = "value".to_owned();
+        let key_val =
+            KeyVal::new("key".to_owned(), value.try_to_vec().unwrap());
+
+        let bytes = key_val.try_to_vec().unwrap();
+        let decoded = KeyVal::try_from_slice(&bytes[..]).unwrap();
+        assert_eq!(decoded.key(), "key");
+        assert_eq!(decoded.val(), key_val.val());
+        assert_eq!(decoded.decode_val::<String>(), Some(value));
+        assert_eq!(decoded.decode_val::<u64>(), None);
+    }
+}
@@ -65,18 +65,31 @@ where
             "anoma_tx_has_key" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_has_key),
             "anoma_tx_write" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_write),
             "anoma_tx_write_temp" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_write_temp),
+            "anoma_tx_write_immutable" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_write_immutable),
+            "anoma_tx_write_with_ttl" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_write_with_ttl),
             "anoma_tx_delete" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_delete),
             "anoma_tx_iter_prefix" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_iter_prefix),
             "anoma_tx_iter_next" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_iter_next),
+            "anoma_tx_iter_is_valid" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_iter_is_valid),
+            "anoma_tx_iter_snapshot_height" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_iter_snapshot_height),
             "anoma_tx_insert_verifier" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_insert_verifier),
             "anoma_tx_update_validity_predicate" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_update_validity_predicate),
+            "anoma_tx_update_validity_predicate_validated" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_update_validity_predicate_validated),
             "anoma_tx_init_account" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_init_account),
+            "anoma_tx_init_account_at" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_init_account_at),
+            "anoma_tx_init_account_from_template" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_init_account_from_template),
             "anoma_tx_emit_ibc_event" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_emit_ibc_event),
+            "anoma_tx_emit_event" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_emit_event),
+            "anoma_tx_schedule" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_schedule),
+            "anoma_tx_cancel_scheduled" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_cancel_scheduled),
             "anoma_tx_get_chain_id" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_get_chain_id),
             "anoma_tx_get_block_height" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_get_block_height),
             "anoma_tx_get_block_time" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_get_block_time),
             "anoma_tx_get_block_hash" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_get_block_hash),
+            "anoma_tx_get_block_randomness" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_get_block_randomness),
             "anoma_tx_get_block_epoch" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_get_block_epoch),
+            "anoma_tx_gas_cost" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_gas_cost),
+            "anoma_exec_context" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_exec_context),
             "anoma_tx_log_string" => Function::new_native_with_env(wasm_store, env.clone(), host_env::tx_log_string),
         },
     }
@@ -102,6 +115,7 @@ where
             "gas" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_charge_gas),
             "anoma_vp_read_pre" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_read_pre),
             "anoma_vp_read_post" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_read_post),
+            "anoma_vp_read_post_range" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_read_post_range),
             "anoma_vp_read_temp" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_read_temp),
             "anoma_vp_result_buffer" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_result_buffer),
             "anoma_vp_has_key_pre" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_has_key_pre),
@@ -113,9 +127,19 @@ where
             "anoma_vp_get_block_height" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_get_block_height),
             "anoma_vp_get_block_hash" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_get_block_hash),
             "anoma_vp_get_tx_code_hash" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_get_tx_code_hash),
+            "anoma_vp_get_prev_block_root" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_get_prev_block_root),
             "anoma_vp_get_block_epoch" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_get_block_epoch),
+            "anoma_vp_gas_cost" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_gas_cost),
+            "anoma_vp_tx_gas_limit" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_tx_gas_limit),
+            "anoma_exec_context" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_exec_context),
+            "anoma_vp_get_changed_keys" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_get_changed_keys),
             "anoma_vp_verify_tx_signature" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_verify_tx_signature),
+            "anoma_vp_verify_tx_signature_logged" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_verify_tx_signature_logged),
+            "anoma_vp_verify_tx_signature_ct" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_verify_tx_signature_ct),
+            "anoma_vp_verify_tx_signatures" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_verify_tx_signatures),
+            "anoma_vp_get_tx_signer" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_get_tx_signer),
             "anoma_vp_eval" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_eval),
+            "anoma_vp_would_accept" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_would_accept),
             "anoma_vp_log_string" => Function::new_native_with_env(wasm_store, env.clone(), host_env::vp_log_string),
         },
     }
@@ -24,10 +24,10 @@ use crate::vm::wasm::host_env::{tx_imports, vp_imports};
 use crate::vm::wasm::{memory, VpCache};
 use crate::vm::{
     validate_untrusted_wasm, WasmCacheAccess, WasmValidationError,
+    VP_ENTRYPOINT,
 };
 
 const TX_ENTRYPOINT: &str = "_apply_tx";
-const VP_ENTRYPOINT: &str = "_validate_tx";
 const WASM_STACK_LIMIT: u32 = u16::MAX as u32;
 
 #[allow(missing_docs)]
@@ -25,12 +25,15 @@ mod tests {
     use anoma::ledger::ibc::vp::{
         get_dummy_header as tm_dummy_header, Error as IbcError,
     };
+    use anoma::ledger::parameters;
     use anoma::proto::{SignedTxData, Tx};
     use anoma::tendermint_proto::Protobuf;
+    use anoma::types::event::TxEvent;
     use anoma::types::key::*;
     use anoma::types::storage::{self, BlockHash, BlockHeight, Key, KeySeg};
     use anoma::types::time::DateTimeUtc;
     use anoma::types::token::{self, Amount};
+    use anoma::types::transaction::MAX_SCHEDULED_TXS_PER_HEIGHT;
     use anoma::types::{address, key};
     use anoma_vm_env::tx_prelude::{
         BorshDeserialize, BorshSerialize, KeyValIterator,
@@ -47,6 +50,7 @@ mod tests {
     // paths to the WASMs used for tests
     const VP_ALWAYS_TRUE_WASM: &str = "../wasm_for_tests/vp_always_true.wasm";
     const VP_ALWAYS_FALSE_WASM: &str = "../wasm_for_tests/vp_always_false.wasm";
+    const TX_NO_OP_WASM: &str = "../wasm_for_tests/tx_no_op.wasm";
 
     #[test]
     fn test_tx_read_write() {
@@ -172,6 +176,524 @@ mod tests {
         itertools::assert_equal(iter.sorted(), expected.sorted());
     }
 
+    /// Test that a prefix iterator doesn't observe keys written after its
+    /// creation.
+    #[test]
+    fn test_tx_iter_prefix_snapshot() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let prefix = Key::parse("prefix").unwrap();
+        let height = BlockHeight(3);
+        tx_host_env::with(|env| {
+            for i in 0..3_i32 {
+                let key = prefix.join(&Key::parse(i.to_string()).unwrap());
+                let value = i.try_to_vec().unwrap();
+                env.storage.write(&key, value).unwrap();
+            }
+            env.storage.commit().unwrap();
+            env.storage.last_height = height;
+        });
+
+        let iter: KeyValIterator<i32> =
+            tx_host_env::iter_prefix(prefix.to_string());
+        assert_eq!(iter.snapshot_height(), Some(height));
+
+        // Write new matching keys after the iterator has been created, both
+        // in the tx and directly into the committed storage
+        let new_key = prefix.join(&Key::parse("3").unwrap());
+        tx_host_env::write(new_key.to_string(), 3_i32);
+        tx_host_env::with(|env| {
+            let key = prefix.join(&Key::parse("4").unwrap());
+            let value = 4_i32.try_to_vec().unwrap();
+            env.storage.write(&key, value).unwrap();
+            env.storage.commit().unwrap();
+        });
+
+        let expected = (0..3).map(|i| (format!("{}/{}", prefix, i), i));
+        itertools::assert_equal(iter.sorted(), expected.sorted());
+    }
+
+    #[test]
+    fn test_tx_write_with_ttl() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let addr = address::testing::established_address_1();
+        tx_host_env::with(|env| env.spawn_accounts([&addr]));
+        let key = storage::Key::from(addr.to_db_key())
+            .push(&"session".to_owned())
+            .unwrap();
+        let ttl_key = key.ttl().unwrap();
+
+        tx_host_env::write_with_ttl(key.to_string(), "secret".to_owned(), 2);
+        tx_host_env::commit_tx_and_block();
+
+        let expiry: Option<BlockHeight> =
+            tx_host_env::read(ttl_key.to_string());
+        assert_eq!(expiry, Some(BlockHeight(2)));
+        let index_key = key.ttl_index(BlockHeight(2)).to_string();
+        assert!(tx_host_env::has_key(&index_key));
+
+        // The value is still present before the expiry height
+        tx_host_env::with(|env| {
+            env.storage
+                .begin_block(BlockHash::default(), BlockHeight(1))
+                .unwrap();
+            env.storage.prune_expired().unwrap();
+        });
+        let value: Option<String> = tx_host_env::read(key.to_string());
+        assert_eq!(value, Some("secret".to_owned()));
+
+        // The value and its expiry height are pruned at the expiry height
+        tx_host_env::with(|env| {
+            env.storage
+                .begin_block(BlockHash::default(), BlockHeight(2))
+                .unwrap();
+            env.storage.prune_expired().unwrap();
+        });
+        let value: Option<String> = tx_host_env::read(key.to_string());
+        assert_eq!(value, None);
+        let expiry: Option<BlockHeight> =
+            tx_host_env::read(ttl_key.to_string());
+        assert_eq!(expiry, None);
+        assert!(!tx_host_env::has_key(&index_key));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tx_write_ttl_expiry() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let addr = address::testing::established_address_1();
+        tx_host_env::with(|env| env.spawn_accounts([&addr]));
+        let key = storage::Key::from(addr.to_db_key())
+            .push(&"session".to_owned())
+            .unwrap();
+
+        // The expiry heights can only be written by the host
+        tx_host_env::write_bytes(key.ttl().unwrap().to_string(), vec![0xff]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tx_delete_ttl_index_entry() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let addr = address::testing::established_address_1();
+        tx_host_env::with(|env| env.spawn_accounts([&addr]));
+        let key = storage::Key::from(addr.to_db_key())
+            .push(&"session".to_owned())
+            .unwrap();
+        tx_host_env::write_with_ttl(key.to_string(), "secret".to_owned(), 2);
+
+        // The index of expiring values can only be modified by the host
+        tx_host_env::delete(key.ttl_index(BlockHeight(2)).to_string());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tx_write_scheduled_tx() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let scheduler = address::testing::established_address_1();
+        tx_host_env::with(|env| env.spawn_accounts([&scheduler]));
+        let current = tx_host_env::get_block_height();
+        let at_height = BlockHeight(current.0 + 10);
+        let id = tx_host_env::schedule_tx(&scheduler, vec![], None, at_height);
+
+        // The scheduled txs can only be modified by the host
+        let key = Key::scheduled_tx(&scheduler, id);
+        tx_host_env::write_bytes(key.to_string(), vec![0xff]);
+    }
+
+    #[test]
+    fn test_tx_schedule_too_many_txs() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let scheduler = address::testing::established_address_1();
+        tx_host_env::with(|env| env.spawn_accounts([&scheduler]));
+        let current = tx_host_env::get_block_height();
+        let at_height = BlockHeight(current.0 + 10);
+        for _ in 0..MAX_SCHEDULED_TXS_PER_HEIGHT {
+            tx_host_env::schedule_tx(&scheduler, vec![], None, at_height);
+        }
+
+        // Canceling a tx makes space for another one
+        assert!(tx_host_env::cancel_scheduled(&scheduler, 0));
+        tx_host_env::schedule_tx(&scheduler, vec![], None, at_height);
+
+        // The height is full
+        let result = panic::catch_unwind(|| {
+            tx_host_env::schedule_tx(&scheduler, vec![], None, at_height)
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tx_delete_scheduled_tx_index_entry() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let scheduler = address::testing::established_address_1();
+        tx_host_env::with(|env| env.spawn_accounts([&scheduler]));
+        let current = tx_host_env::get_block_height();
+        let at_height = BlockHeight(current.0 + 10);
+        let id = tx_host_env::schedule_tx(&scheduler, vec![], None, at_height);
+
+        // The index of scheduled txs can only be modified by the host
+        let index_key = Key::scheduled_tx_index(at_height, &scheduler, id);
+        tx_host_env::delete(index_key.to_string());
+    }
+
+    #[test]
+    fn test_tx_write_immutable() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let addr = address::testing::established_address_1();
+        tx_host_env::with(|env| env.spawn_accounts([&addr]));
+        let key = Key::from(addr.to_db_key())
+            .push(&"code_hash".to_owned())
+            .unwrap()
+            .to_string();
+
+        // The first write succeeds
+        assert_eq!(tx_host_env::write_immutable(&key, 1_u64), Ok(()));
+        assert_eq!(tx_host_env::read(&key), Some(1_u64));
+
+        // The second write is rejected and the value is unchanged
+        assert_eq!(
+            tx_host_env::write_immutable(&key, 2_u64),
+            Err(tx_host_env::EnvError::Host(
+                tx_host_env::HostError::ImmutableKey
+            ))
+        );
+        assert_eq!(tx_host_env::read(&key), Some(1_u64));
+
+        // The key remains immutable after the block is committed
+        tx_host_env::commit_tx_and_block();
+        assert_eq!(
+            tx_host_env::write_immutable(&key, 2_u64),
+            Err(tx_host_env::EnvError::Host(
+                tx_host_env::HostError::ImmutableKey
+            ))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tx_write_to_immutable_key() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let addr = address::testing::established_address_1();
+        tx_host_env::with(|env| env.spawn_accounts([&addr]));
+        let key = Key::from(addr.to_db_key())
+            .push(&"code_hash".to_owned())
+            .unwrap()
+            .to_string();
+        tx_host_env::write_immutable(&key, 1_u64).unwrap();
+
+        // A regular write to an immutable key is rejected
+        tx_host_env::write(&key, 2_u64);
+    }
+
+    /// Make a key of the given length in the sub-space of the given address
+    fn key_of_len(addr: &address::Address, len: usize) -> String {
+        let prefix = format!("{}/", Key::from(addr.to_db_key()));
+        format!("{}{}", prefix, "a".repeat(len - prefix.len()))
+    }
+
+    #[test]
+    fn test_tx_write_checked() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let addr = address::testing::established_address_1();
+        tx_host_env::with(|env| env.spawn_accounts([&addr]));
+
+        // A key at the limit can be written
+        let key = key_of_len(&addr, storage::MAX_KEY_LEN);
+        assert_eq!(tx_host_env::write_checked(&key, 1_u64), Ok(()));
+        assert_eq!(tx_host_env::read(&key), Some(1_u64));
+        tx_host_env::write(&key, 2_u64);
+        assert_eq!(tx_host_env::read(&key), Some(2_u64));
+
+        // A key over the limit is rejected and not written
+        let key = key_of_len(&addr, storage::MAX_KEY_LEN + 1);
+        assert_eq!(
+            tx_host_env::write_checked(&key, 1_u64),
+            Err(tx_host_env::EnvError::Host(
+                tx_host_env::HostError::KeyTooLong {
+                    len: storage::MAX_KEY_LEN + 1,
+                    max: storage::MAX_KEY_LEN,
+                }
+            ))
+        );
+        assert!(!tx_host_env::has_key(&key));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tx_write_key_too_long() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let addr = address::testing::established_address_1();
+        tx_host_env::with(|env| env.spawn_accounts([&addr]));
+
+        // A write of a key over the limit is rejected by the host
+        let key = key_of_len(&addr, storage::MAX_KEY_LEN + 1);
+        tx_host_env::write(&key, 1_u64);
+    }
+
+    #[test]
+    fn test_tx_env_error() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        /// A value that always fails to be encoded
+        struct Unencodable;
+        impl BorshSerialize for Unencodable {
+            fn serialize<W: std::io::Write>(
+                &self,
+                _writer: &mut W,
+            ) -> std::io::Result<()> {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "unencodable",
+                ))
+            }
+        }
+
+        let key = "key";
+        assert_eq!(
+            tx_host_env::try_read::<u64>(key),
+            Err(tx_host_env::EnvError::NotFound)
+        );
+        assert_eq!(
+            tx_host_env::try_write(key, Unencodable),
+            Err(tx_host_env::EnvError::Encode)
+        );
+        assert!(!tx_host_env::has_key(key));
+
+        tx_host_env::write_bytes(key, [1_u8]);
+        assert_eq!(
+            tx_host_env::try_read::<u64>(key),
+            Err(tx_host_env::EnvError::Decode)
+        );
+
+        assert_eq!(tx_host_env::try_write(key, 1_u64), Ok(()));
+        assert_eq!(tx_host_env::try_read::<u64>(key), Ok(1));
+
+        // The host calls report their errors with the same type
+        let addr = address::testing::established_address_1();
+        tx_host_env::with(|env| env.spawn_accounts([&addr]));
+        let key = Key::from(addr.to_db_key())
+            .push(&"code_hash".to_owned())
+            .unwrap()
+            .to_string();
+        tx_host_env::write_immutable(&key, 1_u64).unwrap();
+        assert!(matches!(
+            tx_host_env::write_immutable(&key, 2_u64),
+            Err(tx_host_env::EnvError::Host(_))
+        ));
+    }
+
+    #[test]
+    fn test_tx_update() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let key = "key";
+        for _ in 0..5 {
+            tx_host_env::update(key, 0_u64, |counter| counter + 1);
+        }
+        let counter: Option<u64> = tx_host_env::read(key);
+        assert_eq!(counter, Some(5));
+
+        // The default is ignored when the key is present
+        tx_host_env::update(key, 100_u64, |counter| counter * 2);
+        let counter: Option<u64> = tx_host_env::read(key);
+        assert_eq!(counter, Some(10));
+    }
+
+    #[test]
+    fn test_tx_schedule_tx() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let scheduler = address::testing::established_address_1();
+        tx_host_env::with(|env| env.spawn_accounts([&scheduler]));
+        let current = tx_host_env::get_block_height();
+        let at_height = BlockHeight(current.0 + 10);
+        let code = vec![1, 2, 3];
+        let data = Some(vec![4, 5]);
+
+        let id = tx_host_env::schedule_tx(
+            &scheduler,
+            code.clone(),
+            data.clone(),
+            at_height,
+        );
+        let next_id =
+            tx_host_env::schedule_tx(&scheduler, vec![], None, at_height);
+        assert_ne!(id, next_id);
+
+        // The tx is recorded for the right height
+        let index_key = Key::scheduled_tx_index(at_height, &scheduler, id);
+        assert!(tx_host_env::has_key(index_key.to_string()));
+        tx_host_env::commit_tx_and_block();
+        let scheduled = tx_host_env::with(|env| {
+            env.storage.scheduled_txs(at_height).unwrap()
+        });
+        assert_eq!(scheduled.len(), 2);
+        let (key, tx) = &scheduled[0];
+        assert_eq!(key.is_scheduled_tx(), Some((&scheduler, id)));
+        assert_eq!(tx.scheduler, scheduler);
+        assert_eq!(tx.code, code);
+        assert_eq!(tx.data, data);
+        assert_eq!(tx.height, at_height);
+    }
+
+    #[test]
+    fn test_tx_cancel_scheduled() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let scheduler = address::testing::established_address_1();
+        tx_host_env::with(|env| env.spawn_accounts([&scheduler]));
+        let current = tx_host_env::get_block_height();
+        let at_height = BlockHeight(current.0 + 10);
+        let id = tx_host_env::schedule_tx(&scheduler, vec![], None, at_height);
+        let other_id =
+            tx_host_env::schedule_tx(&scheduler, vec![], None, at_height);
+        tx_host_env::commit_tx_and_block();
+
+        assert!(tx_host_env::cancel_scheduled(&scheduler, id));
+        // The tx is removed from the index too
+        let index_key = Key::scheduled_tx_index(at_height, &scheduler, id);
+        assert!(!tx_host_env::has_key(index_key.to_string()));
+        // The tx cannot be canceled again
+        assert!(!tx_host_env::cancel_scheduled(&scheduler, id));
+        // An unknown tx cannot be canceled
+        let other = address::testing::established_address_2();
+        assert!(!tx_host_env::cancel_scheduled(&other, other_id));
+
+        tx_host_env::commit_tx_and_block();
+        let scheduled = tx_host_env::with(|env| {
+            env.storage.scheduled_txs(at_height).unwrap()
+        });
+        assert_eq!(scheduled.len(), 1);
+        assert_eq!(
+            scheduled[0].0.is_scheduled_tx(),
+            Some((&scheduler, other_id))
+        );
+        // The canceled tx's index entry is deleted from the storage
+        let (has_index_key, count) = tx_host_env::with(|env| {
+            let (has_key, _gas) = env.storage.has_key(&index_key).unwrap();
+            let count_key = Key::scheduled_tx_count(at_height);
+            let (count, _gas) = env.storage.read(&count_key).unwrap();
+            (has_key, count)
+        });
+        assert!(!has_index_key);
+        assert_eq!(count, Some(1_u64.try_to_vec().unwrap()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tx_schedule_tx_in_the_past() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let scheduler = address::testing::established_address_1();
+        tx_host_env::with(|env| env.spawn_accounts([&scheduler]));
+        let current = tx_host_env::get_block_height();
+        tx_host_env::schedule_tx(&scheduler, vec![], None, current);
+    }
+
+    #[test]
+    fn test_tx_iter_validity() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        // An exhausted iterator is still valid
+        let mut iter: KeyValIterator<Vec<u8>> =
+            tx_host_env::iter_prefix("empty");
+        assert!(iter.next().is_none());
+        assert!(iter.is_valid());
+        assert_eq!(iter.last_error(), None);
+
+        // Writing outside of the prefix doesn't invalidate the iterator
+        tx_host_env::write_bytes("other/key", vec![1]);
+        assert!(iter.is_valid());
+
+        // A conflicting write under the prefix invalidates the iterator
+        tx_host_env::write_bytes("empty/key", vec![1]);
+        assert!(iter.next().is_none());
+        assert!(!iter.is_valid());
+        assert_eq!(
+            iter.last_error(),
+            Some(tx_host_env::EnvError::Host(
+                tx_host_env::HostError::InvalidIterator { iter_id: iter.0 }
+            ))
+        );
+
+        // And so does a delete
+        let mut iter: KeyValIterator<Vec<u8>> =
+            tx_host_env::iter_prefix("empty");
+        assert!(iter.is_valid());
+        tx_host_env::delete("empty/key");
+        assert!(iter.next().is_none());
+        assert_eq!(
+            iter.last_error(),
+            Some(tx_host_env::EnvError::Host(
+                tx_host_env::HostError::InvalidIterator { iter_id: iter.0 }
+            ))
+        );
+
+        // An iterator that is not known to the host is invalid
+        let iter_id = iter.0 + 1;
+        let mut iter: KeyValIterator<Vec<u8>> =
+            KeyValIterator(iter_id, std::marker::PhantomData);
+        assert!(iter.next().is_none());
+        assert!(!iter.is_valid());
+        assert_eq!(
+            iter.last_error(),
+            Some(tx_host_env::EnvError::Host(
+                tx_host_env::HostError::InvalidIterator { iter_id }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_tx_gas_cost() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        for op in [
+            tx_host_env::GasOp::Read,
+            tx_host_env::GasOp::Write,
+            tx_host_env::GasOp::Iter,
+            tx_host_env::GasOp::Hash,
+        ] {
+            let cost = tx_host_env::gas_cost(op);
+            assert!(cost > 0, "The gas cost of {:?} should be positive", op);
+            assert_eq!(
+                cost,
+                tx_host_env::gas_cost(op),
+                "The gas cost of {:?} should be consistent across calls",
+                op
+            );
+        }
+    }
+
     #[test]
     fn test_tx_insert_verifier() {
         // The environment must be initialized first
@@ -214,6 +736,165 @@ mod tests {
         tx_host_env::init_account(code);
     }
 
+    #[test]
+    fn test_tx_init_account_at() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let creator = address::testing::established_address_1();
+        let code =
+            std::fs::read(VP_ALWAYS_TRUE_WASM).expect("cannot load wasm");
+        let salt = b"salt";
+        let expected = creator.derive_sub(salt);
+
+        let addr = tx_host_env::init_account_at(&creator, &code, salt);
+        assert_eq!(addr, Ok(expected.clone()));
+        assert!(tx_host_env::has_key(
+            Key::validity_predicate(&expected).to_string()
+        ));
+        // The creator's VP is triggered
+        assert!(tx_host_env::with(|env| env.verifiers.contains(&creator)));
+
+        // The same address cannot be initialized again, also after commit
+        assert_eq!(
+            tx_host_env::init_account_at(&creator, &code, salt),
+            Err(tx_host_env::EnvError::Host(
+                tx_host_env::HostError::AccountExists
+            ))
+        );
+        tx_host_env::commit_tx_and_block();
+        assert_eq!(
+            tx_host_env::init_account_at(&creator, &code, salt),
+            Err(tx_host_env::EnvError::Host(
+                tx_host_env::HostError::AccountExists
+            ))
+        );
+
+        // A different salt derives a different address
+        let addr = tx_host_env::init_account_at(&creator, &code, b"other");
+        assert_eq!(addr, Ok(creator.derive_sub(b"other")));
+    }
+
+    #[test]
+    fn test_tx_init_account_from_template() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let code =
+            std::fs::read(VP_ALWAYS_TRUE_WASM).expect("cannot load wasm");
+        let allowed = anoma::types::hash::Hash::sha256(&code);
+        let unknown = anoma::types::hash::Hash::sha256(b"unknown");
+        // A whitelisted hash without any template code
+        let missing = anoma::types::hash::Hash::sha256(b"missing");
+        tx_host_env::with(|env| {
+            let whitelist = vec![allowed.to_string(), missing.to_string()];
+            let whitelist_key =
+                parameters::storage::get_vp_whitelist_storage_key();
+            let template_key =
+                parameters::storage::get_vp_template_key(&allowed);
+            env.storage
+                .write(&whitelist_key, whitelist.try_to_vec().unwrap())
+                .unwrap();
+            env.storage.write(&template_key, &code).unwrap();
+        });
+
+        let addr = tx_host_env::init_account_from_template(&allowed)
+            .expect("the template should be allowed");
+        let vp_key = Key::validity_predicate(&addr).to_string();
+        let vp: Option<Vec<u8>> = tx_host_env::read_bytes(&vp_key);
+        assert_eq!(vp, Some(code));
+
+        for hash in [&unknown, &missing] {
+            assert_eq!(
+                tx_host_env::init_account_from_template(hash),
+                Err(tx_host_env::EnvError::Host(
+                    tx_host_env::HostError::UnknownTemplate
+                ))
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tx_update_immutable_validity_predicate() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let addr = address::testing::established_address_1();
+        tx_host_env::with(|env| {
+            env.spawn_accounts([&addr]);
+            // Mark the account's VP as immutable
+            let marker_key =
+                Key::validity_predicate(&addr).immutable().unwrap();
+            env.storage.write(&marker_key, vec![]).unwrap();
+        });
+
+        // The VP cannot be updated
+        let code =
+            std::fs::read(VP_ALWAYS_TRUE_WASM).expect("cannot load wasm");
+        tx_host_env::update_validity_predicate(&addr, code);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tx_update_validity_predicate_validated_of_unknown_address() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        // The account doesn't exist
+        let addr = address::testing::established_address_1();
+        let code =
+            std::fs::read(VP_ALWAYS_TRUE_WASM).expect("cannot load wasm");
+        let _result =
+            tx_host_env::update_validity_predicate_validated(&addr, code);
+    }
+
+    #[test]
+    fn test_tx_update_validity_predicate_validated() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let addr = address::testing::established_address_1();
+        tx_host_env::with(|env| env.spawn_accounts([&addr]));
+        let vp_key = Key::validity_predicate(&addr);
+
+        // Bytes that are not wasm are rejected
+        let result = tx_host_env::update_validity_predicate_validated(
+            &addr,
+            b"not a wasm",
+        );
+        assert_eq!(
+            result,
+            Err(tx_host_env::EnvError::Host(
+                tx_host_env::HostError::InvalidVpCode
+            ))
+        );
+
+        // A wasm without the VP entrypoint is rejected
+        let code = std::fs::read(TX_NO_OP_WASM).expect("cannot load wasm");
+        let result =
+            tx_host_env::update_validity_predicate_validated(&addr, code);
+        assert_eq!(
+            result,
+            Err(tx_host_env::EnvError::Host(
+                tx_host_env::HostError::InvalidVpCode
+            ))
+        );
+        assert_eq!(
+            tx_host_env::read_bytes(vp_key.to_string()),
+            Some(vec![]),
+            "The VP must not be updated when the code is invalid"
+        );
+
+        // A valid VP code is accepted
+        let code =
+            std::fs::read(VP_ALWAYS_TRUE_WASM).expect("cannot load wasm");
+        let result =
+            tx_host_env::update_validity_predicate_validated(&addr, &code);
+        assert_eq!(result, Ok(()));
+        assert_eq!(tx_host_env::read_bytes(vp_key.to_string()), Some(code));
+    }
+
     #[test]
     fn test_tx_get_metadata() {
         // The environment must be initialized first
@@ -237,6 +918,74 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_tx_block_randomness() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        // The randomness is the same within a block
+        let randomness = tx_host_env::block_randomness();
+        assert_eq!(tx_host_env::block_randomness(), randomness);
+        assert_eq!(
+            randomness,
+            tx_host_env::with(|env| env.storage.get_block_randomness().0)
+                .as_slice()
+        );
+
+        // It changes with the block
+        tx_host_env::with(|env| {
+            let (height, _gas) = env.storage.get_block_height();
+            env.storage
+                .begin_block(BlockHash::default(), height.next_height())
+                .unwrap();
+        });
+        assert_ne!(tx_host_env::block_randomness(), randomness);
+    }
+
+    #[test]
+    fn test_tx_emit_event() {
+        // The environment must be initialized first
+        tx_host_env::init();
+
+        let transfer = vec![
+            ("source".to_string(), "alice".to_string()),
+            ("target".to_string(), "bob".to_string()),
+        ];
+        let memo = vec![("text".to_string(), "hello".to_string())];
+        tx_host_env::emit_event("transfer", &transfer);
+        tx_host_env::emit_event("memo", &memo);
+
+        let events = tx_host_env::take_events();
+        assert_eq!(
+            events,
+            vec![
+                TxEvent {
+                    event_type: "transfer".to_string(),
+                    attributes: transfer.clone(),
+                },
+                TxEvent {
+                    event_type: "memo".to_string(),
+                    attributes: memo.clone(),
+                },
+            ],
+            "The events should be recorded in the order of emission"
+        );
+        assert!(
+            tx_host_env::take_events().is_empty(),
+            "The events should be returned only once"
+        );
+
+        // The events emitted afterwards are returned on the next take
+        tx_host_env::emit_event("memo", &memo);
+        assert_eq!(
+            tx_host_env::take_events(),
+            vec![TxEvent {
+                event_type: "memo".to_string(),
+                attributes: memo,
+            }]
+        );
+    }
+
     /// An example how to write a VP host environment integration test
     #[test]
     fn test_vp_host_env() {
@@ -259,134 +1008,523 @@ mod tests {
     }
 
     #[test]
-    fn test_vp_read_and_has_key() {
-        let mut tx_env = TestTxEnv::default();
+    fn test_vp_read_and_has_key() {
+        let mut tx_env = TestTxEnv::default();
+
+        let addr = address::testing::established_address_1();
+        let addr_key = Key::from(addr.to_db_key());
+
+        // Write some value to storage
+        let existing_key =
+            addr_key.join(&Key::parse("existing_key_raw").unwrap());
+        let existing_key_raw = existing_key.to_string();
+        let existing_value = vec![2_u8; 1000];
+        // Values written to storage have to be encoded with Borsh
+        let existing_value_encoded = existing_value.try_to_vec().unwrap();
+        tx_env
+            .storage
+            .write(&existing_key, existing_value_encoded)
+            .unwrap();
+
+        // In a transaction, write override the existing key's value and add
+        // another key-value
+        let override_value = "override".to_string();
+        let new_key =
+            addr_key.join(&Key::parse("new_key").unwrap()).to_string();
+        let new_value = "vp".repeat(4);
+
+        // Initialize the VP environment via a transaction
+        vp_host_env::init_from_tx(addr, tx_env, |_addr| {
+            // Override the existing key
+            tx_host_env::write(&existing_key_raw, &override_value);
+
+            // Write the new key-value
+            tx_host_env::write(&new_key, new_value.clone());
+        });
+
+        assert!(
+            vp_host_env::has_key_pre(&existing_key_raw),
+            "The existing key before transaction should be found"
+        );
+        let pre_existing_value: Option<Vec<u8>> =
+            vp_host_env::read_pre(&existing_key_raw);
+        assert_eq!(
+            Some(existing_value),
+            pre_existing_value,
+            "The existing value read from state before transaction should be \
+             unchanged"
+        );
+
+        assert!(
+            !vp_host_env::has_key_pre(&new_key),
+            "The new key before transaction shouldn't be found"
+        );
+        let pre_new_value: Option<Vec<u8>> = vp_host_env::read_pre(&new_key);
+        assert_eq!(
+            None, pre_new_value,
+            "The new value read from state before transaction shouldn't yet \
+             exist"
+        );
+
+        assert!(
+            vp_host_env::has_key_post(&existing_key_raw),
+            "The existing key after transaction should still be found"
+        );
+        let post_existing_value: Option<String> =
+            vp_host_env::read_post(&existing_key_raw);
+        assert_eq!(
+            Some(override_value),
+            post_existing_value,
+            "The existing value read from state after transaction should be \
+             overridden"
+        );
+
+        assert!(
+            vp_host_env::has_key_post(&new_key),
+            "The new key after transaction should be found"
+        );
+        let post_new_value: Option<String> = vp_host_env::read_post(&new_key);
+        assert_eq!(
+            Some(new_value),
+            post_new_value,
+            "The new value read from state after transaction should have a \
+             value equal to the one written in the transaction"
+        );
+    }
+
+    #[test]
+    fn test_vp_iter_prefix() {
+        let mut tx_env = TestTxEnv::default();
+
+        let addr = address::testing::established_address_1();
+        let addr_key = Key::from(addr.to_db_key());
+
+        // Write some value to storage
+        let prefix = addr_key.join(&Key::parse("prefix").unwrap());
+        for i in 0..10_i32 {
+            let key = prefix.join(&Key::parse(i.to_string()).unwrap());
+            let value = i.try_to_vec().unwrap();
+            tx_env.storage.write(&key, value).unwrap();
+        }
+        tx_env.storage.commit().unwrap();
+
+        // In a transaction, write override the existing key's value and add
+        // another key-value
+        let existing_key = prefix.join(&Key::parse(5.to_string()).unwrap());
+        let existing_key_raw = existing_key.to_string();
+        let new_key = prefix.join(&Key::parse(11.to_string()).unwrap());
+        let new_key_raw = new_key.to_string();
+
+        // Initialize the VP environment via a transaction
+        vp_host_env::init_from_tx(addr, tx_env, |_addr| {
+            // Override one of the existing keys
+            tx_host_env::write(&existing_key_raw, 100_i32);
+
+            // Write the new key-value under the same prefix
+            tx_host_env::write(&new_key_raw, 11.try_to_vec().unwrap());
+        });
+
+        let iter_pre: PreKeyValIterator<i32> =
+            vp_host_env::iter_prefix_pre(prefix.to_string());
+        let expected_pre = (0..10).map(|i| (format!("{}/{}", prefix, i), i));
+        itertools::assert_equal(iter_pre.sorted(), expected_pre.sorted());
+
+        let iter_post: PostKeyValIterator<i32> =
+            vp_host_env::iter_prefix_post(prefix.to_string());
+        let expected_post = (0..10).map(|i| {
+            let val = if i == 5 { 100 } else { i };
+            (format!("{}/{}", prefix, i), val)
+        });
+        itertools::assert_equal(iter_post.sorted(), expected_post.sorted());
+    }
+
+    #[test]
+    fn test_vp_iter_prefix_borrowed() {
+        let mut tx_env = TestTxEnv::default();
+
+        let addr = address::testing::established_address_1();
+        let addr_key = Key::from(addr.to_db_key());
+
+        // Write some value to storage
+        let prefix = addr_key.join(&Key::parse("prefix").unwrap());
+        for i in 0..10_i32 {
+            let key = prefix.join(&Key::parse(i.to_string()).unwrap());
+            let value = i.try_to_vec().unwrap();
+            tx_env.storage.write(&key, value).unwrap();
+        }
+        tx_env.storage.commit().unwrap();
+
+        // Initialize the VP environment via a transaction that overrides one
+        // of the existing keys
+        let existing_key = prefix.join(&Key::parse(5.to_string()).unwrap());
+        let existing_key_raw = existing_key.to_string();
+        vp_host_env::init_from_tx(addr, tx_env, |_addr| {
+            tx_host_env::write(&existing_key_raw, 100_i32);
+        });
+
+        let mut iter =
+            vp_host_env::iter_prefix_borrowed::<i32>(prefix.to_string());
+        let mut items = vec![];
+        while let Some((key, val)) = iter.next() {
+            items.push((key.to_owned(), val));
+        }
+        let expected = (0..10).map(|i| {
+            let val = if i == 5 { 100 } else { i };
+            (format!("{}/{}", prefix, i), val)
+        });
+        itertools::assert_equal(items.into_iter().sorted(), expected.sorted());
+    }
+
+    #[test]
+    fn test_vp_iter_prefix_raw() {
+        let mut tx_env = TestTxEnv::default();
+
+        let addr = address::testing::established_address_1();
+        let addr_key = Key::from(addr.to_db_key());
+        let prefix = addr_key.push(&"info".to_owned()).unwrap();
+
+        // Write values of different types under the same prefix
+        let balance_key = prefix.push(&"balance".to_owned()).unwrap();
+        let balance = Amount::from(1_000);
+        tx_env
+            .storage
+            .write(&balance_key, balance.try_to_vec().unwrap())
+            .unwrap();
+        let name_key = prefix.push(&"name".to_owned()).unwrap();
+        tx_env
+            .storage
+            .write(&name_key, "alice".try_to_vec().unwrap())
+            .unwrap();
+        tx_env.storage.commit().unwrap();
+
+        // Initialize the VP environment via a transaction that updates the
+        // name
+        let name = "bob".to_owned();
+        let name_key_raw = name_key.to_string();
+        vp_host_env::init_from_tx(addr, tx_env, |_addr| {
+            tx_host_env::write(&name_key_raw, name.clone());
+        });
+
+        // The values are read from the state after the transaction
+        let mut seen = 0;
+        for (key, val) in vp_host_env::iter_prefix_raw(prefix.to_string()) {
+            // Choose the decoder from the key
+            if key == balance_key.to_string() {
+                assert_eq!(Amount::try_from_slice(&val).unwrap(), balance);
+            } else if key == name_key.to_string() {
+                assert_eq!(String::try_from_slice(&val).unwrap(), name);
+            } else {
+                panic!("Unexpected key {}", key);
+            }
+            seen += 1;
+        }
+        assert_eq!(seen, 2);
+    }
+
+    #[test]
+    fn test_vp_sum_prefix_post() {
+        let mut tx_env = TestTxEnv::default();
+
+        let addr = address::testing::established_address_1();
+        let addr_key = Key::from(addr.to_db_key());
+        let prefix = addr_key.push(&"sub_balance".to_owned()).unwrap();
+        let other_prefix = addr_key.push(&"other".to_owned()).unwrap();
+
+        // Write some numeric values under the prefix
+        for i in 1..=3_u64 {
+            let key = prefix.push(&i.to_string()).unwrap();
+            tx_env.storage.write(&key, i.try_to_vec().unwrap()).unwrap();
+        }
+        // Write a value of a different type under the other prefix
+        let key = other_prefix.push(&"name".to_owned()).unwrap();
+        tx_env
+            .storage
+            .write(&key, "alice".try_to_vec().unwrap())
+            .unwrap();
+        tx_env.storage.commit().unwrap();
+
+        // Initialize the VP environment via a transaction that adds a numeric
+        // value under both prefixes
+        let new_key_raw = prefix.push(&"4".to_owned()).unwrap().to_string();
+        let other_key_raw =
+            other_prefix.push(&"4".to_owned()).unwrap().to_string();
+        vp_host_env::init_from_tx(addr, tx_env, |_addr| {
+            tx_host_env::write(&new_key_raw, 4_u64);
+            tx_host_env::write(&other_key_raw, 4_u64);
+        });
+
+        assert_eq!(vp_host_env::sum_prefix_post(prefix.to_string()), Some(10));
+        // A value that's not a `u64` cannot be summed
+        assert_eq!(
+            vp_host_env::sum_prefix_post(other_prefix.to_string()),
+            None
+        );
+        // Nothing under the prefix sums to zero
+        let empty_prefix = addr_key.push(&"empty".to_owned()).unwrap();
+        assert_eq!(
+            vp_host_env::sum_prefix_post(empty_prefix.to_string()),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_vp_gas_cost() {
+        // The environment must be initialized first
+        vp_host_env::init();
+
+        for op in [
+            vp_host_env::GasOp::Read,
+            vp_host_env::GasOp::Write,
+            vp_host_env::GasOp::Iter,
+            vp_host_env::GasOp::Hash,
+        ] {
+            let cost = vp_host_env::gas_cost(op);
+            assert!(cost > 0, "The gas cost of {:?} should be positive", op);
+            assert_eq!(cost, vp_host_env::gas_cost(op));
+        }
+    }
+
+    #[test]
+    fn test_vp_read_post_range() {
+        let tx_env = TestTxEnv::default();
 
         let addr = address::testing::established_address_1();
         let addr_key = Key::from(addr.to_db_key());
-
-        // Write some value to storage
-        let existing_key =
-            addr_key.join(&Key::parse("existing_key_raw").unwrap());
-        let existing_key_raw = existing_key.to_string();
-        let existing_value = vec![2_u8; 1000];
-        // Values written to storage have to be encoded with Borsh
-        let existing_value_encoded = existing_value.try_to_vec().unwrap();
-        tx_env
-            .storage
-            .write(&existing_key, existing_value_encoded)
-            .unwrap();
-
-        // In a transaction, write override the existing key's value and add
-        // another key-value
-        let override_value = "override".to_string();
-        let new_key =
-            addr_key.join(&Key::parse("new_key").unwrap()).to_string();
-        let new_value = "vp".repeat(4);
+        let key = addr_key.push(&"large".to_string()).unwrap().to_string();
+        let value: Vec<u8> = (0..1024_u32).map(|i| (i % 256) as u8).collect();
 
         // Initialize the VP environment via a transaction
         vp_host_env::init_from_tx(addr, tx_env, |_addr| {
-            // Override the existing key
-            tx_host_env::write(&existing_key_raw, &override_value);
-
-            // Write the new key-value
-            tx_host_env::write(&new_key, new_value.clone());
+            tx_host_env::write_bytes(&key, &value);
         });
 
-        assert!(
-            vp_host_env::has_key_pre(&existing_key_raw),
-            "The existing key before transaction should be found"
-        );
-        let pre_existing_value: Option<Vec<u8>> =
-            vp_host_env::read_pre(&existing_key_raw);
         assert_eq!(
-            Some(existing_value),
-            pre_existing_value,
-            "The existing value read from state before transaction should be \
-             unchanged"
-        );
-
-        assert!(
-            !vp_host_env::has_key_pre(&new_key),
-            "The new key before transaction shouldn't be found"
+            vp_host_env::read_post_range(&key, 10, 10),
+            Some(value[10..20].to_vec())
         );
-        let pre_new_value: Option<Vec<u8>> = vp_host_env::read_pre(&new_key);
+        // The range is truncated at the end of the value
         assert_eq!(
-            None, pre_new_value,
-            "The new value read from state before transaction shouldn't yet \
-             exist"
+            vp_host_env::read_post_range(&key, 1020, 10),
+            Some(value[1020..].to_vec())
         );
+        assert_eq!(vp_host_env::read_post_range(&key, 2048, 10), None);
+        assert_eq!(vp_host_env::read_post_range("missing", 0, 10), None);
+    }
 
-        assert!(
-            vp_host_env::has_key_post(&existing_key_raw),
-            "The existing key after transaction should still be found"
-        );
-        let post_existing_value: Option<String> =
-            vp_host_env::read_post(&existing_key_raw);
-        assert_eq!(
-            Some(override_value),
-            post_existing_value,
-            "The existing value read from state after transaction should be \
-             overridden"
-        );
+    #[test]
+    fn test_vp_all_changed_keys() {
+        let mut tx_env = TestTxEnv::default();
 
-        assert!(
-            vp_host_env::has_key_post(&new_key),
-            "The new key after transaction should be found"
-        );
-        let post_new_value: Option<String> = vp_host_env::read_post(&new_key);
-        assert_eq!(
-            Some(new_value),
-            post_new_value,
-            "The new value read from state after transaction should have a \
-             value equal to the one written in the transaction"
-        );
+        let addr = address::testing::established_address_1();
+        let other = address::testing::established_address_2();
+        tx_env.spawn_accounts([&other]);
+        let addr_key = Key::from(addr.to_db_key());
+        let other_key = Key::from(other.to_db_key());
+        let keys = [
+            addr_key.join(&Key::parse("a/1").unwrap()),
+            addr_key.join(&Key::parse("b/2").unwrap()),
+            other_key.join(&Key::parse("c/3").unwrap()),
+        ];
+
+        // Initialize the VP environment via a transaction that writes keys
+        // under multiple prefixes
+        vp_host_env::init_from_tx(addr, tx_env, |_addr| {
+            for key in &keys {
+                tx_host_env::write(key.to_string(), "value".to_string());
+            }
+        });
+
+        let changed_keys = vp_host_env::all_changed_keys();
+        for key in &keys {
+            assert!(
+                changed_keys.contains(&key.to_string()),
+                "The changed key {} should be found in {:?}",
+                key,
+                changed_keys
+            );
+        }
     }
 
     #[test]
-    fn test_vp_iter_prefix() {
+    fn test_vp_changed_with_values_post() {
         let mut tx_env = TestTxEnv::default();
 
         let addr = address::testing::established_address_1();
+        let other = address::testing::established_address_2();
+        tx_env.spawn_accounts([&other]);
         let addr_key = Key::from(addr.to_db_key());
+        let prefix = addr_key.push(&"data".to_owned()).unwrap();
+        let sub_key = |sub: &str| prefix.push(&sub.to_owned()).unwrap();
+        let (created, deleted, modified) =
+            (sub_key("created"), sub_key("deleted"), sub_key("modified"));
+        let outside = Key::from(other.to_db_key())
+            .push(&"data".to_owned())
+            .unwrap();
 
-        // Write some value to storage
-        let prefix = addr_key.join(&Key::parse("prefix").unwrap());
-        for i in 0..10_i32 {
-            let key = prefix.join(&Key::parse(i.to_string()).unwrap());
-            let value = i.try_to_vec().unwrap();
-            tx_env.storage.write(&key, value).unwrap();
+        // Write the prior values
+        for (key, value) in [(&deleted, 1_u64), (&modified, 2_u64)] {
+            let value = value.try_to_vec().unwrap();
+            tx_env.storage.write(key, value).unwrap();
         }
         tx_env.storage.commit().unwrap();
 
-        // In a transaction, write override the existing key's value and add
-        // another key-value
-        let existing_key = prefix.join(&Key::parse(5.to_string()).unwrap());
-        let existing_key_raw = existing_key.to_string();
-        let new_key = prefix.join(&Key::parse(11.to_string()).unwrap());
-        let new_key_raw = new_key.to_string();
-
-        // Initialize the VP environment via a transaction
+        // Initialize the VP environment via a transaction that creates,
+        // deletes and modifies keys under the prefix and outside of it
         vp_host_env::init_from_tx(addr, tx_env, |_addr| {
-            // Override one of the existing keys
-            tx_host_env::write(&existing_key_raw, 100_i32);
+            tx_host_env::write(created.to_string(), 3_u64);
+            tx_host_env::delete(deleted.to_string());
+            tx_host_env::write(modified.to_string(), 4_u64);
+            tx_host_env::write(outside.to_string(), 5_u64);
+        });
 
-            // Write the new key-value under the same prefix
-            tx_host_env::write(&new_key_raw, 11.try_to_vec().unwrap());
+        let mut changes =
+            vp_host_env::changed_with_values_post::<u64>(prefix.to_string());
+        changes.sort();
+        let expected = vec![
+            (created.to_string(), None, Some(3)),
+            (deleted.to_string(), Some(1), None),
+            (modified.to_string(), Some(2), Some(4)),
+        ];
+        assert_eq!(changes, expected);
+    }
+
+    #[test]
+    fn test_vp_assert_all_changes_owned() {
+        let addr = address::testing::established_address_1();
+        let other = address::testing::established_address_2();
+        let token = address::xan();
+        let check = |key: Key| {
+            let mut tx_env = TestTxEnv::default();
+            tx_env.spawn_accounts([&other, &token]);
+
+            vp_host_env::init_from_tx(addr.clone(), tx_env, |_addr| {
+                tx_host_env::write(key.to_string(), "value".to_string());
+            });
+            vp_host_env::assert_all_changes_owned(&[addr.clone()])
+        };
+
+        // Changes in the account's sub-space and of its balance are owned
+        let addr_key = Key::from(addr.to_db_key());
+        assert!(check(addr_key.push(&"data".to_owned()).unwrap()));
+        assert!(check(token::balance_key(&token, &addr)));
+        // Changes in another account's sub-space or of its balance are not
+        let other_key = Key::from(other.to_db_key());
+        assert!(!check(other_key.push(&"data".to_owned()).unwrap()));
+        assert!(!check(token::balance_key(&token, &other)));
+
+        let balance_key = token::balance_key(&token, &addr).to_string();
+        assert_eq!(vp_host_env::parse_owned_key(balance_key), Some(addr));
+        assert_eq!(vp_host_env::parse_owned_key("not_an_address/key"), None);
+    }
+
+    #[test]
+    fn test_vp_assert_balance_delta() {
+        let mut tx_env = TestTxEnv::default();
+
+        let addr = address::testing::established_address_1();
+        let token = address::xan();
+        let missing = address::testing::established_address_2();
+        tx_env.spawn_accounts([&token]);
+        tx_env.credit_tokens(&addr, &token, Amount::whole(100));
+        tx_env.storage.commit().unwrap();
+
+        // Initialize the VP environment via a transaction
+        vp_host_env::init_from_tx(addr.clone(), tx_env, |addr| {
+            let key = token::balance_key(&token, addr).to_string();
+            tx_host_env::write(&key, Amount::whole(70));
         });
 
-        let iter_pre: PreKeyValIterator<i32> =
-            vp_host_env::iter_prefix_pre(prefix.to_string());
-        let expected_pre = (0..10).map(|i| (format!("{}/{}", prefix, i), i));
-        itertools::assert_equal(iter_pre.sorted(), expected_pre.sorted());
+        let delta = -(Amount::whole(30).change());
+        assert!(vp_host_env::token::assert_balance_delta(
+            &addr, &token, delta
+        ));
+        assert!(!vp_host_env::token::assert_balance_delta(
+            &addr,
+            &token,
+            delta + 1
+        ));
+        assert!(!vp_host_env::token::assert_balance_delta(
+            &addr,
+            &token,
+            -delta
+        ));
 
-        let iter_post: PostKeyValIterator<i32> =
-            vp_host_env::iter_prefix_post(prefix.to_string());
-        let expected_post = (0..10).map(|i| {
-            let val = if i == 5 { 100 } else { i };
-            (format!("{}/{}", prefix, i), val)
+        // An account without any balance has not changed
+        assert!(vp_host_env::token::assert_balance_delta(
+            &missing, &token, 0
+        ));
+        assert!(!vp_host_env::token::assert_balance_delta(
+            &missing, &token, delta
+        ));
+    }
+
+    #[test]
+    fn test_vp_assert_min_balance() {
+        let addr = address::testing::established_address_1();
+        let token = address::xan();
+        let floor = u64::from(Amount::whole(50));
+        let withdraw = |remaining: Amount| {
+            let mut tx_env = TestTxEnv::default();
+            tx_env.spawn_accounts([&token]);
+            tx_env.credit_tokens(&addr, &token, Amount::whole(100));
+            tx_env.storage.commit().unwrap();
+
+            vp_host_env::init_from_tx(addr.clone(), tx_env, |addr| {
+                let key = token::balance_key(&token, addr).to_string();
+                tx_host_env::write(&key, remaining);
+            });
+            vp_host_env::token::assert_min_balance(&addr, &token, floor)
+        };
+
+        // A withdrawal keeping the balance above or at the floor passes
+        assert!(withdraw(Amount::whole(70)));
+        assert!(withdraw(Amount::whole(50)));
+        // A withdrawal dropping the balance below the floor fails
+        assert!(!withdraw(Amount::from(floor - 1)));
+        assert!(!withdraw(Amount::default()));
+    }
+
+    #[test]
+    fn test_vp_assert_conservation() {
+        let addr = address::testing::established_address_1();
+        let other = address::testing::established_address_2();
+        let token = address::xan();
+        let accounts = [addr.clone(), other.clone()];
+        let init_tx_env = || {
+            let mut tx_env = TestTxEnv::default();
+            tx_env.spawn_accounts([&token, &addr, &other]);
+            tx_env.credit_tokens(&addr, &token, Amount::whole(100));
+            tx_env.storage.commit().unwrap();
+            tx_env
+        };
+
+        // A transfer between the accounts is balanced
+        vp_host_env::init_from_tx(addr.clone(), init_tx_env(), |addr| {
+            tx_host_env::token::transfer(
+                addr,
+                &other,
+                &token,
+                Amount::whole(30),
+            );
         });
-        itertools::assert_equal(iter_post.sorted(), expected_post.sorted());
+        assert!(vp_host_env::token::assert_conservation(&token, &accounts));
+        // Listing an account twice doesn't change the sum
+        assert!(vp_host_env::token::assert_conservation(
+            &token,
+            &[addr.clone(), other.clone(), other.clone()]
+        ));
+        // The transfer alone is not balanced for one of its parties
+        assert!(!vp_host_env::token::assert_conservation(
+            &token,
+            &[addr.clone()]
+        ));
+
+        // Minting tokens is not balanced
+        vp_host_env::init_from_tx(addr.clone(), init_tx_env(), |addr| {
+            let key = token::balance_key(&token, addr).to_string();
+            tx_host_env::write(&key, Amount::whole(130));
+        });
+        assert!(!vp_host_env::token::assert_conservation(&token, &accounts));
     }
 
     #[test]
@@ -431,6 +1569,155 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_vp_verify_tx_signature_logged() {
+        use std::io;
+        use std::sync::{Arc, Mutex};
+
+        /// A log writer that captures the logs into a shared buffer
+        #[derive(Clone)]
+        struct CaptureWriter(Arc<Mutex<Vec<u8>>>);
+
+        impl io::Write for CaptureWriter {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        vp_host_env::init();
+
+        let keypair = key::testing::keypair_1();
+        let pk = keypair.ref_to();
+        let other_pk = key::testing::keypair_2().ref_to();
+        let sig = vp_host_env::with(|env| {
+            env.tx = Tx::new(vec![4, 3, 2, 1, 0], Some(vec![1, 2, 3, 4]))
+                .sign(&keypair);
+            let tx_data = env.tx.data.as_ref().expect("data should exist");
+            SignedTxData::try_from_slice(&tx_data[..])
+                .expect("decoding signed data we just signed")
+                .sig
+        });
+
+        let captured = Arc::new(Mutex::new(vec![]));
+        let writer = CaptureWriter(captured.clone());
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .finish();
+        let (valid, invalid) =
+            tracing::subscriber::with_default(subscriber, || {
+                (
+                    vp_host_env::verify_tx_signature_logged(&pk, &sig),
+                    vp_host_env::verify_tx_signature_logged(&other_pk, &sig),
+                )
+            });
+        assert!(valid);
+        assert!(!invalid);
+
+        // Only the invalid signature should be logged, with its reason
+        let logs = String::from_utf8(captured.lock().unwrap().clone()).unwrap();
+        assert_eq!(logs.lines().count(), 1);
+        assert!(logs.contains("WARN"));
+        assert!(logs.contains("Signature verification failed"));
+
+        // A tx with missing or malformed data fails with its reason logged
+        let verify_with_tx = |tx: Tx| {
+            vp_host_env::with(|env| env.tx = tx);
+            captured.lock().unwrap().clear();
+            let writer = CaptureWriter(captured.clone());
+            let subscriber = tracing_subscriber::fmt()
+                .with_writer(move || writer.clone())
+                .finish();
+            let valid = tracing::subscriber::with_default(subscriber, || {
+                vp_host_env::verify_tx_signature_logged(&pk, &sig)
+            });
+            let logs =
+                String::from_utf8(captured.lock().unwrap().clone()).unwrap();
+            (valid, logs)
+        };
+        let (valid, logs) = verify_with_tx(Tx::new(vec![4, 3, 2, 1, 0], None));
+        assert!(!valid);
+        assert!(logs.contains("doesn't have any data with a signature"));
+        let (valid, logs) =
+            verify_with_tx(Tx::new(vec![4, 3, 2, 1, 0], Some(vec![1, 2, 3])));
+        assert!(!valid);
+        assert!(logs.contains("signed data cannot be decoded"));
+    }
+
+    #[test]
+    fn test_vp_verify_tx_signatures() {
+        vp_host_env::init();
+
+        let keypair_1 = key::testing::keypair_1();
+        let keypair_2 = key::testing::keypair_2();
+        let tx = Tx::new(vec![4, 3, 2, 1, 0], Some(vec![1, 2, 3, 4]));
+        // The co-signers sign the same hash as the tx signer
+        let to_sign = tx.hash();
+        vp_host_env::with(|env| env.tx = tx.clone().sign(&keypair_1));
+
+        let sig_1 = common::SigScheme::sign(&keypair_1, &to_sign);
+        let sig_2 = common::SigScheme::sign(&keypair_2, &to_sign);
+        let pairs = [
+            (keypair_1.ref_to(), sig_1.clone()),
+            (keypair_2.ref_to(), sig_2),
+            // A signature checked against another key is invalid
+            (keypair_2.ref_to(), sig_1.clone()),
+        ];
+        assert_eq!(vp_host_env::verify_tx_signatures(&pairs), 2);
+        assert_eq!(vp_host_env::verify_tx_signatures(&[]), 0);
+
+        // A duplicated pair is only counted once
+        let pairs = vec![(keypair_1.ref_to(), sig_1.clone()); 3];
+        assert_eq!(vp_host_env::verify_tx_signatures(&pairs), 1);
+
+        // A tx without signed data has no valid signatures and doesn't abort
+        vp_host_env::with(|env| env.tx = tx.clone());
+        let pairs = [(keypair_1.ref_to(), sig_1)];
+        assert_eq!(vp_host_env::verify_tx_signatures(&pairs), 0);
+    }
+
+    /// Test that the constant time signature verification takes comparable
+    /// time for valid and invalid signatures.
+    #[test]
+    fn test_vp_verify_tx_signature_ct() {
+        vp_host_env::init();
+
+        let keypair = key::testing::keypair_1();
+        let pk = keypair.ref_to();
+        let other_pk = key::testing::keypair_2().ref_to();
+        let sig = vp_host_env::with(|env| {
+            env.tx = Tx::new(vec![4, 3, 2, 1, 0], Some(vec![1, 2, 3, 4]))
+                .sign(&keypair);
+            let tx_data = env.tx.data.as_ref().expect("data should exist");
+            SignedTxData::try_from_slice(&tx_data[..])
+                .expect("decoding signed data we just signed")
+                .sig
+        });
+        assert!(vp_host_env::verify_tx_signature_ct(&pk, &sig));
+        assert!(!vp_host_env::verify_tx_signature_ct(&other_pk, &sig));
+
+        // The signature of a different tx is invalid
+        vp_host_env::with(|env| {
+            env.tx = Tx::new(vec![4, 3, 2, 1, 0], Some(vec![4, 3, 2, 1]))
+                .sign(&keypair)
+        });
+        assert!(!vp_host_env::verify_tx_signature_ct(&pk, &sig));
+
+        // A tx with missing or malformed data fails without a panic
+        vp_host_env::with(|env| {
+            env.tx = Tx::new(vec![4, 3, 2, 1, 0], None);
+        });
+        assert!(!vp_host_env::verify_tx_signature_ct(&pk, &sig));
+        vp_host_env::with(|env| {
+            env.tx = Tx::new(vec![4, 3, 2, 1, 0], Some(vec![0xff]));
+        });
+        assert!(!vp_host_env::verify_tx_signature_ct(&pk, &sig));
+    }
+
     #[test]
     fn test_vp_get_metadata() {
         // The environment must be initialized first
@@ -454,6 +1741,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_vp_get_prev_block_root() {
+        // The environment must be initialized first
+        vp_host_env::init();
+
+        // At genesis, there is no previous block
+        assert_eq!(vp_host_env::get_prev_block_root(), None);
+
+        // Commit a block and start applying the next one
+        let root = vp_host_env::with(|env| {
+            env.storage.block.height = BlockHeight(2);
+            env.storage.commit().unwrap();
+            env.storage.block.height = BlockHeight(3);
+            env.storage.merkle_root()
+        });
+        let prev_root = vp_host_env::get_prev_block_root()
+            .expect("The previous block should have a root");
+        assert_eq!(prev_root.0.to_vec(), root.0);
+    }
+
+    #[test]
+    fn test_exec_context() {
+        tx_host_env::init();
+        assert_eq!(
+            tx_host_env::context::current(),
+            tx_host_env::context::ExecContext::Tx
+        );
+
+        vp_host_env::init();
+        assert_eq!(
+            vp_host_env::context::current(),
+            vp_host_env::context::ExecContext::Vp
+        );
+    }
+
+    /// Test that the epoch of the applied block is visible to both the tx and
+    /// the VP.
+    #[test]
+    fn test_get_block_epoch_after_update() {
+        let epoch = storage::Epoch(5);
+
+        tx_host_env::init();
+        tx_host_env::with(|env| env.storage.block.epoch = epoch);
+        assert_eq!(tx_host_env::get_block_epoch(), epoch);
+
+        vp_host_env::init();
+        vp_host_env::with(|env| env.storage.block.epoch = epoch);
+        assert_eq!(vp_host_env::get_block_epoch(), epoch);
+    }
+
+    /// Test that the VP can read the gas limit of the transaction.
+    #[test]
+    fn test_vp_tx_gas_limit() {
+        vp_host_env::init();
+        let limit = vp_host_env::with(|env| env.gas_meter.tx_gas_limit());
+        assert_eq!(vp_host_env::tx_gas_limit(), limit);
+    }
+
     #[test]
     fn test_vp_eval() {
         // The environment must be initialized first
@@ -481,6 +1826,122 @@ mod tests {
         assert!(!result);
     }
 
+    #[test]
+    fn test_vp_env_error() {
+        // The environment must be initialized first
+        vp_host_env::init();
+
+        let valid = "valid";
+        let malformed = "malformed";
+        let missing = "missing";
+        vp_host_env::with(|env| {
+            env.storage
+                .write(&Key::parse(valid).unwrap(), 1_u64.try_to_vec().unwrap())
+                .unwrap();
+            env.storage
+                .write(&Key::parse(malformed).unwrap(), vec![1])
+                .unwrap();
+        });
+
+        assert_eq!(vp_host_env::try_read_pre::<u64>(valid), Ok(1));
+        assert_eq!(vp_host_env::try_read_post::<u64>(valid), Ok(1));
+        assert_eq!(
+            vp_host_env::try_read_pre::<u64>(malformed),
+            Err(vp_host_env::EnvError::Decode)
+        );
+        assert_eq!(
+            vp_host_env::try_read_post::<u64>(malformed),
+            Err(vp_host_env::EnvError::Decode)
+        );
+        assert_eq!(
+            vp_host_env::try_read_pre::<u64>(missing),
+            Err(vp_host_env::EnvError::NotFound)
+        );
+        assert_eq!(
+            vp_host_env::try_read_post::<u64>(missing),
+            Err(vp_host_env::EnvError::NotFound)
+        );
+    }
+
+    #[test]
+    fn test_vp_get_tx_signer() {
+        let mut env = TestVpEnv::default();
+
+        let addr = address::testing::established_address_1();
+        let other = address::testing::established_address_2();
+        let shared = address::testing::established_address_3();
+
+        // Write the public keys to storage, the same key is registered for
+        // two of the accounts
+        let keypair = key::testing::keypair_1();
+        for (owner, keypair) in [
+            (&addr, &keypair),
+            (&other, &key::testing::keypair_2()),
+            (&shared, &keypair),
+        ] {
+            env.storage
+                .write(
+                    &key::pk_key(owner),
+                    keypair.ref_to().try_to_vec().unwrap(),
+                )
+                .unwrap();
+        }
+        // Initialize the environment
+        vp_host_env::set(env);
+
+        // A tx signed with a registered key of one of the candidates
+        let code = vec![4, 3, 2, 1, 0];
+        let data = Some(vec![1, 2, 3, 4]);
+        vp_host_env::with(|env| {
+            env.tx = Tx::new(code.clone(), data.clone()).sign(&keypair)
+        });
+        let candidates = [other.clone(), addr.clone()];
+        assert_eq!(vp_host_env::get_tx_signer(&candidates), Some(addr.clone()));
+        // The first of the candidates sharing the key is the signer
+        let candidates = [shared.clone(), addr.clone()];
+        assert_eq!(vp_host_env::get_tx_signer(&candidates), Some(shared));
+        // The signer's account is not among the candidates
+        assert_eq!(vp_host_env::get_tx_signer(&[other.clone()]), None);
+        assert_eq!(vp_host_env::get_tx_signer(&[]), None);
+
+        // A tx signed with an unknown key
+        vp_host_env::with(|env| {
+            env.tx = Tx::new(code.clone(), data.clone())
+                .sign(&key::testing::keypair_n(3))
+        });
+        assert_eq!(vp_host_env::get_tx_signer(&[addr.clone(), other]), None);
+
+        // An unsigned tx
+        vp_host_env::with(|env| env.tx = Tx::new(code.clone(), data.clone()));
+        assert_eq!(vp_host_env::get_tx_signer(&[addr]), None);
+    }
+
+    #[test]
+    fn test_vp_would_accept() {
+        // The environment must be initialized first
+        vp_host_env::init();
+
+        let permissive = address::testing::established_address_1();
+        let restrictive = address::testing::established_address_2();
+        let no_vp = address::testing::established_address_3();
+        vp_host_env::with(|env| {
+            for (addr, vp) in [
+                (&permissive, VP_ALWAYS_TRUE_WASM),
+                (&restrictive, VP_ALWAYS_FALSE_WASM),
+            ] {
+                let code = std::fs::read(vp).expect("cannot load wasm");
+                env.storage
+                    .write(&Key::validity_predicate(addr), code)
+                    .unwrap();
+            }
+        });
+
+        assert!(vp_host_env::would_accept(&permissive, vec![]));
+        assert!(!vp_host_env::would_accept(&restrictive, vec![]));
+        // An account without a VP wouldn't accept anything
+        assert!(!vp_host_env::would_accept(&no_vp, vec![]));
+    }
+
     #[test]
     fn test_ibc_client() {
         // The environment must be initialized first
@@ -8,6 +8,7 @@ use anoma::ledger::storage::testing::TestStorage;
 use anoma::ledger::storage::write_log::WriteLog;
 use anoma::proto::Tx;
 use anoma::types::address::Address;
+use anoma::types::event::TxEvent;
 use anoma::types::storage::Key;
 use anoma::types::time::DurationSecs;
 use anoma::types::{key, token};
@@ -127,6 +128,12 @@ impl TestTxEnv {
         self.gas_meter = BlockGasMeter::default();
     }
 
+    /// Take the events emitted with `tx::emit_event` by the transaction so
+    /// far. The events are cleared, so each of them is returned only once.
+    pub fn take_events(&mut self) -> Vec<TxEvent> {
+        self.write_log.take_events()
+    }
+
     /// Credit tokens to the target account.
     pub fn credit_tokens(
         &mut self,
@@ -226,6 +233,12 @@ mod native_tx_host_env {
         with(|env| env.commit_tx_and_block())
     }
 
+    /// Take the events emitted by the transaction in [`ENV`] so far. The
+    /// [`ENV`] must be initialized.
+    pub fn take_events() -> Vec<TxEvent> {
+        with(|env| env.take_events())
+    }
+
     /// A helper macro to create implementations of the host environment
     /// functions exported to wasm, which uses the environment from the
     /// `ENV` variable.
@@ -324,9 +337,24 @@ mod native_tx_host_env {
         val_ptr: u64,
         val_len: u64
     ));
+    native_host_fn!(tx_write_immutable(
+        key_ptr: u64,
+        key_len: u64,
+        val_ptr: u64,
+        val_len: u64
+    ) -> i64);
+    native_host_fn!(tx_write_with_ttl(
+        key_ptr: u64,
+        key_len: u64,
+        val_ptr: u64,
+        val_len: u64,
+        ttl_blocks: u64,
+    ));
     native_host_fn!(tx_delete(key_ptr: u64, key_len: u64));
     native_host_fn!(tx_iter_prefix(prefix_ptr: u64, prefix_len: u64) -> u64);
     native_host_fn!(tx_iter_next(iter_id: u64) -> i64);
+    native_host_fn!(tx_iter_is_valid(iter_id: u64) -> i64);
+    native_host_fn!(tx_iter_snapshot_height(iter_id: u64) -> i64);
     native_host_fn!(tx_insert_verifier(addr_ptr: u64, addr_len: u64));
     native_host_fn!(tx_update_validity_predicate(
         addr_ptr: u64,
@@ -334,16 +362,45 @@ mod native_tx_host_env {
         code_ptr: u64,
         code_len: u64,
     ));
+    native_host_fn!(tx_update_validity_predicate_validated(
+        addr_ptr: u64,
+        addr_len: u64,
+        code_ptr: u64,
+        code_len: u64,
+    ) -> i64);
     native_host_fn!(tx_init_account(
         code_ptr: u64,
         code_len: u64,
         result_ptr: u64
     ));
+    native_host_fn!(tx_init_account_at(
+        creator_ptr: u64,
+        creator_len: u64,
+        code_ptr: u64,
+        code_len: u64,
+        salt_ptr: u64,
+        salt_len: u64,
+    ) -> i64);
+    native_host_fn!(tx_init_account_from_template(
+        hash_ptr: u64,
+        hash_len: u64,
+        result_ptr: u64,
+    ) -> i64);
     native_host_fn!(tx_emit_ibc_event(event_ptr: u64, event_len: u64));
+    native_host_fn!(tx_emit_event(event_ptr: u64, event_len: u64));
+    native_host_fn!(tx_schedule(tx_ptr: u64, tx_len: u64) -> u64);
+    native_host_fn!(tx_cancel_scheduled(
+            scheduler_ptr: u64,
+            scheduler_len: u64,
+            id: u64,
+        ) -> i64);
     native_host_fn!(tx_get_chain_id(result_ptr: u64));
     native_host_fn!(tx_get_block_height() -> u64);
     native_host_fn!(tx_get_block_time() -> i64);
     native_host_fn!(tx_get_block_hash(result_ptr: u64));
+    native_host_fn!(tx_get_block_randomness(result_ptr: u64));
     native_host_fn!(tx_get_block_epoch() -> u64);
+    native_host_fn!(tx_gas_cost(op: u64) -> i64);
+    native_host_fn!(tx_exec_context() -> u64);
     native_host_fn!(tx_log_string(str_ptr: u64, str_len: u64));
 }
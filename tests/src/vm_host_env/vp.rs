@@ -312,6 +312,12 @@ mod native_vp_host_env {
     // [`anoma_vm_env::imports::vp`] `extern "C"` section.
     native_host_fn!(vp_read_pre(key_ptr: u64, key_len: u64) -> i64);
     native_host_fn!(vp_read_post(key_ptr: u64, key_len: u64) -> i64);
+    native_host_fn!(vp_read_post_range(
+            key_ptr: u64,
+            key_len: u64,
+            offset: u64,
+            len: u64,
+        ) -> i64);
     native_host_fn!(vp_result_buffer(result_ptr: u64));
     native_host_fn!(vp_has_key_pre(key_ptr: u64, key_len: u64) -> i64);
     native_host_fn!(vp_has_key_post(key_ptr: u64, key_len: u64) -> i64);
@@ -322,18 +328,65 @@ mod native_vp_host_env {
     native_host_fn!(vp_get_block_height() -> u64);
     native_host_fn!(vp_get_block_hash(result_ptr: u64));
     native_host_fn!(vp_get_tx_code_hash(result_ptr: u64));
+    native_host_fn!(vp_get_prev_block_root(result_ptr: u64) -> i64);
     native_host_fn!(vp_get_block_epoch() -> u64);
+    native_host_fn!(vp_gas_cost(op: u64) -> i64);
+    native_host_fn!(vp_tx_gas_limit() -> u64);
+    native_host_fn!(vp_get_changed_keys() -> i64);
     native_host_fn!(vp_verify_tx_signature(
             pk_ptr: u64,
             pk_len: u64,
             sig_ptr: u64,
             sig_len: u64,
         ) -> i64);
+    native_host_fn!(vp_verify_tx_signature_logged(
+            pk_ptr: u64,
+            pk_len: u64,
+            sig_ptr: u64,
+            sig_len: u64,
+        ) -> i64);
+    native_host_fn!(vp_verify_tx_signature_ct(
+            pk_ptr: u64,
+            pk_len: u64,
+            sig_ptr: u64,
+            sig_len: u64,
+        ) -> i64);
+    native_host_fn!(vp_verify_tx_signatures(
+            sigs_ptr: u64,
+            sigs_len: u64,
+        ) -> i64);
+    native_host_fn!(vp_get_tx_signer(
+            candidates_ptr: u64,
+            candidates_len: u64,
+        ) -> i64);
     native_host_fn!(vp_eval(
             vp_code_ptr: u64,
             vp_code_len: u64,
             input_data_ptr: u64,
             input_data_len: u64,
         ) -> i64);
+    native_host_fn!(vp_would_accept(
+            addr_ptr: u64,
+            addr_len: u64,
+            input_data_ptr: u64,
+            input_data_len: u64,
+        ) -> i64);
     native_host_fn!(vp_log_string(str_ptr: u64, str_len: u64));
+    native_host_fn!(vp_exec_context() -> u64);
+
+    /// The execution context import is shared by the tx and VP environments.
+    /// This calls the VP host function when the VP [`ENV`] is initialized and
+    /// the tx host function otherwise.
+    #[no_mangle]
+    extern "C" fn anoma_exec_context() -> u64 {
+        extern "C" {
+            fn anoma_tx_exec_context() -> u64;
+        }
+        let is_vp = ENV.with(|env| env.borrow().is_some());
+        if is_vp {
+            anoma_vp_exec_context()
+        } else {
+            unsafe { anoma_tx_exec_context() }
+        }
+    }
 }
@@ -0,0 +1,87 @@
+//! The error type shared by the transaction and validity predicate
+//! environments.
+
+use std::fmt;
+
+/// An error of a fallible call to the host environment, that can be handled
+/// by the guest code
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnvError {
+    /// A value could not be encoded with Borsh
+    Encode,
+    /// A value read from the host could not be decoded with Borsh
+    Decode,
+    /// The host environment rejected the call
+    Host(HostError),
+    /// The requested value is not present
+    NotFound,
+}
+
+/// The reason for which the host environment rejected a call
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HostError {
+    /// The code is not a valid validity predicate wasm
+    InvalidVpCode,
+    /// The key is already present, so it cannot be written as immutable
+    ImmutableKey,
+    /// The account already exists, so it cannot be initialized
+    AccountExists,
+    /// The validity predicate template is not whitelisted or not found
+    UnknownTemplate,
+    /// The key is longer than the maximum length allowed for writes
+    KeyTooLong {
+        /// The length of the key
+        len: usize,
+        /// The maximum length, [`anoma::types::storage::MAX_KEY_LEN`]
+        max: usize,
+    },
+    /// The iterator ID is not known to the host, i.e. it hasn't been created
+    /// in this transaction or it has been invalidated by a write or a delete
+    /// of a key under its prefix
+    InvalidIterator {
+        /// The ID of the iterator
+        iter_id: u64,
+    },
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvError::Encode => write!(f, "Value encoding failed"),
+            EnvError::Decode => write!(f, "Value decoding failed"),
+            EnvError::Host(error) => {
+                write!(f, "The host rejected the call: {}", error)
+            }
+            EnvError::NotFound => write!(f, "The value is not present"),
+        }
+    }
+}
+
+impl fmt::Display for HostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HostError::InvalidVpCode => {
+                write!(f, "Invalid validity predicate code")
+            }
+            HostError::ImmutableKey => write!(f, "The key is immutable"),
+            HostError::AccountExists => write!(f, "The account already exists"),
+            HostError::UnknownTemplate => {
+                write!(f, "Unknown validity predicate template")
+            }
+            HostError::KeyTooLong { len, max } => {
+                write!(f, "The key length {} exceeds the maximum {}", len, max)
+            }
+            HostError::InvalidIterator { iter_id } => {
+                write!(f, "Invalid iterator ID {}", iter_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EnvError {}
+
+impl From<HostError> for EnvError {
+    fn from(error: HostError) -> Self {
+        EnvError::Host(error)
+    }
+}
@@ -4,6 +4,8 @@ use anoma::types::internal::HostEnvResult;
 use anoma::vm::types::KeyVal;
 use borsh::BorshDeserialize;
 
+use crate::error::EnvError;
+
 /// This function is a helper to handle the second step of reading var-len
 /// values from the host.
 ///
@@ -32,20 +34,59 @@ fn read_from_buffer(
     }
 }
 
+/// This function is a helper to decode a value read from the host with Borsh.
+/// A missing value is reported as [`EnvError::NotFound`].
+fn try_decode<T: BorshDeserialize>(
+    value: Option<Vec<u8>>,
+) -> Result<T, EnvError> {
+    let value = value.ok_or(EnvError::NotFound)?;
+    T::try_from_slice(&value[..]).map_err(|_| EnvError::Decode)
+}
+
 /// This function is a helper to handle the second step of reading var-len
 /// values in a key-value pair from the host.
 fn read_key_val_from_buffer<T: BorshDeserialize>(
     read_result: i64,
     result_buffer: unsafe extern "C" fn(u64),
 ) -> Option<(String, T)> {
-    let key_val = read_from_buffer(read_result, result_buffer)
-        .and_then(|t| KeyVal::try_from_slice(&t[..]).ok());
-    key_val.and_then(|key_val| {
-        // decode the value
-        T::try_from_slice(&key_val.val)
-            .map(|val| (key_val.key, val))
-            .ok()
-    })
+    read_raw_key_val_from_buffer(read_result, result_buffer).and_then(
+        |(key, val)| {
+            // decode the value
+            T::try_from_slice(&val).map(|val| (key, val)).ok()
+        },
+    )
+}
+
+/// This function is a helper to handle the second step of reading var-len
+/// values in a key-value pair from the host, without decoding the value.
+fn read_raw_key_val_from_buffer(
+    read_result: i64,
+    result_buffer: unsafe extern "C" fn(u64),
+) -> Option<(String, Vec<u8>)> {
+    read_from_buffer(read_result, result_buffer)
+        .and_then(|t| KeyVal::try_from_slice(&t[..]).ok())
+        .map(|key_val| (key_val.key, key_val.val))
+}
+
+/// Execution context imports, available in both the transaction and the
+/// validity predicate environment
+pub mod context {
+    pub use anoma::types::internal::ExecContext;
+
+    /// Get the kind of wasm code that is currently being executed. This can
+    /// be used by library code shared between transactions and validity
+    /// predicates.
+    pub fn current() -> ExecContext {
+        let context = unsafe { anoma_exec_context() };
+        ExecContext::from_u64(context)
+            .expect("The host should return a valid execution context")
+    }
+
+    // Import from the host environment for both the tx and VP
+    extern "C" {
+        // Get the current execution context
+        fn anoma_exec_context() -> u64;
+    }
 }
 
 /// Transaction environment imports
@@ -54,17 +95,23 @@ pub mod tx {
     use std::convert::TryFrom;
     use std::marker::PhantomData;
 
+    pub use anoma::ledger::gas::GasOp;
     use anoma::types::address;
     use anoma::types::address::Address;
     use anoma::types::chain::CHAIN_ID_LENGTH;
+    use anoma::types::event::TxEvent;
+    use anoma::types::hash::Hash;
     use anoma::types::ibc::IbcEvent;
     use anoma::types::internal::HostEnvResult;
     use anoma::types::storage::{
-        BlockHash, BlockHeight, Epoch, BLOCK_HASH_LENGTH,
+        BlockHash, BlockHeight, Epoch, BLOCK_HASH_LENGTH, MAX_KEY_LEN,
     };
     use anoma::types::time::Rfc3339String;
+    use anoma::types::transaction::ScheduledTx;
     pub use borsh::{BorshDeserialize, BorshSerialize};
 
+    pub use crate::error::{EnvError, HostError};
+
     #[derive(Debug)]
     pub struct KeyValIterator<T>(pub u64, pub PhantomData<T>);
 
@@ -87,6 +134,15 @@ pub mod tx {
         super::read_from_buffer(read_result, anoma_tx_result_buffer)
     }
 
+    /// Read a Borsh encoded variable-length value at the given key from
+    /// storage. Unlike [`read`], a missing and a malformed value are
+    /// distinguished in the error.
+    pub fn try_read<T: BorshDeserialize>(
+        key: impl AsRef<str>,
+    ) -> Result<T, EnvError> {
+        super::try_decode(read_bytes(key))
+    }
+
     /// Check if the given key is present in storage.
     pub fn has_key(key: impl AsRef<str>) -> bool {
         let key = key.as_ref();
@@ -101,6 +157,51 @@ pub mod tx {
         write_bytes(key, buf);
     }
 
+    /// Update the value encoded with Borsh at the given key in storage. The
+    /// function `f` is applied to the current value, or to the `default` if
+    /// the key is not present, and its result is written back.
+    pub fn update<T, F>(key: impl AsRef<str>, default: T, f: F)
+    where
+        T: BorshSerialize + BorshDeserialize,
+        F: FnOnce(T) -> T,
+    {
+        let key = key.as_ref();
+        let current = read(key).unwrap_or(default);
+        write(key, f(current));
+    }
+
+    /// Write a value to be encoded with Borsh at the given key to storage, if
+    /// the key doesn't exceed the [`MAX_KEY_LEN`]. Unlike [`write`], which
+    /// aborts the transaction on an over-length key, the key is checked before
+    /// it's written.
+    pub fn write_checked<T: BorshSerialize>(
+        key: impl AsRef<str>,
+        val: T,
+    ) -> Result<(), EnvError> {
+        let key = key.as_ref();
+        if key.len() > MAX_KEY_LEN {
+            return Err(HostError::KeyTooLong {
+                len: key.len(),
+                max: MAX_KEY_LEN,
+            }
+            .into());
+        }
+        write(key, val);
+        Ok(())
+    }
+
+    /// Write a value to be encoded with Borsh at the given key to storage.
+    /// Unlike [`write`], a value that cannot be encoded is reported in the
+    /// error.
+    pub fn try_write<T: BorshSerialize>(
+        key: impl AsRef<str>,
+        val: T,
+    ) -> Result<(), EnvError> {
+        let buf = val.try_to_vec().map_err(|_| EnvError::Encode)?;
+        write_bytes(key, buf);
+        Ok(())
+    }
+
     /// Write a value as bytes at the given key to storage.
     pub fn write_bytes(key: impl AsRef<str>, val: impl AsRef<[u8]>) {
         let key = key.as_ref();
@@ -114,6 +215,51 @@ pub mod tx {
         };
     }
 
+    /// Write a value to be encoded with Borsh at the given key to storage. The
+    /// value is removed from storage after `ttl_blocks` blocks.
+    pub fn write_with_ttl<T: BorshSerialize>(
+        key: impl AsRef<str>,
+        val: T,
+        ttl_blocks: u64,
+    ) {
+        let buf = val.try_to_vec().unwrap();
+        let key = key.as_ref();
+        unsafe {
+            anoma_tx_write_with_ttl(
+                key.as_ptr() as _,
+                key.len() as _,
+                buf.as_ptr() as _,
+                buf.len() as _,
+                ttl_blocks,
+            )
+        };
+    }
+
+    /// Write an immutable value to be encoded with Borsh at the given key to
+    /// storage. The value is written only if the key is not present. Once
+    /// written, any modification of the key is rejected by the host, which
+    /// aborts the transaction.
+    pub fn write_immutable<T: BorshSerialize>(
+        key: impl AsRef<str>,
+        val: T,
+    ) -> Result<(), EnvError> {
+        let key = key.as_ref();
+        let buf = val.try_to_vec().unwrap();
+        let result = unsafe {
+            anoma_tx_write_immutable(
+                key.as_ptr() as _,
+                key.len() as _,
+                buf.as_ptr() as _,
+                buf.len() as _,
+            )
+        };
+        if HostEnvResult::is_success(result) {
+            Ok(())
+        } else {
+            Err(HostError::ImmutableKey.into())
+        }
+    }
+
     /// Write a temporary value to be encoded with Borsh at the given key to
     /// storage.
     pub fn write_temp<T: BorshSerialize>(key: impl AsRef<str>, val: T) {
@@ -147,6 +293,11 @@ pub mod tx {
     /// applied. It will only find keys that are already committed to
     /// storage (i.e. from predecessor blocks). However, it will provide the
     /// most up-to-date value for such keys.
+    ///
+    /// The set of keys is a snapshot of the committed storage taken when the
+    /// iterator is created (see [`KeyValIterator::snapshot_height`]). Keys
+    /// committed to storage after that are not observed by the iterator.
+    /// Writing or deleting a key under the prefix invalidates the iterator.
     pub fn iter_prefix<T: BorshDeserialize>(
         prefix: impl AsRef<str>,
     ) -> KeyValIterator<T> {
@@ -157,6 +308,35 @@ pub mod tx {
         KeyValIterator(iter_id, PhantomData)
     }
 
+    impl<T> KeyValIterator<T> {
+        /// Check if the iterator is known to the host.
+        pub fn is_valid(&self) -> bool {
+            let valid = unsafe { anoma_tx_iter_is_valid(self.0) };
+            HostEnvResult::is_success(valid)
+        }
+
+        /// Get the height of the committed block whose state this iterator
+        /// reflects. Returns `None` when the iterator is not known to the
+        /// host.
+        pub fn snapshot_height(&self) -> Option<BlockHeight> {
+            let height = unsafe { anoma_tx_iter_snapshot_height(self.0) };
+            u64::try_from(height).ok().map(BlockHeight)
+        }
+
+        /// Get the error of the iterator, if any. When the iterator's `next`
+        /// returns `None` and this returns `None` too, the iterator has been
+        /// exhausted. Otherwise, it has been invalidated by a conflicting
+        /// write or its ID is not known to the host, which is reported as
+        /// [`HostError::InvalidIterator`].
+        pub fn last_error(&self) -> Option<EnvError> {
+            if self.is_valid() {
+                None
+            } else {
+                Some(HostError::InvalidIterator { iter_id: self.0 }.into())
+            }
+        }
+    }
+
     impl<T: BorshDeserialize> Iterator for KeyValIterator<T> {
         type Item = (String, T);
 
@@ -191,6 +371,31 @@ pub mod tx {
         };
     }
 
+    /// Update a validity predicate after checking that the code is a valid
+    /// wasm that exports the validity predicate entrypoint. Unlike
+    /// [`update_validity_predicate`], an invalid code doesn't abort the
+    /// transaction and the validity predicate is left unchanged.
+    pub fn update_validity_predicate_validated(
+        addr: &Address,
+        code: impl AsRef<[u8]>,
+    ) -> Result<(), EnvError> {
+        let addr = addr.encode();
+        let code = code.as_ref();
+        let result = unsafe {
+            anoma_tx_update_validity_predicate_validated(
+                addr.as_ptr() as _,
+                addr.len() as _,
+                code.as_ptr() as _,
+                code.len() as _,
+            )
+        };
+        if HostEnvResult::is_success(result) {
+            Ok(())
+        } else {
+            Err(HostError::InvalidVpCode.into())
+        }
+    }
+
     // Initialize a new account
     pub fn init_account(code: impl AsRef<[u8]>) -> Address {
         let code = code.as_ref();
@@ -212,6 +417,62 @@ pub mod tx {
             .expect("Decoding address created by the ledger shouldn't fail")
     }
 
+    /// Initialize a new account at the address derived from the `creator`'s
+    /// address and the `salt` (see [`Address::derive_sub`]), so that the
+    /// address is known before the account is created. The `creator`'s
+    /// validity predicate is triggered by the transaction.
+    pub fn init_account_at(
+        creator: &Address,
+        code: impl AsRef<[u8]>,
+        salt: &[u8],
+    ) -> Result<Address, EnvError> {
+        let creator_str = creator.encode();
+        let code = code.as_ref();
+        let result = unsafe {
+            anoma_tx_init_account_at(
+                creator_str.as_ptr() as _,
+                creator_str.len() as _,
+                code.as_ptr() as _,
+                code.len() as _,
+                salt.as_ptr() as _,
+                salt.len() as _,
+            )
+        };
+        if HostEnvResult::is_success(result) {
+            Ok(creator.derive_sub(salt))
+        } else {
+            Err(HostError::AccountExists.into())
+        }
+    }
+
+    /// Initialize a new account with the validity predicate template with the
+    /// given code hash. The template's code is resolved by the ledger, so that
+    /// only the whitelisted account types can be created.
+    pub fn init_account_from_template(
+        template_hash: &Hash,
+    ) -> Result<Address, EnvError> {
+        let hash = template_hash.try_to_vec().unwrap();
+        let result = Vec::with_capacity(address::ESTABLISHED_ADDRESS_BYTES_LEN);
+        let init_result = unsafe {
+            anoma_tx_init_account_from_template(
+                hash.as_ptr() as _,
+                hash.len() as _,
+                result.as_ptr() as _,
+            )
+        };
+        if !HostEnvResult::is_success(init_result) {
+            return Err(HostError::UnknownTemplate.into());
+        }
+        let slice = unsafe {
+            slice::from_raw_parts(
+                result.as_ptr(),
+                address::ESTABLISHED_ADDRESS_BYTES_LEN,
+            )
+        };
+        Ok(Address::try_from_slice(slice)
+            .expect("Decoding address created by the ledger shouldn't fail"))
+    }
+
     /// Emit an IBC event. There can be only one event per transaction. On
     /// multiple calls, only the last emitted event will be used.
     pub fn emit_ibc_event(event: &IbcEvent) {
@@ -221,6 +482,54 @@ pub mod tx {
         };
     }
 
+    /// Emit an event with the given type and attributes. A transaction may
+    /// emit any number of events, they are surfaced in the order of emission.
+    pub fn emit_event(event_type: &str, attributes: &[(String, String)]) {
+        let event = TxEvent {
+            event_type: event_type.to_owned(),
+            attributes: attributes.to_vec(),
+        };
+        let event = BorshSerialize::try_to_vec(&event).unwrap();
+        unsafe { anoma_tx_emit_event(event.as_ptr() as _, event.len() as _) };
+    }
+
+    /// Schedule a transaction with the given code and data to be applied at
+    /// the beginning of the block at the given height, which must be after
+    /// the current block height. The transaction is stored in the scheduler's
+    /// sub-space, so its validity predicate must authorize it. Returns the ID
+    /// of the scheduled transaction.
+    pub fn schedule_tx(
+        scheduler: &Address,
+        code: Vec<u8>,
+        data: Option<Vec<u8>>,
+        at_height: BlockHeight,
+    ) -> u64 {
+        let tx = ScheduledTx {
+            scheduler: scheduler.clone(),
+            code,
+            data,
+            height: at_height,
+        };
+        let tx = BorshSerialize::try_to_vec(&tx).unwrap();
+        unsafe { anoma_tx_schedule(tx.as_ptr() as _, tx.len() as _) }
+    }
+
+    /// Cancel the transaction with the given ID, as returned from
+    /// [`schedule_tx`], scheduled by the given scheduler. Like scheduling, the
+    /// cancellation must be authorized by the scheduler's validity predicate.
+    /// Returns `true` if the transaction was found and canceled.
+    pub fn cancel_scheduled(scheduler: &Address, id: u64) -> bool {
+        let scheduler = scheduler.encode();
+        let result = unsafe {
+            anoma_tx_cancel_scheduled(
+                scheduler.as_ptr() as _,
+                scheduler.len() as _,
+                id,
+            )
+        };
+        HostEnvResult::is_success(result)
+    }
+
     /// Get the chain ID
     pub fn get_chain_id() -> String {
         let result = Vec::with_capacity(CHAIN_ID_LENGTH);
@@ -261,11 +570,28 @@ pub mod tx {
         BlockHash::try_from(slice).expect("Cannot convert the hash")
     }
 
+    /// Get the randomness of the current block, derived from its hash and
+    /// height. It's deterministic, so it's the same for all the nodes
+    /// applying the block, but it's not unpredictable.
+    pub fn block_randomness() -> [u8; 32] {
+        let mut result = [0; 32];
+        unsafe {
+            anoma_tx_get_block_randomness(result.as_mut_ptr() as _);
+        }
+        result
+    }
+
     /// Get epoch of the current block
     pub fn get_block_epoch() -> Epoch {
         Epoch(unsafe { anoma_tx_get_block_epoch() })
     }
 
+    /// Get the configured gas cost per byte of the given operation
+    pub fn gas_cost(op: GasOp) -> u64 {
+        let cost = unsafe { anoma_tx_gas_cost(op.to_u64()) };
+        u64::try_from(cost).expect("The gas operation should be known")
+    }
+
     /// Log a string. The message will be printed at the `tracing::Level::Info`.
     pub fn log_string<T: AsRef<str>>(msg: T) {
         let msg = msg.as_ref();
@@ -306,6 +632,23 @@ pub mod tx {
             val_len: u64,
         );
 
+        // Write an immutable key/value, if the key is not present
+        fn anoma_tx_write_immutable(
+            key_ptr: u64,
+            key_len: u64,
+            val_ptr: u64,
+            val_len: u64,
+        ) -> i64;
+
+        // Write a key/value with a time-to-live in number of blocks
+        fn anoma_tx_write_with_ttl(
+            key_ptr: u64,
+            key_len: u64,
+            val_ptr: u64,
+            val_len: u64,
+            ttl_blocks: u64,
+        );
+
         // Delete the given key and its value
         fn anoma_tx_delete(key_ptr: u64, key_len: u64);
 
@@ -318,6 +661,14 @@ pub mod tx {
         // its size.
         fn anoma_tx_iter_next(iter_id: u64) -> i64;
 
+        // Returns 1 if the iterator with the given ID is known to the host,
+        // -1 otherwise.
+        fn anoma_tx_iter_is_valid(iter_id: u64) -> i64;
+
+        // Get the snapshot height of an iterator, returns -1 if the iterator
+        // is not known
+        fn anoma_tx_iter_snapshot_height(iter_id: u64) -> i64;
+
         // Insert a verifier
         fn anoma_tx_insert_verifier(addr_ptr: u64, addr_len: u64);
 
@@ -329,12 +680,52 @@ pub mod tx {
             code_len: u64,
         );
 
+        // Update a validity predicate, if the code is a valid VP wasm
+        fn anoma_tx_update_validity_predicate_validated(
+            addr_ptr: u64,
+            addr_len: u64,
+            code_ptr: u64,
+            code_len: u64,
+        ) -> i64;
+
         // Initialize a new account
         fn anoma_tx_init_account(code_ptr: u64, code_len: u64, result_ptr: u64);
 
+        // Initialize a new account at the address derived from the creator's
+        // address and the salt, returns -1 if the address already exists
+        fn anoma_tx_init_account_at(
+            creator_ptr: u64,
+            creator_len: u64,
+            code_ptr: u64,
+            code_len: u64,
+            salt_ptr: u64,
+            salt_len: u64,
+        ) -> i64;
+
+        // Initialize a new account with a whitelisted validity predicate
+        // template, returns -1 if the template is not found
+        fn anoma_tx_init_account_from_template(
+            hash_ptr: u64,
+            hash_len: u64,
+            result_ptr: u64,
+        ) -> i64;
+
         // Emit an IBC event
         fn anoma_tx_emit_ibc_event(event_ptr: u64, event_len: u64);
 
+        // Emit an event
+        fn anoma_tx_emit_event(event_ptr: u64, event_len: u64);
+
+        // Schedule a transaction for a future block, returns its ID
+        fn anoma_tx_schedule(tx_ptr: u64, tx_len: u64) -> u64;
+
+        // Cancel a scheduled transaction, returns -1 if it's not found
+        fn anoma_tx_cancel_scheduled(
+            scheduler_ptr: u64,
+            scheduler_len: u64,
+            id: u64,
+        ) -> i64;
+
         // Get the chain ID
         fn anoma_tx_get_chain_id(result_ptr: u64);
 
@@ -347,9 +738,16 @@ pub mod tx {
         // Get the current block hash
         fn anoma_tx_get_block_hash(result_ptr: u64);
 
+        // Get the randomness of the current block
+        fn anoma_tx_get_block_randomness(result_ptr: u64);
+
         // Get the current block epoch
         fn anoma_tx_get_block_epoch() -> u64;
 
+        // Get the gas cost per byte of an operation, returns -1 if the
+        // operation is unknown
+        fn anoma_tx_gas_cost(op: u64) -> i64;
+
         // Requires a node running with "Info" log level
         fn anoma_tx_log_string(str_ptr: u64, str_len: u64);
     }
@@ -361,19 +759,34 @@ pub mod vp {
     use std::convert::TryFrom;
     use std::marker::PhantomData;
 
+    pub use anoma::ledger::gas::GasOp;
+    use anoma::types::address::Address;
     use anoma::types::chain::CHAIN_ID_LENGTH;
     use anoma::types::hash::{Hash, HASH_LENGTH};
     use anoma::types::internal::HostEnvResult;
     use anoma::types::key::*;
     use anoma::types::storage::{
-        BlockHash, BlockHeight, Epoch, BLOCK_HASH_LENGTH,
+        BlockHash, BlockHeight, DbKeySeg, Epoch, Key, BLOCK_HASH_LENGTH,
     };
+    use anoma::types::token;
     pub use borsh::{BorshDeserialize, BorshSerialize};
 
+    pub use crate::error::EnvError;
+
     pub struct PreKeyValIterator<T>(pub u64, pub PhantomData<T>);
 
     pub struct PostKeyValIterator<T>(pub u64, pub PhantomData<T>);
 
+    /// A prefix iterator of the posterior state, like [`PostKeyValIterator`],
+    /// which reads the items into a buffer that's reused for all of them,
+    /// instead of allocating a new key for every item. Because the keys borrow
+    /// the buffer, this is not an [`Iterator`].
+    pub struct PostKeyValBorrowedIterator<T> {
+        iter_id: u64,
+        buffer: Vec<u8>,
+        phantom: PhantomData<T>,
+    }
+
     /// Try to read a Borsh encoded variable-length value at the given key from
     /// storage before transaction execution.
     pub fn read_pre<T: BorshDeserialize>(key: impl AsRef<str>) -> Option<T> {
@@ -412,6 +825,45 @@ pub mod vp {
         super::read_from_buffer(read_result, anoma_vp_result_buffer)
     }
 
+    /// Read a Borsh encoded variable-length value at the given key from
+    /// storage before transaction execution. Unlike [`read_pre`], a missing
+    /// and a malformed value are distinguished in the error.
+    pub fn try_read_pre<T: BorshDeserialize>(
+        key: impl AsRef<str>,
+    ) -> Result<T, EnvError> {
+        super::try_decode(read_bytes_pre(key))
+    }
+
+    /// Read a Borsh encoded variable-length value at the given key from
+    /// storage after transaction execution. Unlike [`read_post`], a missing
+    /// and a malformed value are distinguished in the error.
+    pub fn try_read_post<T: BorshDeserialize>(
+        key: impl AsRef<str>,
+    ) -> Result<T, EnvError> {
+        super::try_decode(read_bytes_post(key))
+    }
+
+    /// Try to read a range of at most `len` bytes starting at the `offset` of
+    /// the value at the given key from storage after transaction execution,
+    /// without reading the whole value. Returns `None` if the key is not
+    /// present or the `offset` is beyond the end of the value.
+    pub fn read_post_range(
+        key: impl AsRef<str>,
+        offset: usize,
+        len: usize,
+    ) -> Option<Vec<u8>> {
+        let key = key.as_ref();
+        let read_result = unsafe {
+            anoma_vp_read_post_range(
+                key.as_ptr() as _,
+                key.len() as _,
+                offset as _,
+                len as _,
+            )
+        };
+        super::read_from_buffer(read_result, anoma_vp_result_buffer)
+    }
+
     /// Try to read a Borsh encoded variable-length value at the given key from
     /// storage before transaction execution.
     pub fn read_temp<T: BorshDeserialize>(key: impl AsRef<str>) -> Option<T> {
@@ -489,6 +941,82 @@ pub mod vp {
         }
     }
 
+    /// Get an iterator with the given prefix after transaction execution, whose
+    /// items borrow the iterator's buffer. A key returned from
+    /// [`PostKeyValBorrowedIterator::next`] is only valid until the next call
+    /// of `next`.
+    pub fn iter_prefix_borrowed<T: BorshDeserialize>(
+        prefix: impl AsRef<str>,
+    ) -> PostKeyValBorrowedIterator<T> {
+        let prefix = prefix.as_ref();
+        let iter_id = unsafe {
+            anoma_vp_iter_prefix(prefix.as_ptr() as _, prefix.len() as _)
+        };
+        PostKeyValBorrowedIterator {
+            iter_id,
+            buffer: Vec::new(),
+            phantom: PhantomData,
+        }
+    }
+
+    impl<T: BorshDeserialize> PostKeyValBorrowedIterator<T> {
+        /// Get the next key and value. The key borrows the iterator's buffer,
+        /// which is overwritten by the next call.
+        #[allow(clippy::should_implement_trait)]
+        pub fn next(&mut self) -> Option<(&str, T)> {
+            let read_result = unsafe { anoma_vp_iter_post_next(self.iter_id) };
+            if HostEnvResult::is_fail(read_result) {
+                return None;
+            }
+            let len = read_result as usize;
+            self.buffer.clear();
+            self.buffer.reserve(len);
+            unsafe {
+                anoma_vp_result_buffer(self.buffer.as_mut_ptr() as _);
+                self.buffer.set_len(len);
+            }
+            // The buffer holds a Borsh encoded `KeyVal`, in which both the key
+            // and the value are prefixed with their length
+            let mut bytes = &self.buffer[..];
+            let key_len = u32::deserialize(&mut bytes).ok()? as usize;
+            let key = std::str::from_utf8(bytes.get(..key_len)?).ok()?;
+            let mut bytes = &bytes[key_len..];
+            let val_len = u32::deserialize(&mut bytes).ok()? as usize;
+            let val = T::try_from_slice(bytes.get(..val_len)?).ok()?;
+            Some((key, val))
+        }
+    }
+
+    /// Get an iterator with the given prefix after transaction execution that
+    /// yields the values as undecoded bytes. Unlike the typed iterators, this
+    /// can be used for a prefix whose keys hold values of different types, by
+    /// choosing the decoder from the key.
+    pub fn iter_prefix_raw(
+        prefix: impl AsRef<str>,
+    ) -> impl Iterator<Item = (String, Vec<u8>)> {
+        let prefix = prefix.as_ref();
+        let iter_id = unsafe {
+            anoma_vp_iter_prefix(prefix.as_ptr() as _, prefix.len() as _)
+        };
+        std::iter::from_fn(move || {
+            let read_result = unsafe { anoma_vp_iter_post_next(iter_id) };
+            super::read_raw_key_val_from_buffer(
+                read_result,
+                anoma_vp_result_buffer,
+            )
+        })
+    }
+
+    /// Sum the `u64` values of all the keys with the given prefix after
+    /// transaction execution. Returns `None` if any of the values cannot be
+    /// decoded as `u64` or if the sum overflows.
+    pub fn sum_prefix_post(prefix: impl AsRef<str>) -> Option<u64> {
+        iter_prefix_raw(prefix).try_fold(0_u64, |sum, (_key, val)| {
+            let val = u64::try_from_slice(&val[..]).ok()?;
+            sum.checked_add(val)
+        })
+    }
+
     /// Get the chain ID
     pub fn get_chain_id() -> String {
         let result = Vec::with_capacity(CHAIN_ID_LENGTH);
@@ -528,11 +1056,108 @@ pub mod vp {
         Hash::try_from(slice).expect("Cannot convert the hash")
     }
 
+    /// Get the merkle root of the block before the current one, if any. At
+    /// genesis, there is no previous block and this returns `None`.
+    pub fn get_prev_block_root() -> Option<Hash> {
+        let result = Vec::with_capacity(HASH_LENGTH);
+        let found =
+            unsafe { anoma_vp_get_prev_block_root(result.as_ptr() as _) };
+        if HostEnvResult::is_fail(found) {
+            return None;
+        }
+        let slice =
+            unsafe { slice::from_raw_parts(result.as_ptr(), HASH_LENGTH) };
+        Some(Hash::try_from(slice).expect("Cannot convert the root"))
+    }
+
+    /// Get the address of the transaction's signer among the given
+    /// `candidates`, e.g. the addresses the transaction's data refers to. The
+    /// signer is the first candidate with a public key registered on chain
+    /// that verifies the transaction's signature. Returns `None` if the
+    /// transaction is not signed or none of the candidates signed it.
+    pub fn get_tx_signer(candidates: &[Address]) -> Option<Address> {
+        let candidates = candidates.try_to_vec().unwrap();
+        let result = unsafe {
+            anoma_vp_get_tx_signer(
+                candidates.as_ptr() as _,
+                candidates.len() as _,
+            )
+        };
+        super::read_from_buffer(result, anoma_vp_result_buffer)
+            .and_then(|t| Address::try_from_slice(&t[..]).ok())
+    }
+
     /// Get epoch of the current block
     pub fn get_block_epoch() -> Epoch {
         Epoch(unsafe { anoma_vp_get_block_epoch() })
     }
 
+    /// Get the configured gas cost per byte of the given operation
+    pub fn gas_cost(op: GasOp) -> u64 {
+        let cost = unsafe { anoma_vp_gas_cost(op.to_u64()) };
+        u64::try_from(cost).expect("The gas operation should be known")
+    }
+
+    /// Get the gas limit of the transaction being validated, as enforced by
+    /// the host's gas meter. The budget is shared by the transaction and all
+    /// the VPs it triggers.
+    pub fn tx_gas_limit() -> u64 {
+        unsafe { anoma_vp_tx_gas_limit() }
+    }
+
+    /// Get all the storage keys changed by the current transaction, including
+    /// the keys that are not relevant to this VP's account.
+    pub fn all_changed_keys() -> Vec<String> {
+        let read_result = unsafe { anoma_vp_get_changed_keys() };
+        super::read_from_buffer(read_result, anoma_vp_result_buffer)
+            .and_then(|t| Vec::<String>::try_from_slice(&t[..]).ok())
+            .unwrap_or_default()
+    }
+
+    /// Get the storage keys changed by the current transaction that start
+    /// with the given `prefix`, each with its decoded value before and after
+    /// the transaction. The prior value is `None` for a created key and the
+    /// posterior value is `None` for a deleted key.
+    pub fn changed_with_values_post<T: BorshDeserialize>(
+        prefix: impl AsRef<str>,
+    ) -> Vec<(String, Option<T>, Option<T>)> {
+        let prefix = prefix.as_ref();
+        all_changed_keys()
+            .into_iter()
+            .filter(|key| key.starts_with(prefix))
+            .map(|key| {
+                let pre = read_pre(&key);
+                let post = read_post(&key);
+                (key, pre, post)
+            })
+            .collect()
+    }
+
+    /// Parse the owner of the given storage key. A token balance is owned by
+    /// the balance's owner, while any other key is owned by the account whose
+    /// sub-space it's in. Returns `None` for a key that cannot be parsed or
+    /// that is not in any account's sub-space.
+    pub fn parse_owned_key(key: impl AsRef<str>) -> Option<Address> {
+        let key = Key::parse(key).ok()?;
+        match token::is_any_token_balance_key(&key) {
+            Some(owner) => Some(owner.clone()),
+            None => match key.segments.get(0) {
+                Some(DbKeySeg::AddressSeg(owner)) => Some(owner.clone()),
+                _ => None,
+            },
+        }
+    }
+
+    /// Check that all the storage keys changed by the current transaction are
+    /// owned by one of the given `owners`, as parsed by [`parse_owned_key`].
+    pub fn assert_all_changes_owned(owners: &[Address]) -> bool {
+        all_changed_keys().iter().all(|key| {
+            parse_owned_key(key)
+                .map(|owner| owners.contains(&owner))
+                .unwrap_or(false)
+        })
+    }
+
     /// Verify a transaction signature. The signature is expected to have been
     /// produced on the encoded transaction [`anoma::proto::Tx`]
     /// using [`anoma::proto::Tx::sign`].
@@ -553,6 +1178,60 @@ pub mod vp {
         HostEnvResult::is_success(valid)
     }
 
+    /// Verify a transaction signature like [`verify_tx_signature`]. When the
+    /// verification fails, the host logs its reason at the warning level.
+    pub fn verify_tx_signature_logged(
+        pk: &common::PublicKey,
+        sig: &common::Signature,
+    ) -> bool {
+        let pk = BorshSerialize::try_to_vec(pk).unwrap();
+        let sig = BorshSerialize::try_to_vec(sig).unwrap();
+        let valid = unsafe {
+            anoma_vp_verify_tx_signature_logged(
+                pk.as_ptr() as _,
+                pk.len() as _,
+                sig.as_ptr() as _,
+                sig.len() as _,
+            )
+        };
+        HostEnvResult::is_success(valid)
+    }
+
+    /// Verify a transaction signature like [`verify_tx_signature`], but the
+    /// host checks the signature in constant time with respect to its
+    /// validity, i.e. without an early return when any of the checks fails.
+    pub fn verify_tx_signature_ct(
+        pk: &common::PublicKey,
+        sig: &common::Signature,
+    ) -> bool {
+        let pk = BorshSerialize::try_to_vec(pk).unwrap();
+        let sig = BorshSerialize::try_to_vec(sig).unwrap();
+        let valid = unsafe {
+            anoma_vp_verify_tx_signature_ct(
+                pk.as_ptr() as _,
+                pk.len() as _,
+                sig.as_ptr() as _,
+                sig.len() as _,
+            )
+        };
+        HostEnvResult::is_success(valid)
+    }
+
+    /// Verify multiple transaction signatures, e.g. of the co-signers of a
+    /// multisig account, in one call. Returns the number of distinct public
+    /// keys with a signature that is valid over the current transaction, to
+    /// be compared against a threshold. A repeated public key is only counted
+    /// once.
+    pub fn verify_tx_signatures(
+        pairs: &[(common::PublicKey, common::Signature)],
+    ) -> usize {
+        let pairs = BorshSerialize::try_to_vec(pairs).unwrap();
+        let valid = unsafe {
+            anoma_vp_verify_tx_signatures(pairs.as_ptr() as _, pairs.len() as _)
+        };
+        valid as _
+    }
+
     /// Log a string. The message will be printed at the `tracing::Level::Info`.
     pub fn log_string<T: AsRef<str>>(msg: T) {
         let msg = msg.as_ref();
@@ -579,6 +1258,25 @@ pub mod vp {
         HostEnvResult::is_success(result)
     }
 
+    /// Evaluate the current validity predicate of the given account with the
+    /// given input data, to find out if it would accept it.
+    ///
+    /// If the account has no validity predicate or the execution fails for
+    /// whatever reason, this will return `false`. Otherwise returns the result
+    /// of evaluation.
+    pub fn would_accept(addr: &Address, input_data: Vec<u8>) -> bool {
+        let addr = addr.try_to_vec().unwrap();
+        let result = unsafe {
+            anoma_vp_would_accept(
+                addr.as_ptr() as _,
+                addr.len() as _,
+                input_data.as_ptr() as _,
+                input_data.len() as _,
+            )
+        };
+        HostEnvResult::is_success(result)
+    }
+
     // These host functions are implemented in the Anoma's [`host_env`]
     // module. The environment provides calls to them via this C interface.
     extern "C" {
@@ -596,6 +1294,17 @@ pub mod vp {
         // we know its size.
         fn anoma_vp_read_post(key_ptr: u64, key_len: u64) -> i64;
 
+        // Read a range of variable-length posterior state, returns the size of
+        // the range (can be 0), or -1 if the key is not present or the offset
+        // is beyond the end of the value. If a range is found, it will be
+        // placed in the result buffer.
+        fn anoma_vp_read_post_range(
+            key_ptr: u64,
+            key_len: u64,
+            offset: u64,
+            len: u64,
+        ) -> i64;
+
         // Read variable-length temporary state when we don't know the size
         // up-front, returns the size of the value (can be 0), or -1 if
         // the key is not present. If a value is found, it will be placed in the
@@ -641,9 +1350,33 @@ pub mod vp {
         // Get the current tx hash
         fn anoma_vp_get_tx_code_hash(result_ptr: u64);
 
+        // Get the previous block's merkle root, returns -1 if there is no
+        // previous block
+        fn anoma_vp_get_prev_block_root(result_ptr: u64) -> i64;
+
+        // Get the address of the tx signer among the candidates, returns -1 if
+        // it's not found. If it's found, it will be placed in the result
+        // buffer.
+        fn anoma_vp_get_tx_signer(
+            candidates_ptr: u64,
+            candidates_len: u64,
+        ) -> i64;
+
         // Get the current block epoch
         fn anoma_vp_get_block_epoch() -> u64;
 
+        // Get the gas cost per byte of an operation, returns -1 if the
+        // operation is unknown
+        fn anoma_vp_gas_cost(op: u64) -> i64;
+
+        // Get the gas limit of the current transaction
+        fn anoma_vp_tx_gas_limit() -> u64;
+
+        // Get all the storage keys changed by the current transaction, returns
+        // the size of the Borsh encoded keys, which are placed in the result
+        // buffer.
+        fn anoma_vp_get_changed_keys() -> i64;
+
         // Verify a transaction signature
         fn anoma_vp_verify_tx_signature(
             pk_ptr: u64,
@@ -652,6 +1385,26 @@ pub mod vp {
             sig_len: u64,
         ) -> i64;
 
+        // Verify a transaction signature and log the reason of a failure
+        fn anoma_vp_verify_tx_signature_logged(
+            pk_ptr: u64,
+            pk_len: u64,
+            sig_ptr: u64,
+            sig_len: u64,
+        ) -> i64;
+
+        // Verify a transaction signature in constant time
+        fn anoma_vp_verify_tx_signature_ct(
+            pk_ptr: u64,
+            pk_len: u64,
+            sig_ptr: u64,
+            sig_len: u64,
+        ) -> i64;
+
+        // Verify multiple transaction signatures, returns the number of
+        // distinct public keys with a valid signature
+        fn anoma_vp_verify_tx_signatures(sigs_ptr: u64, sigs_len: u64) -> i64;
+
         // Requires a node running with "Info" log level
         fn anoma_vp_log_string(str_ptr: u64, str_len: u64);
 
@@ -661,5 +1414,13 @@ pub mod vp {
             input_data_ptr: u64,
             input_data_len: u64,
         ) -> i64;
+
+        // Evaluate the current validity predicate of the given account
+        fn anoma_vp_would_accept(
+            addr_ptr: u64,
+            addr_len: u64,
+            input_data_ptr: u64,
+            input_data_len: u64,
+        ) -> i64;
     }
 }
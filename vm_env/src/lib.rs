@@ -6,6 +6,7 @@
 #![deny(rustdoc::broken_intra_doc_links)]
 #![deny(rustdoc::private_intra_doc_links)]
 
+pub mod error;
 pub mod governance;
 pub mod ibc;
 pub mod imports;
@@ -28,6 +29,7 @@ pub mod tx_prelude {
 
     pub use crate::governance::tx as governance;
     pub use crate::ibc::{Ibc, IbcActions};
+    pub use crate::imports::context;
     pub use crate::imports::tx::*;
     pub use crate::intent::tx as intent;
     pub use crate::nft::tx as nft;
@@ -47,6 +49,7 @@ pub mod vp_prelude {
     pub use anoma::types::*;
     pub use anoma_macros::validity_predicate;
 
+    pub use crate::imports::context;
     pub use crate::imports::vp::*;
     pub use crate::intent::vp as intent;
     pub use crate::key::vp as key;
@@ -59,6 +59,51 @@ pub mod vp {
         });
         all_checked && change == 0
     }
+
+    /// Check that the balance of the given token owned by the `owner` has
+    /// changed by exactly the `expected_delta`. A missing balance is treated
+    /// as zero.
+    pub fn assert_balance_delta(
+        owner: &Address,
+        token: &Address,
+        expected_delta: Change,
+    ) -> bool {
+        let key = token::balance_key(token, owner).to_string();
+        let pre: Amount = vp::read_pre(&key).unwrap_or_default();
+        let post: Amount = vp::read_post(&key).unwrap_or_default();
+        post.change() - pre.change() == expected_delta
+    }
+
+    /// Check that the balance of the given token owned by the `owner` after
+    /// the transaction is at least the `floor` in micro units, e.g. to
+    /// enforce a vesting lock. A missing balance is treated as zero.
+    pub fn assert_min_balance(
+        owner: &Address,
+        token: &Address,
+        floor: u64,
+    ) -> bool {
+        let key = token::balance_key(token, owner).to_string();
+        let post: Amount = vp::read_post(&key).unwrap_or_default();
+        u64::from(post) >= floor
+    }
+
+    /// Check that the sum of the balances of the given token owned by the
+    /// `accounts` is the same before and after the transaction, i.e. the
+    /// tokens have only been transferred between them. A missing balance is
+    /// treated as zero and any duplicate accounts are counted once.
+    pub fn assert_conservation(token: &Address, accounts: &[Address]) -> bool {
+        let accounts: BTreeSet<&Address> = accounts.iter().collect();
+        let change: Change = accounts
+            .into_iter()
+            .map(|owner| {
+                let key = token::balance_key(token, owner).to_string();
+                let pre: Amount = vp::read_pre(&key).unwrap_or_default();
+                let post: Amount = vp::read_post(&key).unwrap_or_default();
+                post.change() - pre.change()
+            })
+            .sum();
+        change == 0
+    }
 }
 
 /// Tx imports and functions.